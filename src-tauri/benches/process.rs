@@ -0,0 +1,39 @@
+//! 比较单线程与线程池并行生成多个变体的耗时
+//!
+//! 运行: `cargo bench --bench process`
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use ghostpcb_lib::gerber::{GerberProcessor, ProcessRequest};
+use std::path::Path;
+
+const SAMPLE_ZIP: &str = "tests/fixtures/Gerber.zip";
+const VARIANTS: u32 = 8;
+
+fn request(output_dir: &Path, threads: Option<usize>) -> ProcessRequest {
+    ProcessRequest {
+        input_path: SAMPLE_ZIP.to_string(),
+        output_dir: Some(output_dir.to_string_lossy().to_string()),
+        count: VARIANTS,
+        seed: Some(42),
+        threads,
+        ..Default::default()
+    }
+}
+
+fn bench_variants(c: &mut Criterion) {
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut group = c.benchmark_group("生成变体");
+    group.sample_size(10);
+
+    for (label, threads) in [("单线程", Some(1)), ("线程池", None)] {
+        let request = request(dir.path(), threads);
+        group.bench_with_input(BenchmarkId::new(label, VARIANTS), &request, |b, request| {
+            b.iter(|| GerberProcessor::process(request).unwrap())
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_variants);
+criterion_main!(benches);
@@ -1,11 +1,107 @@
+use crate::gerber::changelog::ChangeLog;
+use crate::gerber::presets::{Preset, PresetStore};
+use crate::gerber::progress::{new_job_id, JobRegistry, ProgressEvent};
 use crate::gerber::{GerberProcessor, ProcessRequest, ProcessResult};
 
+/// 进度事件名，前端通过 `listen` 订阅
+const PROGRESS_EVENT: &str = "gerber-progress";
+
+/// 在后台线程处理，期间发出进度事件；可通过 `cancel_job` 取消
+#[tauri::command]
+pub async fn process_gerber(
+    app: tauri::AppHandle,
+    jobs: tauri::State<'_, JobRegistry>,
+    mut request: ProcessRequest,
+) -> Result<ProcessResult, String> {
+    use tauri::Emitter;
+
+    let job_id = request.job_id.get_or_insert_with(new_job_id).clone();
+    let cancel = jobs.register(&job_id);
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        let emit = move |event: ProgressEvent| {
+            let _ = app.emit(PROGRESS_EVENT, event);
+        };
+        GerberProcessor::process_with(&request, &emit, &cancel)
+    })
+    .await;
+    jobs.finish(&job_id);
+
+    result
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())
+}
+
+/// 请求取消任务，任务不存在或已结束时返回 false
+#[tauri::command]
+pub fn cancel_job(jobs: tauri::State<'_, JobRegistry>, job_id: String) -> bool {
+    jobs.cancel(&job_id)
+}
+
 #[tauri::command]
-pub async fn process_gerber(request: ProcessRequest) -> Result<ProcessResult, String> {
-    GerberProcessor::process(&request).map_err(|e| e.to_string())
+pub async fn lint_gerber(
+    input_path: String,
+) -> Result<Vec<crate::gerber::lint::Diagnostic>, String> {
+    GerberProcessor::lint(std::path::Path::new(&input_path)).map_err(|e| e.to_string())
+}
+
+/// 依据输出包旁的修改记录还原原始文件，写入 `dest_path`
+#[tauri::command]
+pub async fn revert_package(output_path: String, dest_path: String) -> Result<(), String> {
+    let output = std::path::Path::new(&output_path);
+    ChangeLog::load(&ChangeLog::path_beside(output))
+        .and_then(|log| log.revert_package(output, std::path::Path::new(&dest_path)))
+        .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
 pub fn get_default_options() -> crate::gerber::ObfuscateOptions {
     crate::gerber::ObfuscateOptions::default()
 }
+
+#[tauri::command]
+pub fn list_profiles() -> Vec<crate::gerber::CapabilityProfile> {
+    crate::gerber::CapabilityProfile::builtin_profiles()
+}
+
+/// 预设保存在应用配置目录的 presets 子目录中
+fn preset_store(app: &tauri::AppHandle) -> Result<PresetStore, String> {
+    use tauri::Manager;
+    app.path()
+        .app_config_dir()
+        .map(|dir| PresetStore::new(dir.join("presets")))
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn list_presets(app: tauri::AppHandle) -> Result<Vec<Preset>, String> {
+    preset_store(&app)?.list().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn load_preset(app: tauri::AppHandle, name: String) -> Result<Preset, String> {
+    preset_store(&app)?.load(&name).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn save_preset(app: tauri::AppHandle, preset: Preset) -> Result<(), String> {
+    preset_store(&app)?.save(&preset).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn delete_preset(app: tauri::AppHandle, name: String) -> Result<(), String> {
+    preset_store(&app)?.delete(&name).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn import_preset(app: tauri::AppHandle, path: String) -> Result<Preset, String> {
+    preset_store(&app)?
+        .import(std::path::Path::new(&path))
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn export_preset(app: tauri::AppHandle, name: String, path: String) -> Result<(), String> {
+    preset_store(&app)?
+        .export(&name, std::path::Path::new(&path))
+        .map_err(|e| e.to_string())
+}
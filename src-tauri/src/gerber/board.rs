@@ -0,0 +1,377 @@
+use crate::gerber::encoding::TextEncoding;
+use crate::gerber::error::Result;
+use crate::gerber::layout::split_eol;
+use crate::gerber::transform::{parse_operations, transform_operations};
+use crate::gerber::types::GerberFileType;
+use crate::gerber::units::{CoordinateFormat, Length};
+use rand::{Rng, RngCore};
+use regex::Regex;
+use std::f64::consts::TAU;
+
+/// 与文件格式无关的坐标点
+pub type BoardPoint = (Length, Length);
+
+/// 一个输出包中的所有层，坐标变换以板为单位进行
+#[derive(Debug, Clone, Default)]
+pub struct Board {
+    pub layers: Vec<Layer>,
+}
+
+/// 单个 Gerber 或钻孔文件
+#[derive(Debug, Clone)]
+pub struct Layer {
+    /// 包内相对路径
+    pub name: String,
+    pub file_type: GerberFileType,
+    pub content: String,
+    /// 写回时使用的编码
+    pub encoding: TextEncoding,
+}
+
+/// 坐标的外接矩形
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Extent {
+    pub min_x: Length,
+    pub min_y: Length,
+    pub max_x: Length,
+    pub max_y: Length,
+}
+
+impl Extent {
+    pub(crate) fn from_points(points: &[BoardPoint]) -> Option<Self> {
+        let first = points.first()?;
+        let mut extent = Self {
+            min_x: first.0,
+            min_y: first.1,
+            max_x: first.0,
+            max_y: first.1,
+        };
+        for p in points {
+            extent.min_x = extent.min_x.min(p.0);
+            extent.min_y = extent.min_y.min(p.1);
+            extent.max_x = extent.max_x.max(p.0);
+            extent.max_y = extent.max_y.max(p.1);
+        }
+        Some(extent)
+    }
+
+    pub fn width(&self) -> Length {
+        self.max_x - self.min_x
+    }
+
+    pub fn height(&self) -> Length {
+        self.max_y - self.min_y
+    }
+}
+
+impl Board {
+    pub fn new(layers: Vec<Layer>) -> Self {
+        Self { layers }
+    }
+
+    /// 板子范围：优先取板框层，没有板框时取所有层
+    pub fn extent(&self) -> Option<Extent> {
+        let outline: Vec<BoardPoint> = self
+            .layers
+            .iter()
+            .filter(|l| l.file_type.is_outline())
+            .flat_map(|l| l.points())
+            .collect();
+        if let Some(extent) = Extent::from_points(&outline) {
+            return Some(extent);
+        }
+
+        let all: Vec<BoardPoint> = self.layers.iter().flat_map(|l| l.points()).collect();
+        Extent::from_points(&all)
+    }
+
+    /// 铜层叠层顺序：顶层、内层（按文件名）、底层
+    pub fn copper_stack(&self) -> Vec<&Layer> {
+        let rank = |t: GerberFileType| match t {
+            GerberFileType::TopLayer => 0,
+            GerberFileType::InnerLayer => 1,
+            _ => 2,
+        };
+        let mut stack: Vec<&Layer> = self
+            .layers
+            .iter()
+            .filter(|l| l.file_type.is_copper_layer())
+            .collect();
+        stack.sort_by(|a, b| {
+            rank(a.file_type)
+                .cmp(&rank(b.file_type))
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        stack
+    }
+
+    /// 把同一个位移场作用到所有层，同一位置的焊盘、开窗、钢网和孔移动相同的矢量
+    pub fn apply_field(&mut self, field: &DisplacementField) {
+        for layer in &mut self.layers {
+            layer.apply_field(field);
+        }
+    }
+}
+
+impl Layer {
+    pub fn new(name: &str, file_type: GerberFileType, content: String) -> Self {
+        Self {
+            name: name.to_string(),
+            file_type,
+            content,
+            encoding: TextEncoding::Utf8,
+        }
+    }
+
+    /// 从原始字节读入，自动识别编码
+    pub fn from_bytes(name: &str, file_type: GerberFileType, bytes: &[u8]) -> Self {
+        let (content, encoding) = TextEncoding::detect(bytes);
+        Self {
+            encoding,
+            ..Self::new(name, file_type, content)
+        }
+    }
+
+    /// 按读入时的编码写回字节
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        self.encoding.encode(&self.content)
+    }
+
+    /// 本层文件的坐标格式
+    pub fn format(&self) -> CoordinateFormat {
+        if self.file_type.is_drill() {
+            CoordinateFormat::excellon(&self.content)
+        } else {
+            CoordinateFormat::gerber(&self.content)
+        }
+    }
+
+    /// 所有操作点的坐标
+    pub fn points(&self) -> Vec<BoardPoint> {
+        match self.file_type {
+            GerberFileType::Unknown => Vec::new(),
+            GerberFileType::Drill => {
+                let mut points = Vec::new();
+                transform_excellon(&self.content, |p| {
+                    points.push(p);
+                    p
+                });
+                points
+            }
+            _ => {
+                let format = CoordinateFormat::gerber(&self.content);
+                parse_operations(&self.content)
+                    .iter()
+                    .map(|op| (format.from_raw(op.end.0), format.from_raw(op.end.1)))
+                    .collect()
+            }
+        }
+    }
+
+    /// 按位移场移动本层的所有操作点
+    pub fn apply_field(&mut self, field: &DisplacementField) {
+        self.transform(|p| {
+            let (dx, dy) = field.at(p);
+            (p.0 + dx, p.1 + dy)
+        });
+    }
+
+    /// 变换所有操作点，圆弧与区域轮廓由 transform 模块保持有效
+    pub fn transform<F>(&mut self, mut map: F)
+    where
+        F: FnMut(BoardPoint) -> BoardPoint,
+    {
+        self.content = match self.file_type {
+            GerberFileType::Unknown => return,
+            GerberFileType::Drill => transform_excellon(&self.content, map),
+            _ => {
+                let format = CoordinateFormat::gerber(&self.content);
+                transform_operations(&self.content, |op, _| {
+                    let (x, y) = map((format.from_raw(op.end.0), format.from_raw(op.end.1)));
+                    (format.to_raw(x), format.to_raw(y))
+                })
+            }
+        };
+    }
+}
+
+/// 变换 Excellon 钻孔文件中的坐标（含 G85 槽孔）
+///
+/// 缺省的轴沿用上一个坐标；按文件头的单位与补零方式读写。
+pub(crate) fn transform_excellon<F>(content: &str, mut map: F) -> String
+where
+    F: FnMut(BoardPoint) -> BoardPoint,
+{
+    let line_re = Regex::new(r"^(?:[XY][+-]?[\d.]+)+(?:G85(?:[XY][+-]?[\d.]+)+)?$").unwrap();
+    let coord_re = Regex::new(r"([XY])([+-]?[\d.]+)").unwrap();
+    let format = CoordinateFormat::excellon(content);
+
+    let mut current = (Length::ZERO, Length::ZERO);
+    let mut template = (String::new(), String::new());
+    let mut result = String::with_capacity(content.len());
+
+    for raw_line in content.split_inclusive('\n') {
+        let (body, eol) = split_eol(raw_line);
+        let trimmed = body.trim();
+        if !line_re.is_match(trimmed) {
+            result.push_str(raw_line);
+            continue;
+        }
+
+        let mut rewritten = String::with_capacity(trimmed.len());
+        for (n, part) in trimmed.split("G85").enumerate() {
+            if n > 0 {
+                rewritten.push_str("G85");
+            }
+
+            for caps in coord_re.captures_iter(part) {
+                let value = format.parse(&caps[2]).unwrap_or(Length::ZERO);
+                if &caps[1] == "X" {
+                    current.0 = value;
+                    template.0 = caps[2].to_string();
+                } else {
+                    current.1 = value;
+                    template.1 = caps[2].to_string();
+                }
+            }
+
+            let (x, y) = map(current);
+            if (x, y) == current {
+                rewritten.push_str(part);
+                continue;
+            }
+            // 位移后两个轴都可能变化，统一写出；缺省轴参照另一个轴的写法
+            let template_x = if template.0.is_empty() {
+                &template.1
+            } else {
+                &template.0
+            };
+            let template_y = if template.1.is_empty() {
+                &template.0
+            } else {
+                &template.1
+            };
+            rewritten.push('X');
+            rewritten.push_str(&format.format(x, template_x));
+            rewritten.push('Y');
+            rewritten.push_str(&format.format(y, template_y));
+        }
+
+        if rewritten == trimmed {
+            result.push_str(raw_line);
+        } else {
+            // 行首行尾的空白与换行原样保留
+            result.push_str(&body[..body.len() - body.trim_start().len()]);
+            result.push_str(&rewritten);
+            result.push_str(&body[body.trim_end().len()..]);
+            result.push_str(eol);
+        }
+    }
+
+    result
+}
+
+/// 单个正弦分量
+#[derive(Debug, Clone)]
+struct Wave {
+    /// 波矢 (弧度/毫米)
+    kx: f64,
+    ky: f64,
+    phase: f64,
+    weight_x: f64,
+    weight_y: f64,
+}
+
+/// 覆盖整块板的平滑位移场
+///
+/// 由若干长波长的正弦分量叠加而成，任意位置的位移不超过幅值。
+/// 波长不小于给定的最短波长，相距很近的两个图形位移几乎相同，
+/// 线距与焊环基本不受影响。
+#[derive(Debug, Clone)]
+pub struct DisplacementField {
+    amplitude: Length,
+    waves: Vec<Wave>,
+}
+
+const WAVE_COUNT: usize = 4;
+
+impl DisplacementField {
+    /// 不产生位移的场
+    pub fn zero() -> Self {
+        Self {
+            amplitude: Length::ZERO,
+            waves: Vec::new(),
+        }
+    }
+
+    /// 按板子尺寸随机生成位移场
+    pub fn random(
+        extent: Option<Extent>,
+        amplitude: Length,
+        min_wavelength: Length,
+        rng: &mut dyn RngCore,
+    ) -> Self {
+        let min_wavelength = min_wavelength.as_mm();
+        let span = extent
+            .map(|e| e.width().hypot(e.height()).as_mm())
+            .unwrap_or(0.0)
+            .max(min_wavelength * 2.0);
+
+        let waves = (0..WAVE_COUNT)
+            .map(|_| {
+                let wavelength = rng.gen_range(min_wavelength..=span);
+                let direction = rng.gen_range(0.0..TAU);
+                let k = TAU / wavelength;
+                Wave {
+                    kx: k * direction.cos(),
+                    ky: k * direction.sin(),
+                    phase: rng.gen_range(0.0..TAU),
+                    weight_x: rng.gen_range(-1.0..=1.0),
+                    weight_y: rng.gen_range(-1.0..=1.0),
+                }
+            })
+            .collect();
+
+        Self { amplitude, waves }
+    }
+
+    pub fn amplitude(&self) -> Length {
+        self.amplitude
+    }
+
+    /// 形状不变、幅值按比例缩放的场
+    pub fn scaled(&self, factor: f64) -> Self {
+        Self {
+            amplitude: self.amplitude * factor.max(0.0),
+            waves: self.waves.clone(),
+        }
+    }
+
+    /// 指定位置的位移 (dx, dy)，模长不超过幅值
+    pub fn at(&self, p: BoardPoint) -> BoardPoint {
+        let total_x: f64 = self.waves.iter().map(|w| w.weight_x.abs()).sum();
+        let total_y: f64 = self.waves.iter().map(|w| w.weight_y.abs()).sum();
+        if total_x == 0.0 && total_y == 0.0 {
+            return (Length::ZERO, Length::ZERO);
+        }
+
+        let (x, y) = (p.0.as_mm(), p.1.as_mm());
+        let (mut dx, mut dy) = (0.0, 0.0);
+        for w in &self.waves {
+            let s = (w.kx * x + w.ky * y + w.phase).sin();
+            dx += w.weight_x * s;
+            dy += w.weight_y * s;
+        }
+
+        // 每个轴限制在 A/√2 以内，合成位移不超过 A
+        let axis_max = self.amplitude * std::f64::consts::FRAC_1_SQRT_2;
+        let norm = |v: f64, total: f64| {
+            if total > 0.0 {
+                axis_max * (v / total)
+            } else {
+                Length::ZERO
+            }
+        };
+        (norm(dx, total_x), norm(dy, total_y))
+    }
+}
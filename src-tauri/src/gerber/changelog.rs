@@ -0,0 +1,108 @@
+use crate::gerber::board::{Board, Layer};
+use crate::gerber::edit::{apply_edits, edits_match, invert_edits, Edit};
+use crate::gerber::encoding::TextEncoding;
+use crate::gerber::error::{GhostPcbError, Result};
+use crate::gerber::output::write_file_atomic;
+use crate::gerber::package::{path_beside, Package};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 某个阶段对文件的逐行修改
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StageLog {
+    pub stage: String,
+    pub edits: Vec<Edit>,
+}
+
+/// 单个文件按执行顺序记录的全部修改
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileLog {
+    pub file: String,
+    /// 读入与写出该文件时使用的编码
+    #[serde(default)]
+    pub encoding: TextEncoding,
+    pub stages: Vec<StageLog>,
+}
+
+impl FileLog {
+    /// 按相反顺序撤销各阶段，得到处理前的文件内容
+    pub fn revert(&self, content: &str) -> Result<String> {
+        let mut content = content.to_string();
+        for stage in self.stages.iter().rev() {
+            let inverse = invert_edits(&stage.edits);
+            if !edits_match(&content, &inverse) {
+                return Err(GhostPcbError::ProcessError(format!(
+                    "{}: 文件内容与修改记录不符，无法撤销 {}",
+                    self.file, stage.stage
+                )));
+            }
+            content = apply_edits(&content, &inverse);
+        }
+        Ok(content)
+    }
+}
+
+/// 一个输出包的修改记录，保存在包旁的 `<包名>.changes.json`
+///
+/// 未记录的文件原样保留，撤销全部记录即可逐字节还原原始文件。
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChangeLog {
+    pub output_file: String,
+    pub files: Vec<FileLog>,
+}
+
+impl ChangeLog {
+    pub fn path_beside(zip_path: &Path) -> PathBuf {
+        path_beside(zip_path, "changes.json")
+    }
+
+    /// 以紧凑 JSON 写在输出包旁
+    pub fn write_beside(&self, zip_path: &Path) -> Result<PathBuf> {
+        let json =
+            serde_json::to_string(self).map_err(|e| GhostPcbError::ProcessError(e.to_string()))?;
+        let path = Self::path_beside(zip_path);
+        write_file_atomic(&path, json.as_bytes())?;
+        Ok(path)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)?;
+        serde_json::from_str(&text).map_err(|e| {
+            GhostPcbError::ProcessError(format!("修改记录无法解析 {}: {}", path.display(), e))
+        })
+    }
+
+    /// 还原单个文件；没有记录的文件原样返回
+    pub fn revert_file(&self, name: &str, content: &str) -> Result<String> {
+        match self.files.iter().find(|f| f.file == name) {
+            Some(log) => log.revert(content),
+            None => Ok(content.to_string()),
+        }
+    }
+
+    /// 读取输出包，撤销记录中的全部修改，按输出包的容器类型把原始文件写入 `dest`
+    pub fn revert_package(&self, output_zip: &Path, dest: &Path) -> Result<()> {
+        let package = Package::open(output_zip)?;
+        let mut layers = Vec::new();
+        for log in &self.files {
+            let entry = package
+                .entries
+                .iter()
+                .find(|e| e.name == log.file)
+                .ok_or_else(|| {
+                    GhostPcbError::ProcessError(format!("输出包中缺少 {}", log.file))
+                })?;
+            // 按记录的编码解码，保证与处理时看到的文本一致
+            let content = log.encoding.decode(&entry.data).ok_or_else(|| {
+                GhostPcbError::ProcessError(format!("{}: 编码与修改记录不符", log.file))
+            })?;
+            layers.push(Layer {
+                content: log.revert(&content)?,
+                encoding: log.encoding,
+                ..Layer::new(&log.file, entry.file_type(), String::new())
+            });
+        }
+        package.write(dest, &Board::new(layers))
+    }
+}
@@ -0,0 +1,182 @@
+use crate::gerber::layout::split_lines;
+use serde::{Deserialize, Serialize};
+
+/// 对文件某一行的结构化修改
+///
+/// 行号指修改前文件中的行（从 0 开始），行文本包含行尾换行符，
+/// 因此修改可以逐字节还原，CRLF 与缺少末尾换行的文件也不例外。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Edit {
+    pub line: usize,
+    pub action: EditAction,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EditAction {
+    /// 在该行之前插入；行号等于总行数时追加到末尾
+    Insert(String),
+    /// 删除该行，记录原文
+    Remove(String),
+    /// 替换该行，记录新旧文本
+    Replace { old: String, new: String },
+}
+
+impl Edit {
+    pub fn insert(line: usize, text: &str) -> Self {
+        Self {
+            line,
+            action: EditAction::Insert(text.to_string()),
+        }
+    }
+
+    pub fn remove(line: usize, text: &str) -> Self {
+        Self {
+            line,
+            action: EditAction::Remove(text.to_string()),
+        }
+    }
+
+    pub fn replace(line: usize, old: &str, new: &str) -> Self {
+        Self {
+            line,
+            action: EditAction::Replace {
+                old: old.to_string(),
+                new: new.to_string(),
+            },
+        }
+    }
+}
+
+/// 把修改应用到原文件
+///
+/// 同一行的插入按给出的顺序排在该行之前；删除与替换以记录的原文为准。
+pub fn apply_edits(content: &str, edits: &[Edit]) -> String {
+    if edits.is_empty() {
+        return content.to_string();
+    }
+
+    let lines = split_lines(content);
+    let mut sorted: Vec<&Edit> = edits.iter().collect();
+    // 同一行内插入排在删除/替换之前，稳定排序保持插入顺序
+    sorted.sort_by_key(|e| (e.line, !matches!(e.action, EditAction::Insert(_))));
+
+    let mut result = String::with_capacity(content.len());
+    let mut next = sorted.into_iter().peekable();
+    for (i, line) in lines.iter().enumerate() {
+        let mut keep = true;
+        while let Some(edit) = next.next_if(|e| e.line == i) {
+            match &edit.action {
+                EditAction::Insert(text) => result.push_str(text),
+                EditAction::Remove(_) => keep = false,
+                EditAction::Replace { new, .. } => {
+                    result.push_str(new);
+                    keep = false;
+                }
+            }
+        }
+        if keep {
+            result.push_str(line);
+        }
+    }
+    for edit in next {
+        if let EditAction::Insert(text) = &edit.action {
+            result.push_str(text);
+        }
+    }
+
+    result
+}
+
+/// 修改中记录的原文是否与文件一致
+pub fn edits_match(content: &str, edits: &[Edit]) -> bool {
+    let lines = split_lines(content);
+    edits.iter().all(|edit| match &edit.action {
+        EditAction::Insert(_) => edit.line <= lines.len(),
+        EditAction::Remove(old) | EditAction::Replace { old, .. } => {
+            lines.get(edit.line) == Some(&old.as_str())
+        }
+    })
+}
+
+/// 求逆修改：作用于修改后的文本，得到修改前的文本
+///
+/// 按 [`apply_edits`] 的顺序遍历，把行号换算到修改后的文件中。
+pub fn invert_edits(edits: &[Edit]) -> Vec<Edit> {
+    let mut sorted: Vec<&Edit> = edits.iter().collect();
+    sorted.sort_by_key(|e| (e.line, !matches!(e.action, EditAction::Insert(_))));
+
+    let (mut inserted, mut removed) = (0, 0);
+    sorted
+        .into_iter()
+        .map(|edit| {
+            let line = edit.line + inserted - removed;
+            match &edit.action {
+                EditAction::Insert(text) => {
+                    inserted += 1;
+                    Edit::remove(line, text)
+                }
+                EditAction::Remove(text) => {
+                    removed += 1;
+                    Edit::insert(line, text)
+                }
+                EditAction::Replace { old, new } => Edit::replace(line, new, old),
+            }
+        })
+        .collect()
+}
+
+/// 向后查找重新对齐的最大行数
+const RESYNC_WINDOW: usize = 8;
+
+/// 比较修改前后的文本，得到逐行修改
+///
+/// 逐行对齐：相同的行跳过；若能在少量行内重新对齐则记为插入或删除，
+/// 否则记为替换。结果总能通过 [`apply_edits`] 精确得到 `after`。
+pub fn diff_lines(before: &str, after: &str) -> Vec<Edit> {
+    if before == after {
+        return Vec::new();
+    }
+
+    let old = split_lines(before);
+    let new = split_lines(after);
+    let mut edits = Vec::new();
+    let (mut i, mut j) = (0, 0);
+
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            i += 1;
+            j += 1;
+            continue;
+        }
+
+        let inserted = (1..=RESYNC_WINDOW).find(|k| new.get(j + k) == Some(&old[i]));
+        let removed = (1..=RESYNC_WINDOW).find(|k| old.get(i + k) == Some(&new[j]));
+        match (inserted, removed) {
+            (Some(k), r) if r.is_none_or(|r| k <= r) => {
+                for text in &new[j..j + k] {
+                    edits.push(Edit::insert(i, text));
+                }
+                j += k;
+            }
+            (_, Some(k)) => {
+                for (n, text) in old[i..i + k].iter().enumerate() {
+                    edits.push(Edit::remove(i + n, text));
+                }
+                i += k;
+            }
+            _ => {
+                edits.push(Edit::replace(i, old[i], new[j]));
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    for (n, text) in old[i..].iter().enumerate() {
+        edits.push(Edit::remove(i + n, text));
+    }
+    for text in &new[j..] {
+        edits.push(Edit::insert(old.len(), text));
+    }
+
+    edits
+}
@@ -0,0 +1,58 @@
+use crate::gerber::error::{GhostPcbError, Result};
+use serde::{Deserialize, Serialize};
+
+/// 文件内容的字节编码
+///
+/// 解析器只关心 ASCII 命令流，注释中的非 ASCII 字节（GBK、Latin-1 等）
+/// 原样带过，任何编码的文件都不会因编码被拒绝或损坏。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TextEncoding {
+    /// 合法的 UTF-8
+    #[default]
+    Utf8,
+    /// 其他编码：每个字节映射为同值的 U+0000..U+00FF 字符，可逐字节还原
+    Bytes,
+}
+
+impl TextEncoding {
+    /// 合法 UTF-8 按 UTF-8 解码，否则按字节映射
+    pub fn detect(bytes: &[u8]) -> (String, Self) {
+        match std::str::from_utf8(bytes) {
+            Ok(text) => (text.to_string(), Self::Utf8),
+            Err(_) => (Self::Bytes.decode(bytes).unwrap_or_default(), Self::Bytes),
+        }
+    }
+
+    /// 按指定编码解码；不是合法 UTF-8 却要求按 UTF-8 解码时返回 `None`
+    pub fn decode(self, bytes: &[u8]) -> Option<String> {
+        match self {
+            Self::Utf8 => std::str::from_utf8(bytes).ok().map(str::to_string),
+            Self::Bytes => Some(bytes.iter().map(|&b| char::from(b)).collect()),
+        }
+    }
+
+    /// 编码回字节
+    ///
+    /// 按字节映射的文件中出现 U+00FF 以上的字符时报错，不写出混有 UTF-8 的内容。
+    pub fn encode(self, text: &str) -> Result<Vec<u8>> {
+        match self {
+            Self::Utf8 => Ok(text.as_bytes().to_vec()),
+            Self::Bytes => text
+                .chars()
+                .map(|c| {
+                    u8::try_from(c).map_err(|_| {
+                        GhostPcbError::ProcessError(format!(
+                            "字符 {} (U+{:04X}) 无法按原字节编码写回",
+                            c, c as u32
+                        ))
+                    })
+                })
+                .collect(),
+        }
+    }
+
+    /// 文本能否按此编码写回
+    pub fn can_encode(self, text: &str) -> bool {
+        self == Self::Utf8 || text.chars().all(|c| u8::try_from(c).is_ok())
+    }
+}
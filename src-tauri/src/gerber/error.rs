@@ -6,12 +6,33 @@ pub enum GhostPcbError {
     #[error("IO 错误: {0}")]
     IoError(#[from] std::io::Error),
 
+    #[error("压缩包不安全: {0}")]
+    UnsafeArchive(String),
+
+    #[error("文件名不是有效的 UTF-8: {0}")]
+    InvalidFileName(String),
+
     #[error("文件不存在: {0}")]
     FileNotFound(String),
 
     #[error("无效的 Gerber 文件: {0}")]
     InvalidGerber(String),
 
+    #[error("工艺配置无效: {0}")]
+    InvalidProfile(String),
+
+    #[error("混淆参数无效: {0}")]
+    InvalidOptions(String),
+
+    #[error("预设无效: {0}")]
+    InvalidPreset(String),
+
+    #[error("Gerber 语法检查未通过: {0}")]
+    LintFailed(String),
+
+    #[error("任务已取消")]
+    Cancelled,
+
     #[error("处理失败: {0}")]
     ProcessError(String),
 }
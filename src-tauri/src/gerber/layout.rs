@@ -0,0 +1,55 @@
+/// 文本的换行风格，各阶段新插入的行沿用它
+///
+/// 已有的行连同各自的行尾原样保留，Altium 等 Windows 工具导出的 CRLF 文件不会被改成 LF。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextLayout {
+    pub eol: &'static str,
+}
+
+impl Default for TextLayout {
+    fn default() -> Self {
+        Self { eol: "\n" }
+    }
+}
+
+impl TextLayout {
+    /// 以第一个换行为准，没有换行时用 LF
+    pub fn detect(content: &str) -> Self {
+        match content.find('\n') {
+            Some(pos) if content[..pos].ends_with('\r') => Self { eol: "\r\n" },
+            _ => Self::default(),
+        }
+    }
+
+    /// 带换行的完整一行
+    pub fn line(&self, body: &str) -> String {
+        format!("{}{}", body, self.eol)
+    }
+}
+
+/// 拆出一行末尾的换行符，返回 (正文, 行尾)
+pub fn split_eol(raw_line: &str) -> (&str, &str) {
+    let body_len = raw_line.trim_end_matches(['\r', '\n']).len();
+    raw_line.split_at(body_len)
+}
+
+/// 按行切分，每行保留自己的换行符，拼接后与原文逐字节相同
+pub fn split_lines(content: &str) -> Vec<&str> {
+    content.split_inclusive('\n').collect()
+}
+
+/// 在 `index` 处插入一行
+///
+/// 插在没有换行的末行之后时，换行移到新行之前，文件结尾保持原样。
+pub fn insert_line(lines: &mut Vec<String>, index: usize, body: &str, layout: TextLayout) {
+    if index == lines.len() {
+        if let Some(last) = lines.last_mut() {
+            if !last.ends_with('\n') {
+                last.push_str(layout.eol);
+                lines.push(body.to_string());
+                return;
+            }
+        }
+    }
+    lines.insert(index, layout.line(body));
+}
@@ -0,0 +1,455 @@
+use crate::gerber::units::CoordinateFormat;
+use crate::gerber::validation::Severity;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// 单条语法诊断
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub file: String,
+    /// 从 1 开始的行号
+    pub line: usize,
+    pub severity: Severity,
+    pub code: String,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn new(file: &str, line: usize, severity: Severity, code: &str, message: String) -> Self {
+        Self {
+            file: file.to_string(),
+            line,
+            severity,
+            code: code.to_string(),
+            message,
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+/// 已废弃的函数码及其说明
+const DEPRECATED_CODES: &[(&str, &str)] = &[
+    ("G54", "G54 光圈选择前缀已废弃"),
+    ("G55", "G55 闪光准备指令已废弃"),
+    ("G70", "G70 英制单位指令已废弃，应使用 %MOIN*%"),
+    ("G71", "G71 公制单位指令已废弃，应使用 %MOMM*%"),
+    ("G74", "G74 单象限圆弧模式已废弃"),
+    ("G90", "G90 绝对坐标指令已废弃"),
+    ("G91", "G91 增量坐标指令已废弃"),
+    ("M00", "M00 程序停止已废弃，应使用 M02"),
+    ("M01", "M01 可选停止已废弃"),
+];
+
+/// 轮廓首尾相差不超过该坐标单位数时视为导出工具的舍入误差
+pub(crate) const CONTOUR_ROUNDING_GAP: i64 = 2;
+
+const DEPRECATED_EXTENDED: &[&str] = &["IN", "IP", "AS", "IR", "MI", "OF", "SF", "LN"];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Interpolation {
+    Linear,
+    Arc,
+}
+
+struct Linter<'a> {
+    file: &'a str,
+    code_re: Regex,
+    d_re: Regex,
+    coord_re: Regex,
+    format: CoordinateFormat,
+    diagnostics: Vec<Diagnostic>,
+    /// 每种废弃指令只报告首次出现的位置
+    deprecated_seen: HashMap<String, (usize, usize)>,
+    apertures: HashMap<u32, bool>,
+    reported_undefined: HashSet<u32>,
+    current_aperture: Option<u32>,
+    interpolation: Interpolation,
+    in_region: bool,
+    region_start_line: usize,
+    contour_start: Option<(i64, i64)>,
+    contour_has_draws: bool,
+    point: (i64, i64),
+    has_fs: bool,
+    has_mo: bool,
+    last_command: Option<(usize, String)>,
+}
+
+/// 检查单个 Gerber 文件的语法
+pub fn lint_gerber(file: &str, content: &str) -> Vec<Diagnostic> {
+    let mut linter = Linter {
+        file,
+        code_re: Regex::new(r"^([GM])(\d+)").unwrap(),
+        d_re: Regex::new(r"D(\d+)$").unwrap(),
+        coord_re: Regex::new(r"([XY])([+-]?\d+)").unwrap(),
+        format: CoordinateFormat::gerber(content),
+        diagnostics: Vec::new(),
+        deprecated_seen: HashMap::new(),
+        apertures: HashMap::new(),
+        reported_undefined: HashSet::new(),
+        current_aperture: None,
+        interpolation: Interpolation::Linear,
+        in_region: false,
+        region_start_line: 0,
+        contour_start: None,
+        contour_has_draws: false,
+        point: (0, 0),
+        has_fs: false,
+        has_mo: false,
+        last_command: None,
+    };
+
+    let mut extended: Option<(usize, String)> = None;
+
+    for (index, line) in content.lines().enumerate() {
+        let line_no = index + 1;
+        let trimmed = line.trim();
+
+        // 扩展指令可能跨行，例如 %AM 宏定义
+        if let Some((start, mut buffer)) = extended.take() {
+            buffer.push_str(trimmed);
+            if trimmed.ends_with('%') {
+                linter.extended_command(start, &buffer);
+            } else {
+                extended = Some((start, buffer));
+            }
+            continue;
+        }
+
+        if trimmed.is_empty() {
+            continue;
+        }
+
+        if trimmed.starts_with('%') {
+            if trimmed.len() > 1 && trimmed.ends_with('%') {
+                linter.extended_command(line_no, trimmed);
+            } else {
+                extended = Some((line_no, trimmed.to_string()));
+            }
+            continue;
+        }
+
+        for word in trimmed.split('*') {
+            let word = word.trim();
+            if !word.is_empty() {
+                linter.word(line_no, word);
+            }
+        }
+    }
+
+    if let Some((start, _)) = extended {
+        linter.push(
+            start,
+            Severity::Error,
+            "unterminated-extended",
+            "扩展指令缺少结尾的 %".to_string(),
+        );
+    }
+
+    linter.finish()
+}
+
+impl Linter<'_> {
+    fn push(&mut self, line: usize, severity: Severity, code: &str, message: String) {
+        self.diagnostics
+            .push(Diagnostic::new(self.file, line, severity, code, message));
+    }
+
+    fn deprecated(&mut self, line: usize, key: &str) {
+        self.deprecated_seen
+            .entry(key.to_string())
+            .and_modify(|(_, count)| *count += 1)
+            .or_insert((line, 1));
+    }
+
+    fn extended_command(&mut self, line: usize, command: &str) {
+        let body = command.trim_matches('%');
+        self.last_command = Some((line, body.to_string()));
+
+        if body.starts_with("FS") {
+            self.has_fs = true;
+        } else if body.starts_with("MO") {
+            self.has_mo = true;
+        } else if let Some(rest) = body.strip_prefix("ADD") {
+            let digits: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+            if let Ok(id) = digits.parse::<u32>() {
+                let template = &rest[digits.len()..];
+                let is_circle = template.starts_with("C,") || template.starts_with("C*");
+                self.apertures.insert(id, is_circle);
+            }
+        } else if let Some(code) = DEPRECATED_EXTENDED.iter().find(|c| body.starts_with(**c)) {
+            self.deprecated(line, &format!("%{}", code));
+        }
+    }
+
+    fn word(&mut self, line: usize, word: &str) {
+        if word.starts_with("G04") {
+            return;
+        }
+
+        self.last_command = Some((line, word.to_string()));
+
+        let mut rest = word;
+        while let Some(caps) = self.code_re.captures(rest) {
+            let letter = &caps[1];
+            let number: u32 = caps[2].parse().unwrap_or(u32::MAX);
+            let normalized = format!("{}{:02}", letter, number);
+            rest = &rest[caps[0].len()..];
+
+            if let Some((code, _)) = DEPRECATED_CODES.iter().find(|(c, _)| *c == normalized) {
+                self.deprecated(line, code);
+            }
+
+            match normalized.as_str() {
+                "G01" => self.interpolation = Interpolation::Linear,
+                "G02" | "G03" => self.interpolation = Interpolation::Arc,
+                "G36" => self.begin_region(line),
+                "G37" => self.end_region(line),
+                _ => {}
+            }
+        }
+
+        if rest.is_empty() {
+            return;
+        }
+
+        let d_code = self
+            .d_re
+            .captures(rest)
+            .and_then(|caps| caps[1].parse::<u32>().ok());
+        let has_coords = rest.contains(['X', 'Y', 'I', 'J']);
+
+        match d_code {
+            Some(id) if id >= 10 && !has_coords => self.select_aperture(line, id),
+            Some(op @ 1..=3) => self.operation(line, rest, op),
+            None if has_coords => self.push(
+                line,
+                Severity::Warning,
+                "implicit-operation",
+                "坐标数据缺少 D01/D02/D03，依赖已废弃的模态操作码".to_string(),
+            ),
+            _ => {}
+        }
+    }
+
+    fn select_aperture(&mut self, line: usize, id: u32) {
+        if !self.apertures.contains_key(&id) && self.reported_undefined.insert(id) {
+            self.push(
+                line,
+                Severity::Error,
+                "undefined-aperture",
+                format!("D{} 在使用前未通过 %ADD 定义", id),
+            );
+        }
+        self.current_aperture = Some(id);
+    }
+
+    fn operation(&mut self, line: usize, word: &str, op: u32) {
+        let target = self.parse_point(word);
+
+        match op {
+            1 => {
+                if self.in_region {
+                    self.contour_has_draws = true;
+                    if self.contour_start.is_none() {
+                        self.contour_start = Some(self.point);
+                    }
+                } else {
+                    self.check_aperture(line);
+                    if self.interpolation == Interpolation::Arc {
+                        let circular = self
+                            .current_aperture
+                            .and_then(|id| self.apertures.get(&id))
+                            .copied()
+                            .unwrap_or(true);
+                        if !circular {
+                            self.push(
+                                line,
+                                Severity::Error,
+                                "arc-non-circular",
+                                format!(
+                                    "圆弧插补使用了非圆形光圈 D{}",
+                                    self.current_aperture.unwrap_or(0)
+                                ),
+                            );
+                        }
+                    }
+                }
+            }
+            2 => {
+                if self.in_region {
+                    self.close_contour(line);
+                    self.contour_start = Some(target);
+                    self.contour_has_draws = false;
+                }
+            }
+            _ => {
+                if self.in_region {
+                    self.push(
+                        line,
+                        Severity::Error,
+                        "flash-in-region",
+                        "区域 (G36/G37) 内不允许 D03 闪光".to_string(),
+                    );
+                } else {
+                    self.check_aperture(line);
+                }
+            }
+        }
+
+        self.point = target;
+    }
+
+    fn check_aperture(&mut self, line: usize) {
+        if self.current_aperture.is_none() {
+            self.push(
+                line,
+                Severity::Error,
+                "no-aperture",
+                "绘制或闪光前未选择光圈".to_string(),
+            );
+            // 只报告一次
+            self.current_aperture = Some(0);
+            self.apertures.insert(0, true);
+        }
+    }
+
+    fn parse_point(&self, word: &str) -> (i64, i64) {
+        let mut point = self.point;
+        for caps in self.coord_re.captures_iter(word) {
+            let value = self.format.parse_raw(&caps[2]).unwrap_or(0);
+            if &caps[1] == "X" {
+                point.0 = value;
+            } else {
+                point.1 = value;
+            }
+        }
+        point
+    }
+
+    fn begin_region(&mut self, line: usize) {
+        if self.in_region {
+            self.push(
+                line,
+                Severity::Error,
+                "nested-region",
+                format!("第 {} 行的区域尚未以 G37 结束", self.region_start_line),
+            );
+        }
+        self.in_region = true;
+        self.region_start_line = line;
+        self.contour_start = None;
+        self.contour_has_draws = false;
+    }
+
+    fn end_region(&mut self, line: usize) {
+        if !self.in_region {
+            self.push(
+                line,
+                Severity::Error,
+                "unmatched-region-end",
+                "G37 之前没有对应的 G36".to_string(),
+            );
+            return;
+        }
+        self.close_contour(line);
+        self.in_region = false;
+        self.contour_start = None;
+        self.contour_has_draws = false;
+    }
+
+    fn close_contour(&mut self, line: usize) {
+        if !self.contour_has_draws {
+            return;
+        }
+        if let Some(start) = self.contour_start {
+            let gap = (start.0 - self.point.0)
+                .abs()
+                .max((start.1 - self.point.1).abs());
+            if gap > 0 && gap <= CONTOUR_ROUNDING_GAP {
+                self.push(
+                    line,
+                    Severity::Warning,
+                    "contour-gap",
+                    format!("区域轮廓首尾相差 {} 个坐标单位，疑似舍入误差", gap),
+                );
+            } else if gap > CONTOUR_ROUNDING_GAP {
+                self.push(
+                    line,
+                    Severity::Error,
+                    "open-contour",
+                    format!(
+                        "区域轮廓未闭合: 起点 ({}, {})，终点 ({}, {})",
+                        start.0, start.1, self.point.0, self.point.1
+                    ),
+                );
+            }
+        }
+    }
+
+    fn finish(mut self) -> Vec<Diagnostic> {
+        if self.in_region {
+            let line = self.region_start_line;
+            self.push(
+                line,
+                Severity::Error,
+                "unclosed-region",
+                "G36 区域直到文件结束都没有 G37".to_string(),
+            );
+        }
+
+        if !self.has_fs {
+            self.push(
+                1,
+                Severity::Error,
+                "missing-fs",
+                "缺少 %FS 坐标格式定义".to_string(),
+            );
+        }
+        if !self.has_mo {
+            self.push(
+                1,
+                Severity::Error,
+                "missing-mo",
+                "缺少 %MO 单位定义".to_string(),
+            );
+        }
+
+        match &self.last_command {
+            Some((_, cmd)) if cmd == "M02" => {}
+            Some((line, _)) => {
+                let line = *line;
+                self.push(
+                    line,
+                    Severity::Error,
+                    "missing-m02",
+                    "文件未以 M02* 结束".to_string(),
+                );
+            }
+            None => self.push(1, Severity::Error, "missing-m02", "文件为空".to_string()),
+        }
+
+        let mut deprecated: Vec<_> = self.deprecated_seen.drain().collect();
+        deprecated.sort_by_key(|(_, (line, _))| *line);
+        for (code, (line, count)) in deprecated {
+            let description = DEPRECATED_CODES
+                .iter()
+                .find(|(c, _)| *c == code)
+                .map(|(_, d)| d.to_string())
+                .unwrap_or_else(|| format!("{} 扩展指令已废弃", code));
+            self.diagnostics.push(Diagnostic::new(
+                self.file,
+                line,
+                Severity::Warning,
+                "deprecated-command",
+                format!("{} (共 {} 处)", description, count),
+            ));
+        }
+
+        self.diagnostics.sort_by_key(|d| d.line);
+        self.diagnostics
+    }
+}
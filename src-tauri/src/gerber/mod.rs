@@ -1,9 +1,28 @@
+pub mod board;
+pub mod changelog;
+pub mod edit;
+pub mod encoding;
 pub mod error;
+pub mod layout;
+pub mod lint;
 pub mod obfuscators;
+pub mod output;
+pub mod package;
+pub mod params;
 pub mod pipeline;
+pub mod presets;
 pub mod processor;
+pub mod profile;
+pub mod progress;
+pub mod report;
+pub mod rules;
+pub mod seed;
 pub mod signature;
+pub mod transform;
 pub mod types;
+pub mod units;
+pub mod validation;
 
 pub use processor::GerberProcessor;
+pub use profile::CapabilityProfile;
 pub use types::{GerberFileType, ObfuscateOptions, ProcessRequest, ProcessResult};
@@ -0,0 +1,62 @@
+use super::{BoardTransform, Result};
+use crate::gerber::board::{Board, DisplacementField};
+use crate::gerber::params::DisplacementParams;
+use crate::gerber::profile::CapabilityProfile;
+use crate::gerber::units::Length;
+use rand::RngCore;
+
+/// 跨层一致的坐标位移
+///
+/// 对整块板生成一个平滑位移场，所有层和钻孔文件按同一个场移动，
+/// 焊盘、阻焊开窗、钢网开口与孔始终保持对齐。
+pub struct CoherentJitterTransform {
+    amplitude: Length,
+    min_wavelength: Length,
+}
+
+impl Default for CoherentJitterTransform {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CoherentJitterTransform {
+    pub fn new() -> Self {
+        Self::with_profile(&CapabilityProfile::default())
+    }
+
+    pub fn with_profile(profile: &CapabilityProfile) -> Self {
+        Self::with_params(profile, &DisplacementParams::default())
+    }
+
+    pub fn with_params(profile: &CapabilityProfile, params: &DisplacementParams) -> Self {
+        Self {
+            amplitude: Length::mm(
+                params
+                    .amplitude_mm
+                    .unwrap_or(profile.tolerance.feature_jitter_mm),
+            ),
+            min_wavelength: Length::mm(params.min_wavelength_mm),
+        }
+    }
+
+    pub fn amplitude(&self) -> Length {
+        self.amplitude
+    }
+
+    /// 按板子尺寸生成位移场
+    pub fn field(&self, board: &Board, rng: &mut dyn RngCore) -> DisplacementField {
+        DisplacementField::random(board.extent(), self.amplitude, self.min_wavelength, rng)
+    }
+}
+
+impl BoardTransform for CoherentJitterTransform {
+    fn name(&self) -> &'static str {
+        "跨层一致位移"
+    }
+
+    fn apply(&self, board: &mut Board, rng: &mut dyn RngCore) -> Result<()> {
+        board.apply_field(&self.field(board, rng));
+        Ok(())
+    }
+}
@@ -1,156 +1,122 @@
-use super::{Obfuscator, Result};
+use super::{BoardTransform, Obfuscator, Result};
+use crate::gerber::board::{Board, DisplacementField, Layer};
+use crate::gerber::params::GeometryParams;
+use crate::gerber::profile::CapabilityProfile;
 use crate::gerber::types::GerberFileType;
-use rand::Rng;
-use regex::Regex;
+use crate::gerber::units::Length;
+use rand::RngCore;
+
+/// 局部扰动位移场的最短波长，比跨层一致位移短，改变的是各处的细节
+const MIN_WAVELENGTH_MM: f64 = 2.0;
+
+/// 孔位与铜层的局部扰动
+///
+/// 在板上生成一个波长较短的位移场。选中层中只要有铜层、阻焊、钢网或钻孔，
+/// 这几类层就按同一个场一起移动，焊盘与孔、开窗的对位保持不变。
+pub struct GeometryObfuscator {
+    /// 钻孔坐标偏移上限
+    drill_jitter: Length,
+    /// 铜层坐标偏移上限
+    copper_jitter: Length,
+    layers: Vec<GerberFileType>,
+}
 
-/// 钻孔坐标偏移范围（约 ±0.02mm）
-const DRILL_OFFSET_MAX: i64 = 200;
+impl Default for GeometryObfuscator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-pub struct GeometryObfuscator;
+/// 焊盘位置需要跨层对齐的层
+fn is_registered(file_type: GerberFileType) -> bool {
+    file_type.is_copper_layer()
+        || file_type.is_drill()
+        || matches!(
+            file_type,
+            GerberFileType::TopSolderMask
+                | GerberFileType::BottomSolderMask
+                | GerberFileType::TopPaste
+                | GerberFileType::BottomPaste
+        )
+}
 
 impl GeometryObfuscator {
     pub fn new() -> Self {
-        Self
+        Self::with_profile(&CapabilityProfile::default())
     }
 
-    fn apply_drill_jitter(coord_str: &str) -> String {
-        // 钻孔文件坐标格式可能是浮点数
-        if let Ok(coord) = coord_str.parse::<f64>() {
-            let mut rng = rand::thread_rng();
-            let offset = rng.gen_range(-0.02..=0.02); // ±0.02mm
-            let new_coord = coord + offset;
-            // 保持原有精度格式
-            if coord_str.contains('.') {
-                let decimals = coord_str.split('.').nth(1).map(|s| s.len()).unwrap_or(3);
-                format!("{:.prec$}", new_coord, prec = decimals)
-            } else {
-                format!("{:.5}", new_coord)
-            }
-        } else {
-            coord_str.to_string()
-        }
+    pub fn with_profile(profile: &CapabilityProfile) -> Self {
+        Self::with_params(profile, &GeometryParams::default())
     }
 
-    fn apply_gerber_coord_jitter(coord: i64) -> i64 {
-        let mut rng = rand::thread_rng();
-        let offset = rng.gen_range(-DRILL_OFFSET_MAX..=DRILL_OFFSET_MAX);
-        coord + offset
+    pub fn with_params(profile: &CapabilityProfile, params: &GeometryParams) -> Self {
+        let tol = &profile.tolerance;
+        Self {
+            drill_jitter: Length::mm(params.drill_jitter_mm.unwrap_or(tol.drill_jitter_mm)),
+            copper_jitter: Length::mm(params.copper_jitter_mm.unwrap_or(tol.feature_jitter_mm)),
+            layers: params.layers.clone(),
+        }
     }
-}
 
-impl Obfuscator for GeometryObfuscator {
-    fn name(&self) -> &'static str {
-        "几何结构扰动"
+    /// 该类型的层是否随位移场移动：选中的层，以及与选中层对位的层
+    fn moves(&self, file_type: GerberFileType) -> bool {
+        self.layers.contains(&file_type)
+            || (is_registered(file_type) && self.layers.iter().any(|t| is_registered(*t)))
     }
 
-    fn obfuscate(&self, content: &str, file_type: GerberFileType) -> Result<String> {
-        if file_type.is_drill() {
-            return self.obfuscate_drill(content);
-        }
-
-        // 对铜层应用微小偏移
-        if file_type.is_copper_layer() {
-            return self.obfuscate_copper(content);
+    /// 所有移动的层共用一个幅值，有钻孔时不超过孔位偏移上限
+    fn amplitude(&self, board: &Board) -> Length {
+        let moving = board.layers.iter().filter(|l| self.moves(l.file_type));
+        let (drills, others): (Vec<&Layer>, Vec<&Layer>) =
+            moving.partition(|l| l.file_type.is_drill());
+        match (drills.is_empty(), others.is_empty()) {
+            (false, false) => self.drill_jitter.min(self.copper_jitter),
+            (false, true) => self.drill_jitter,
+            _ => self.copper_jitter,
         }
+    }
 
-        Ok(content.to_string())
+    /// 按板子尺寸生成位移场
+    pub fn field(&self, board: &Board, rng: &mut dyn RngCore) -> DisplacementField {
+        DisplacementField::random(
+            board.extent(),
+            self.amplitude(board),
+            Length::mm(MIN_WAVELENGTH_MM),
+            rng,
+        )
     }
 }
 
-impl GeometryObfuscator {
-    fn obfuscate_drill(&self, content: &str) -> Result<String> {
-        let mut result = String::new();
-        // Excellon 钻孔格式: X25.24994Y8.763 或 X25Y8
-        let coord_re = Regex::new(r"^X(-?[\d.]+)Y(-?[\d.]+)$").unwrap();
-
-        for line in content.lines() {
-            let trimmed = line.trim();
-
-            // 跳过头部定义行
-            if trimmed.starts_with(';')
-                || trimmed.starts_with('%')
-                || trimmed.starts_with('M')
-                || trimmed.starts_with('T')
-                || trimmed.starts_with('G')
-                || trimmed.is_empty()
-            {
-                result.push_str(line);
-                result.push('\n');
-                continue;
-            }
-
-            if coord_re.is_match(trimmed) {
-                let new_line = coord_re.replace(trimmed, |caps: &regex::Captures| {
-                    let x = Self::apply_drill_jitter(&caps[1]);
-                    let y = Self::apply_drill_jitter(&caps[2]);
-                    format!("X{}Y{}", x, y)
-                });
-                result.push_str(&new_line);
-            } else {
-                result.push_str(line);
-            }
-            result.push('\n');
-        }
-
-        if result.ends_with('\n') && !content.ends_with('\n') {
-            result.pop();
-        }
-
-        Ok(result)
+impl BoardTransform for GeometryObfuscator {
+    fn name(&self) -> &'static str {
+        "几何结构扰动"
     }
 
-    fn obfuscate_copper(&self, content: &str) -> Result<String> {
-        let mut result = String::new();
-        // 匹配 G01/G02/G03 坐标指令或纯坐标指令
-        let coord_re = Regex::new(r"^(G0[123])?X(-?\d+)Y(-?\d+)(D\d+\*?)?$").unwrap();
-
-        for line in content.lines() {
-            let trimmed = line.trim();
-
-            // 跳过格式定义行（以 % 开头）、注释行（G04）、控制指令
-            if trimmed.starts_with('%')
-                || trimmed.starts_with("G04")
-                || trimmed.starts_with('M')
-                || trimmed.is_empty()
-                || trimmed.starts_with("G36")
-                || trimmed.starts_with("G37")
-                || trimmed.starts_with("G75")
-            {
-                result.push_str(line);
-                result.push('\n');
-                continue;
-            }
-
-            // 跳过 D 码选择指令 (如 D10*)
-            if trimmed.starts_with('D') && !trimmed.contains('X') {
-                result.push_str(line);
-                result.push('\n');
-                continue;
-            }
-
-            if coord_re.is_match(trimmed) {
-                let new_line = coord_re.replace(trimmed, |caps: &regex::Captures| {
-                    let prefix = caps.get(1).map(|m| m.as_str()).unwrap_or("");
-                    let x: i64 = caps[2].parse().unwrap_or(0);
-                    let y: i64 = caps[3].parse().unwrap_or(0);
-                    let suffix = caps.get(4).map(|m| m.as_str()).unwrap_or("");
-
-                    let new_x = Self::apply_gerber_coord_jitter(x);
-                    let new_y = Self::apply_gerber_coord_jitter(y);
-
-                    format!("{}X{}Y{}{}", prefix, new_x, new_y, suffix)
-                });
-                result.push_str(&new_line);
-            } else {
-                result.push_str(line);
+    fn apply(&self, board: &mut Board, rng: &mut dyn RngCore) -> Result<()> {
+        let field = self.field(board, rng);
+        for layer in board.layers.iter_mut() {
+            if self.moves(layer.file_type) {
+                layer.apply_field(&field);
             }
-            result.push('\n');
         }
+        Ok(())
+    }
+}
 
-        if result.ends_with('\n') && !content.ends_with('\n') {
-            result.pop();
-        }
+/// 单独处理一个文件时，位移场只作用于该文件
+impl Obfuscator for GeometryObfuscator {
+    fn name(&self) -> &'static str {
+        "几何结构扰动"
+    }
 
-        Ok(result)
+    fn obfuscate(
+        &self,
+        content: &str,
+        file_type: GerberFileType,
+        rng: &mut dyn RngCore,
+    ) -> Result<String> {
+        let mut board = Board::new(vec![Layer::new("", file_type, content.to_string())]);
+        BoardTransform::apply(self, &mut board, rng)?;
+        Ok(board.layers.remove(0).content)
     }
 }
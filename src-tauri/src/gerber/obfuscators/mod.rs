@@ -1,22 +1,98 @@
-mod coordinate_jitter;
+mod coherent_jitter;
 mod geometry;
 mod physical;
 mod silkscreen;
 mod structure;
 mod timestamp;
 
-pub use coordinate_jitter::CoordinateJitterObfuscator;
+pub use coherent_jitter::CoherentJitterTransform;
 pub use geometry::GeometryObfuscator;
 pub use physical::PhysicalObfuscator;
 pub use silkscreen::SilkscreenObfuscator;
 pub use structure::StructureObfuscator;
 pub use timestamp::TimestampObfuscator;
 
+use crate::gerber::board::{Board, Extent, Layer};
+use crate::gerber::edit::{diff_lines, Edit};
 use crate::gerber::error::Result;
+use crate::gerber::profile::CapabilityProfile;
 use crate::gerber::types::GerberFileType;
+use rand::RngCore;
 
 /// 混淆器 trait
+///
+/// 所有随机数都取自调用方传入的 `rng`，同一随机数流总是得到相同的输出。
 pub trait Obfuscator: Send + Sync {
     fn name(&self) -> &'static str;
-    fn obfuscate(&self, content: &str, file_type: GerberFileType) -> Result<String>;
+    fn obfuscate(
+        &self,
+        content: &str,
+        file_type: GerberFileType,
+        rng: &mut dyn RngCore,
+    ) -> Result<String>;
+}
+
+/// 板级变换 trait，一次作用于包内所有层，保证跨层对齐
+pub trait BoardTransform: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn apply(&self, board: &mut Board, rng: &mut dyn RngCore) -> Result<()>;
+}
+
+/// 第二代混淆器可见的整板信息
+pub struct BoardContext<'a> {
+    /// 本阶段开始前的整块板，可读取其他层的几何
+    pub board: &'a Board,
+    pub profile: &'a CapabilityProfile,
+    /// 板框范围，没有板框时取所有层
+    pub outline: Option<Extent>,
+    /// 当前文件在当前阶段的随机数流
+    pub rng: &'a mut dyn RngCore,
+}
+
+impl BoardContext<'_> {
+    /// 铜层叠层顺序
+    pub fn stackup(&self) -> Vec<&Layer> {
+        self.board.copper_stack()
+    }
+
+    /// 同类型的其他层
+    pub fn layers_of(&self, file_type: GerberFileType) -> impl Iterator<Item = &Layer> {
+        self.board
+            .layers
+            .iter()
+            .filter(move |l| l.file_type == file_type)
+    }
+}
+
+/// 第二代混淆器 trait
+///
+/// 可以读取整块板的上下文，返回对当前文件的逐行修改而不是新文本，
+/// 修改由管道统一应用，便于记录与预览。
+pub trait BoardObfuscator: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn edit(&self, layer: &Layer, context: &mut BoardContext) -> Result<Vec<Edit>>;
+}
+
+/// 把只处理单个文件文本的 [`Obfuscator`] 适配为 [`BoardObfuscator`]
+pub struct TextObfuscatorAdapter<T: Obfuscator> {
+    inner: T,
+}
+
+impl<T: Obfuscator> TextObfuscatorAdapter<T> {
+    pub fn new(inner: T) -> Self {
+        Self { inner }
+    }
+}
+
+impl<T: Obfuscator> BoardObfuscator for TextObfuscatorAdapter<T> {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn edit(&self, layer: &Layer, context: &mut BoardContext) -> Result<Vec<Edit>> {
+        let result = self
+            .inner
+            .obfuscate(&layer.content, layer.file_type, context.rng)?;
+        Ok(diff_lines(&layer.content, &result))
+    }
 }
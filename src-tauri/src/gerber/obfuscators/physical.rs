@@ -1,29 +1,57 @@
 use super::{Obfuscator, Result};
+use crate::gerber::params::PhysicalParams;
+use crate::gerber::profile::CapabilityProfile;
+use crate::gerber::transform::transform_operations;
 use crate::gerber::types::GerberFileType;
-use rand::Rng;
+use crate::gerber::units::{CoordinateFormat, Length};
+use rand::{Rng, RngCore};
 use regex::Regex;
 
-/// 外框尺寸偏移（约 ±0.01mm，单位是 Gerber 内部单位）
-const OUTLINE_OFFSET: i64 = 100;
-
-/// 最大板子尺寸限制 100mm × 100mm（Gerber 内部单位，假设精度为 4.5 即 10^5）
-const MAX_BOARD_SIZE: i64 = 100_00000; // 100mm = 100 * 10^5
+pub struct PhysicalObfuscator {
+    /// 外框整体偏移范围
+    offset_min: Length,
+    offset_max: Length,
+    /// 最大板子尺寸
+    max_width: Length,
+    max_height: Length,
+    layers: Vec<GerberFileType>,
+}
 
-pub struct PhysicalObfuscator;
+impl Default for PhysicalObfuscator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl PhysicalObfuscator {
     pub fn new() -> Self {
-        Self
+        Self::with_profile(&CapabilityProfile::default())
     }
 
-    fn get_uniform_offset() -> i64 {
-        let mut rng = rand::thread_rng();
+    pub fn with_profile(profile: &CapabilityProfile) -> Self {
+        Self::with_params(profile, &PhysicalParams::default())
+    }
+
+    pub fn with_params(profile: &CapabilityProfile, params: &PhysicalParams) -> Self {
+        let tol = &profile.tolerance;
+        Self {
+            offset_min: Length::mm(params.offset_min_mm.unwrap_or(tol.outline_offset_min_mm)),
+            offset_max: Length::mm(params.offset_max_mm.unwrap_or(tol.outline_offset_max_mm)),
+            max_width: Length::mm(profile.max_board_width_mm),
+            max_height: Length::mm(profile.max_board_height_mm),
+            layers: params.layers.clone(),
+        }
+    }
+
+    fn get_uniform_offset(&self, format: &CoordinateFormat, rng: &mut dyn RngCore) -> i64 {
+        let min = format.to_raw(self.offset_min);
+        let max = format.to_raw(self.offset_max).max(min);
         let sign = if rng.gen_bool(0.5) { 1 } else { -1 };
-        sign * rng.gen_range(50..=OUTLINE_OFFSET)
+        sign * rng.gen_range(min..=max)
     }
 
     /// 解析板框文件，获取当前板子的最大坐标
-    fn get_board_bounds(content: &str) -> (i64, i64) {
+    fn get_board_bounds(content: &str, format: &CoordinateFormat) -> (i64, i64) {
         let coord_re = Regex::new(r"X(-?\d+)Y(-?\d+)").unwrap();
         let mut max_x: i64 = 0;
         let mut max_y: i64 = 0;
@@ -36,7 +64,8 @@ impl PhysicalObfuscator {
             }
 
             if let Some(caps) = coord_re.captures(trimmed) {
-                if let (Ok(x), Ok(y)) = (caps[1].parse::<i64>(), caps[2].parse::<i64>()) {
+                if let (Some(x), Some(y)) = (format.parse_raw(&caps[1]), format.parse_raw(&caps[2]))
+                {
                     max_x = max_x.max(x.abs());
                     max_y = max_y.max(y.abs());
                 }
@@ -52,24 +81,32 @@ impl Obfuscator for PhysicalObfuscator {
         "物理参数微调"
     }
 
-    fn obfuscate(&self, content: &str, file_type: GerberFileType) -> Result<String> {
-        // 只处理板框文件
-        if !file_type.is_outline() {
+    fn obfuscate(
+        &self,
+        content: &str,
+        file_type: GerberFileType,
+        rng: &mut dyn RngCore,
+    ) -> Result<String> {
+        // 默认只处理板框文件
+        if !self.layers.contains(&file_type) {
             return Ok(content.to_string());
         }
 
         // 获取当前板子尺寸
-        let (max_x, max_y) = Self::get_board_bounds(content);
+        let format = CoordinateFormat::gerber(content);
+        let (max_x, max_y) = Self::get_board_bounds(content, &format);
+        let max_width = format.to_raw(self.max_width);
+        let max_height = format.to_raw(self.max_height);
 
-        // 计算允许的最大偏移量，确保不超过 100mm
-        let offset = Self::get_uniform_offset();
+        // 计算允许的最大偏移量，确保不超过配置的最大板子尺寸
+        let offset = self.get_uniform_offset(&format, rng);
 
-        // 如果偏移后会超过 100mm，则只使用负偏移或不偏移
+        // 如果偏移后会超过最大尺寸，则只使用负偏移或不偏移
         let safe_offset = if offset > 0 {
             let new_max_x = max_x + offset;
             let new_max_y = max_y + offset;
-            if new_max_x > MAX_BOARD_SIZE || new_max_y > MAX_BOARD_SIZE {
-                // 板子已经接近或超过 100mm，使用负偏移
+            if new_max_x > max_width || new_max_y > max_height {
+                // 板子已经接近或超过最大尺寸，使用负偏移
                 -offset.abs()
             } else {
                 offset
@@ -78,57 +115,10 @@ impl Obfuscator for PhysicalObfuscator {
             offset
         };
 
-        let mut result = String::new();
-        // 匹配 G01/G02/G03 坐标指令或纯坐标指令
-        let coord_re = Regex::new(r"^(G0[123])?X(-?\d+)Y(-?\d+)(D\d+\*?)?$").unwrap();
-
-        for line in content.lines() {
-            let trimmed = line.trim();
-
-            // 跳过格式定义行（以 % 开头）、注释行（G04）、控制指令
-            if trimmed.starts_with('%')
-                || trimmed.starts_with("G04")
-                || trimmed.starts_with('M')
-                || trimmed.is_empty()
-                || trimmed.starts_with("G36")
-                || trimmed.starts_with("G37")
-                || trimmed.starts_with("G75")
-            {
-                result.push_str(line);
-                result.push('\n');
-                continue;
-            }
-
-            // 跳过 D 码选择指令 (如 D10*)
-            if trimmed.starts_with('D') && !trimmed.contains('X') {
-                result.push_str(line);
-                result.push('\n');
-                continue;
-            }
-
-            if coord_re.is_match(trimmed) {
-                let new_line = coord_re.replace(trimmed, |caps: &regex::Captures| {
-                    let prefix = caps.get(1).map(|m| m.as_str()).unwrap_or("");
-                    let x: i64 = caps[2].parse().unwrap_or(0);
-                    let y: i64 = caps[3].parse().unwrap_or(0);
-                    let suffix = caps.get(4).map(|m| m.as_str()).unwrap_or("");
-
-                    // 对所有坐标应用统一偏移
-                    let new_x = x + safe_offset;
-                    let new_y = y + safe_offset;
-
-                    format!("{}X{}Y{}{}", prefix, new_x, new_y, suffix)
-                });
-                result.push_str(&new_line);
-            } else {
-                result.push_str(line);
-            }
-            result.push('\n');
-        }
-
-        if result.ends_with('\n') && !content.ends_with('\n') {
-            result.pop();
-        }
+        // 对所有坐标应用统一偏移，圆弧 I/J 随整体平移保持不变
+        let result = transform_operations(content, |op, _| {
+            (op.end.0 + safe_offset, op.end.1 + safe_offset)
+        });
 
         Ok(result)
     }
@@ -1,25 +1,45 @@
 use super::{Obfuscator, Result};
+use crate::gerber::layout::{insert_line, split_eol, split_lines, TextLayout};
+use crate::gerber::params::StructureParams;
 use crate::gerber::types::GerberFileType;
-use rand::Rng;
+use rand::{Rng, RngCore};
 
-pub struct StructureObfuscator;
+pub struct StructureObfuscator {
+    build_id_comment: bool,
+    /// 每行之后重复当前 D-code 选择的概率
+    redundant_dcode_probability: f64,
+    layers: Vec<GerberFileType>,
+}
+
+impl Default for StructureObfuscator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl StructureObfuscator {
     pub fn new() -> Self {
-        Self
+        Self::with_params(&StructureParams::default())
+    }
+
+    pub fn with_params(params: &StructureParams) -> Self {
+        Self {
+            build_id_comment: params.build_id_comment,
+            redundant_dcode_probability: params.redundant_dcode_probability,
+            layers: params.layers.clone(),
+        }
     }
 
     /// 生成随机 ID
-    fn generate_random_id() -> String {
-        let mut rng = rand::thread_rng();
+    fn generate_random_id(rng: &mut dyn RngCore) -> String {
         let id: u32 = rng.gen_range(100000..999999);
         format!("{:X}", id)
     }
 
     /// 在文件头部添加随机注释
-    fn add_random_comment(content: &str) -> String {
-        let random_id = Self::generate_random_id();
-        let comment = format!("G04 Build ID: {}*\n", random_id);
+    fn add_random_comment(content: &str, rng: &mut dyn RngCore) -> String {
+        let random_id = Self::generate_random_id(rng);
+        let comment = TextLayout::detect(content).line(&format!("G04 Build ID: {}*", random_id));
 
         // 在第一个 G04 注释后插入
         if let Some(pos) = content.find("G04") {
@@ -35,13 +55,14 @@ impl StructureObfuscator {
         format!("{}{}", comment, content)
     }
 
-    /// 插入冗余 D-code 指令
-    fn insert_redundant_dcodes(content: &str) -> String {
-        let mut result = String::new();
-        let mut rng = rand::thread_rng();
+    /// 插入冗余 D-code 指令，原有各行连同行尾保持不变
+    fn insert_redundant_dcodes(&self, content: &str, rng: &mut dyn RngCore) -> String {
+        let layout = TextLayout::detect(content);
+        let mut result: Vec<String> = Vec::new();
         let mut current_dcode: Option<String> = None;
 
-        for line in content.lines() {
+        for raw_line in split_lines(content) {
+            let (line, _) = split_eol(raw_line);
             // 检测当前选择的 D-code
             if line.starts_with('D')
                 && line.ends_with('*')
@@ -51,24 +72,18 @@ impl StructureObfuscator {
                 current_dcode = Some(line.to_string());
             }
 
-            result.push_str(line);
-            result.push('\n');
+            result.push(raw_line.to_string());
 
             // 随机在某些行后插入冗余 D-code 选择
             if let Some(ref dcode) = current_dcode {
-                if rng.gen_bool(0.05) {
-                    // 5% 概率
-                    result.push_str(dcode);
-                    result.push('\n');
+                if rng.gen_bool(self.redundant_dcode_probability) {
+                    let end = result.len();
+                    insert_line(&mut result, end, dcode, layout);
                 }
             }
         }
 
-        if result.ends_with('\n') && !content.ends_with('\n') {
-            result.pop();
-        }
-
-        result
+        result.concat()
     }
 }
 
@@ -77,19 +92,26 @@ impl Obfuscator for StructureObfuscator {
         "文件结构混淆"
     }
 
-    fn obfuscate(&self, content: &str, file_type: GerberFileType) -> Result<String> {
-        // 钻孔文件格式不同，跳过
-        if file_type.is_drill() {
+    fn obfuscate(
+        &self,
+        content: &str,
+        file_type: GerberFileType,
+        rng: &mut dyn RngCore,
+    ) -> Result<String> {
+        // 钻孔文件格式不同，默认不在处理范围内
+        if !self.layers.contains(&file_type) || file_type.is_drill() {
             return Ok(content.to_string());
         }
 
         let mut result = content.to_string();
 
         // 添加随机注释
-        result = Self::add_random_comment(&result);
+        if self.build_id_comment {
+            result = Self::add_random_comment(&result, rng);
+        }
 
         // 插入冗余指令
-        result = Self::insert_redundant_dcodes(&result);
+        result = self.insert_redundant_dcodes(&result, rng);
 
         Ok(result)
     }
@@ -1,32 +1,67 @@
 use super::{Obfuscator, Result};
+use crate::gerber::params::TimestampParams;
 use crate::gerber::types::GerberFileType;
 use chrono::{Duration, Local, NaiveDateTime, Timelike};
-use rand::Rng;
+use rand::{Rng, RngCore};
 use regex::Regex;
 
 pub struct TimestampObfuscator {
-    new_datetime: NaiveDateTime,
+    params: TimestampParams,
+    /// 新时间以此为“现在”往前推
+    now: NaiveDateTime,
+    /// 固定的新时间；为空时每次处理从传入的随机数流生成
+    new_datetime: Option<NaiveDateTime>,
+}
+
+impl Default for TimestampObfuscator {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl TimestampObfuscator {
+    /// 以当前时间为基准，每次处理按传入的随机数流生成新时间
     pub fn new() -> Self {
         Self {
-            new_datetime: generate_random_datetime(),
+            params: TimestampParams::default(),
+            now: Local::now().naive_local(),
+            new_datetime: None,
+        }
+    }
+
+    /// 新时间在构造时由 `now` 与 `rng` 确定，同一包内所有文件使用同一时间
+    pub fn with_params(
+        params: &TimestampParams,
+        now: NaiveDateTime,
+        rng: &mut dyn RngCore,
+    ) -> Self {
+        Self {
+            params: params.clone(),
+            now,
+            new_datetime: Some(generate_random_datetime(params, now, rng)),
         }
     }
 
     pub fn with_datetime(datetime: NaiveDateTime) -> Self {
         Self {
-            new_datetime: datetime,
+            params: TimestampParams::default(),
+            now: datetime,
+            new_datetime: Some(datetime),
         }
     }
 }
 
-fn generate_random_datetime() -> NaiveDateTime {
-    let now = Local::now().naive_local();
-    let mut rng = rand::thread_rng();
-    let days_ago = rng.gen_range(1..30);
-    let hours = rng.gen_range(8..18);
+fn generate_random_datetime(
+    params: &TimestampParams,
+    now: NaiveDateTime,
+    rng: &mut dyn RngCore,
+) -> NaiveDateTime {
+    // 范围颠倒或越界时收窄到最近的有效范围，不在采样时 panic
+    let days_max = params.days_ago_max.max(params.days_ago_min);
+    let hour_end = params.hour_end.clamp(1, 24);
+    let hour_start = params.hour_start.min(hour_end - 1);
+    let days_ago = rng.gen_range(params.days_ago_min..=days_max) as i64;
+    let hours = rng.gen_range(hour_start..hour_end) as i64;
     let minutes = rng.gen_range(0..60);
     let seconds = rng.gen_range(0..60);
 
@@ -44,27 +79,35 @@ impl Obfuscator for TimestampObfuscator {
         "时间戳修改"
     }
 
-    fn obfuscate(&self, content: &str, _file_type: GerberFileType) -> Result<String> {
+    fn obfuscate(
+        &self,
+        content: &str,
+        _file_type: GerberFileType,
+        rng: &mut dyn RngCore,
+    ) -> Result<String> {
+        let new_datetime = self
+            .new_datetime
+            .unwrap_or_else(|| generate_random_datetime(&self.params, self.now, rng));
         let mut result = content.to_string();
 
         // 匹配 YYYY-MM-DD HH:MM:SS 格式
         let re1 = Regex::new(r"\d{4}-\d{2}-\d{2}\s+\d{2}:\d{2}:\d{2}").unwrap();
-        let new_dt1 = self.new_datetime.format("%Y-%m-%d %H:%M:%S").to_string();
+        let new_dt1 = new_datetime.format("%Y-%m-%d %H:%M:%S").to_string();
         result = re1.replace_all(&result, new_dt1.as_str()).to_string();
 
         // 匹配 YYYY/MM/DD 格式
         let re2 = Regex::new(r"\d{4}/\d{2}/\d{2}").unwrap();
-        let new_dt2 = self.new_datetime.format("%Y/%m/%d").to_string();
+        let new_dt2 = new_datetime.format("%Y/%m/%d").to_string();
         result = re2.replace_all(&result, new_dt2.as_str()).to_string();
 
         // 匹配 MM/DD/YYYY 格式
         let re3 = Regex::new(r"\d{2}/\d{2}/\d{4}").unwrap();
-        let new_dt3 = self.new_datetime.format("%m/%d/%Y").to_string();
+        let new_dt3 = new_datetime.format("%m/%d/%Y").to_string();
         result = re3.replace_all(&result, new_dt3.as_str()).to_string();
 
         // 匹配纯日期 YYYY-MM-DD
         let re4 = Regex::new(r"\d{4}-\d{2}-\d{2}").unwrap();
-        let new_dt4 = self.new_datetime.format("%Y-%m-%d").to_string();
+        let new_dt4 = new_datetime.format("%Y-%m-%d").to_string();
         result = re4.replace_all(&result, new_dt4.as_str()).to_string();
 
         Ok(result)
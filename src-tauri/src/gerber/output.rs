@@ -0,0 +1,28 @@
+use crate::gerber::error::Result;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+/// 先写入同目录下的临时文件，成功后再重命名为 `path`
+///
+/// 写入失败时临时文件被删除，目标路径上不会出现写了一半的文件。
+pub fn write_atomic(path: &Path, write: impl FnOnce(&mut File) -> Result<()>) -> Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let mut temp = tempfile::Builder::new()
+        .prefix(".ghostpcb-")
+        .suffix(".tmp")
+        .tempfile_in(dir)?;
+
+    write(temp.as_file_mut())?;
+    temp.as_file().sync_all()?;
+    temp.persist(path).map_err(|e| e.error)?;
+    Ok(())
+}
+
+/// 以原子方式写入整个文件
+pub fn write_file_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    write_atomic(path, |file| Ok(file.write_all(contents)?))
+}
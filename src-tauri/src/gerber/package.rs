@@ -0,0 +1,764 @@
+use crate::gerber::board::{Board, Layer};
+use crate::gerber::error::{GhostPcbError, Result};
+use crate::gerber::output::write_atomic;
+use crate::gerber::types::GerberFileType;
+use chrono::{Datelike, NaiveDate, NaiveDateTime, Timelike};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::fs::{self, File};
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+use walkdir::WalkDir;
+use zip::read::ZipFile;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, DateTime, HasZipMetadata, ZipArchive, ZipWriter};
+
+/// 最多解开几层只含一个 ZIP 的压缩包
+const MAX_NESTED_ZIPS: usize = 3;
+
+/// 规范化打包时所有条目的修改时间：1980-01-01 00:00:00，ZIP 能表示的最早时间
+const NORMALIZED_UNIX_TIME: u64 = 315_532_800;
+
+/// 读取压缩包时的限制，防止路径穿越与解压炸弹
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ArchiveLimits {
+    pub max_entries: usize,
+    /// 单个文件解压后的最大字节数
+    pub max_file_size: u64,
+    /// 所有文件解压后的总字节数上限
+    pub max_total_size: u64,
+}
+
+impl Default for ArchiveLimits {
+    fn default() -> Self {
+        Self {
+            max_entries: 10_000,
+            max_file_size: 256 * 1024 * 1024,
+            max_total_size: 1024 * 1024 * 1024,
+        }
+    }
+}
+
+/// 输入包的容器类型，默认按输入的类型写出
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PackageFormat {
+    #[default]
+    Zip,
+    Tar,
+    TarGz,
+    Directory,
+}
+
+impl PackageFormat {
+    /// 输出文件名的扩展名，目录没有扩展名
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Zip => ".zip",
+            Self::Tar => ".tar",
+            Self::TarGz => ".tar.gz",
+            Self::Directory => "",
+        }
+    }
+
+    /// 按文件头识别压缩包类型，无法识别的按 ZIP 读取
+    fn sniff(file: &mut File) -> Result<Self> {
+        let mut head = Vec::with_capacity(262);
+        (&mut *file).take(262).read_to_end(&mut head)?;
+        file.seek(SeekFrom::Start(0))?;
+
+        Ok(if head.starts_with(&[0x1f, 0x8b]) {
+            Self::TarGz
+        } else if head.get(257..262) == Some(b"ustar") {
+            Self::Tar
+        } else {
+            Self::Zip
+        })
+    }
+}
+
+/// 重新打包的方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RepackMode {
+    /// 保留原有的条目顺序、压缩方式、修改时间、权限与包注释，与输入对比时只有内容差异
+    ///
+    /// zip 库不能写条目注释，读到的条目注释留在 [`EntryMeta`] 中，写出时丢弃并给出警告，
+    /// 见 [`Package::dropped_comments`]。
+    #[default]
+    Preserve,
+    /// 规范化：统一压缩方式，修改时间固定为 1980-01-01，去掉注释
+    Normalize,
+}
+
+/// 写出输出包的选项，`sort_entries` 等只在规范化时生效
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RepackOptions {
+    pub mode: RepackMode,
+    /// 按名称排序条目
+    pub sort_entries: bool,
+    /// 是否压缩，关闭时 ZIP 条目以 Stored 方式存储
+    pub compress: bool,
+    /// Deflate 压缩级别，缺省使用库的默认级别
+    pub compression_level: Option<i64>,
+}
+
+impl Default for RepackOptions {
+    fn default() -> Self {
+        Self {
+            mode: RepackMode::Preserve,
+            sort_entries: true,
+            compress: true,
+            compression_level: None,
+        }
+    }
+}
+
+impl RepackOptions {
+    fn normalize(&self) -> bool {
+        self.mode == RepackMode::Normalize
+    }
+}
+
+/// 去掉包扩展名（`.zip`、`.tar`、`.tar.gz`、`.tgz`）后的文件名
+pub fn package_stem(path: &Path) -> &str {
+    let name = path.file_name().and_then(|s| s.to_str()).unwrap_or("");
+    let lower = name.to_ascii_lowercase();
+    for ext in [".tar.gz", ".tgz", ".tar", ".zip"] {
+        if lower.len() > ext.len() && lower.ends_with(ext) {
+            return &name[..name.len() - ext.len()];
+        }
+    }
+    name
+}
+
+/// 输出包旁的附属文件，如 `Gerber_PCB1.tar.gz` 旁的 `Gerber_PCB1.report.json`
+pub fn path_beside(package: &Path, suffix: &str) -> PathBuf {
+    package.with_file_name(format!("{}.{}", package_stem(package), suffix))
+}
+
+/// 条目在原包中的元数据，按原样重新打包时写回
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EntryMeta {
+    /// ZIP 中的压缩方式，来自 tar 包或目录的条目没有
+    pub compression: Option<CompressionMethod>,
+    pub modified: Option<NaiveDateTime>,
+    /// Unix 权限位，只取 Unix 系统写入的 ZIP 条目，DOS/FAT 条目没有
+    pub unix_mode: Option<u32>,
+    /// ZIP 条目注释，写出时无法保留
+    pub comment: String,
+}
+
+/// 包内的一个条目，名称以 `/` 结尾的是目录
+#[derive(Debug, Clone)]
+pub struct PackageEntry {
+    pub name: String,
+    pub data: Vec<u8>,
+    pub meta: EntryMeta,
+}
+
+impl PackageEntry {
+    pub fn is_dir(&self) -> bool {
+        self.name.ends_with('/')
+    }
+
+    pub fn file_type(&self) -> GerberFileType {
+        GerberFileType::from_extension(
+            Path::new(&self.name)
+                .extension()
+                .and_then(|s| s.to_str())
+                .unwrap_or(""),
+        )
+    }
+
+    /// 是否为可识别的 Gerber/钻孔文件
+    pub fn is_recognized(&self) -> bool {
+        !self.is_dir() && !matches!(self.file_type(), GerberFileType::Unknown)
+    }
+
+    fn is_zip(&self) -> bool {
+        !self.is_dir() && self.name.to_ascii_lowercase().ends_with(".zip")
+    }
+}
+
+/// 一次性读入内存的输入包，保持条目原有顺序
+///
+/// 目录、ZIP、tar 与 tar.gz 都读成同样的条目列表。
+/// 各变体都从同一个包生成，输出时未识别的文件原样写回。
+#[derive(Debug, Clone, Default)]
+pub struct Package {
+    pub entries: Vec<PackageEntry>,
+    /// 输入的容器类型
+    pub format: PackageFormat,
+    /// ZIP 的包注释
+    pub comment: Vec<u8>,
+}
+
+impl Package {
+    pub fn open(path: &Path) -> Result<Self> {
+        Self::open_with(path, &ArchiveLimits::default())
+    }
+
+    /// 读取目录或压缩包，压缩包类型按文件头识别
+    pub fn open_with(path: &Path, limits: &ArchiveLimits) -> Result<Self> {
+        if path.is_dir() {
+            return Self::read_dir_with(path, limits);
+        }
+
+        let mut file = File::open(path)?;
+        match PackageFormat::sniff(&mut file)? {
+            PackageFormat::Tar => Self::read_tar(file, PackageFormat::Tar, limits),
+            PackageFormat::TarGz => {
+                Self::read_tar(GzDecoder::new(file), PackageFormat::TarGz, limits)
+            }
+            _ => Self::read_zip_from(file, limits),
+        }
+    }
+
+    pub fn read_zip(path: &Path) -> Result<Self> {
+        Self::read_zip_with(path, &ArchiveLimits::default())
+    }
+
+    /// 读取 ZIP，拒绝绝对路径、`..` 穿越与符号链接，并按 `limits` 限制解压大小
+    ///
+    /// 包内只有一个 ZIP 文件时（转发时常见的套娃包）读取里层的 ZIP。
+    pub fn read_zip_with(path: &Path, limits: &ArchiveLimits) -> Result<Self> {
+        Self::read_zip_from(File::open(path)?, limits)
+    }
+
+    fn read_zip_from<R: Read + Seek>(reader: R, limits: &ArchiveLimits) -> Result<Self> {
+        let mut package = Self::read_zip_entries(reader, limits)?;
+        for _ in 0..MAX_NESTED_ZIPS {
+            let mut files = package.entries.iter().filter(|e| !e.is_dir());
+            let inner = match (files.next(), files.next()) {
+                (Some(only), None) if only.is_zip() => Cursor::new(only.data.clone()),
+                _ => break,
+            };
+            package = Self::read_zip_entries(inner, limits)?;
+        }
+        Ok(package)
+    }
+
+    fn read_zip_entries<R: Read + Seek>(reader: R, limits: &ArchiveLimits) -> Result<Self> {
+        let mut archive = ZipArchive::new(reader)?;
+        if archive.len() > limits.max_entries {
+            return Err(unsafe_archive(format!(
+                "条目数 {} 超过上限 {}",
+                archive.len(),
+                limits.max_entries
+            )));
+        }
+
+        let mut entries = EntryReader::new(limits);
+        for i in 0..archive.len() {
+            let entry = archive.by_index(i)?;
+            let name = entry.name().to_string();
+            if is_absolute(&name) || entry.enclosed_name().is_none() {
+                return Err(unsafe_archive(format!("路径越出压缩包: {}", name)));
+            }
+            if entry.is_symlink() {
+                return Err(unsafe_archive(format!("不支持符号链接: {}", name)));
+            }
+            let meta = EntryMeta {
+                compression: Some(entry.compression()),
+                modified: entry.last_modified().and_then(from_zip_time),
+                unix_mode: entry.unix_mode().filter(|_| made_by_unix(&entry)),
+                comment: entry.comment().to_string(),
+            };
+            let size = entry.size();
+            entries.push(name, meta, size, entry)?;
+        }
+
+        Ok(Self {
+            entries: entries.finish(),
+            format: PackageFormat::Zip,
+            comment: archive.comment().to_vec(),
+        })
+    }
+
+    /// 读取 tar 或 tar.gz，链接与设备文件一律拒绝
+    fn read_tar<R: Read>(reader: R, format: PackageFormat, limits: &ArchiveLimits) -> Result<Self> {
+        let mut archive = tar::Archive::new(reader);
+        let mut entries = EntryReader::new(limits);
+        for entry in archive.entries()? {
+            let entry = entry?;
+            let kind = entry.header().entry_type();
+            // git archive 等工具写入的全局扩展头不是文件
+            if kind == tar::EntryType::XGlobalHeader {
+                continue;
+            }
+            let path = entry.path()?.into_owned();
+            let name = match relative_name(&path)? {
+                Some(name) => name,
+                // `tar -C dir .` 写入的 `./` 即包的根目录
+                None if kind.is_dir() => continue,
+                None => return Err(unsafe_archive(format!("条目缺少名称: {}", path.display()))),
+            };
+            let header = entry.header();
+            let meta = EntryMeta {
+                modified: header.mtime().ok().and_then(from_unix_time),
+                unix_mode: header.mode().ok(),
+                ..Default::default()
+            };
+            if kind.is_dir() {
+                entries.push_dir(name, meta)?;
+            } else if kind.is_file() {
+                let size = entry.size();
+                entries.push(name, meta, size, entry)?;
+            } else {
+                return Err(unsafe_archive(format!("不支持的条目类型: {}", name)));
+            }
+        }
+
+        Ok(Self {
+            entries: entries.finish(),
+            format,
+            comment: Vec::new(),
+        })
+    }
+
+    /// 读取目录，条目按名称排序，不跟随符号链接
+    fn read_dir_with(root: &Path, limits: &ArchiveLimits) -> Result<Self> {
+        let mut entries = EntryReader::new(limits);
+        for entry in WalkDir::new(root).min_depth(1).sort_by_file_name() {
+            let entry = entry.map_err(|e| GhostPcbError::IoError(e.into()))?;
+            let relative = entry.path().strip_prefix(root).unwrap_or(entry.path());
+            let Some(name) = relative_name(relative)? else {
+                continue;
+            };
+            let kind = entry.file_type();
+            let metadata = entry.metadata().ok();
+            let meta = EntryMeta {
+                modified: metadata
+                    .as_ref()
+                    .and_then(|m| m.modified().ok())
+                    .map(|t| chrono::DateTime::<chrono::Utc>::from(t).naive_utc()),
+                ..Default::default()
+            };
+            if kind.is_dir() {
+                entries.push_dir(name, meta)?;
+            } else if kind.is_file() {
+                let size = metadata.map(|m| m.len()).unwrap_or(0);
+                entries.push(name, meta, size, File::open(entry.path())?)?;
+            } else {
+                return Err(unsafe_archive(format!("不支持符号链接: {}", name)));
+            }
+        }
+
+        Ok(Self {
+            entries: entries.finish(),
+            format: PackageFormat::Directory,
+            comment: Vec::new(),
+        })
+    }
+
+    /// 所有文件（不含目录）的名称
+    pub fn file_names(&self) -> impl Iterator<Item = &str> {
+        self.entries
+            .iter()
+            .filter(|e| !e.is_dir())
+            .map(|e| e.name.as_str())
+    }
+
+    /// 按 `format` 与 `options` 写出时无法沿用原压缩方式、改用 Deflate 的条目
+    pub fn recompressed(
+        &self,
+        format: PackageFormat,
+        options: &RepackOptions,
+    ) -> impl Iterator<Item = (&str, CompressionMethod)> {
+        let preserve = format == PackageFormat::Zip && !options.normalize();
+        self.entries
+            .iter()
+            .filter(move |_| preserve)
+            .filter_map(|e| match e.meta.compression {
+                Some(method) if !is_writable(method) => Some((e.name.as_str(), method)),
+                _ => None,
+            })
+    }
+
+    /// 保留模式下带有注释、写出时注释会丢失的条目
+    pub fn dropped_comments(&self, options: &RepackOptions) -> impl Iterator<Item = &str> {
+        let preserve = !options.normalize();
+        self.entries
+            .iter()
+            .filter(move |e| preserve && !e.meta.comment.is_empty())
+            .map(|e| e.name.as_str())
+    }
+
+    /// 未识别、将原样保留的文件
+    pub fn unrecognized(&self) -> impl Iterator<Item = &str> {
+        self.entries
+            .iter()
+            .filter(|e| !e.is_dir() && !e.is_recognized())
+            .map(|e| e.name.as_str())
+    }
+
+    /// 所有可识别的文件组成的板模型，每个文件按自身编码解码
+    pub fn board(&self) -> Board {
+        Board::new(
+            self.entries
+                .iter()
+                .filter(|e| e.is_recognized())
+                .map(|e| Layer::from_bytes(&e.name, e.file_type(), &e.data))
+                .collect(),
+        )
+    }
+
+    /// 按输入的容器类型写出，保留原有的元数据
+    pub fn write(&self, path: &Path, board: &Board) -> Result<()> {
+        self.write_as(path, self.format, board, &RepackOptions::default())
+    }
+
+    /// 按 `format` 写出，板中各层的内容替换同名条目
+    ///
+    /// 先写入临时文件（或临时目录），完整写出后才出现在 `path`。
+    pub fn write_as(
+        &self,
+        path: &Path,
+        format: PackageFormat,
+        board: &Board,
+        options: &RepackOptions,
+    ) -> Result<()> {
+        match format {
+            PackageFormat::Zip => {
+                write_atomic(path, |file| self.write_zip_to(file, board, options))
+            }
+            PackageFormat::Tar => {
+                write_atomic(path, |file| self.write_tar_to(file, board, options))
+            }
+            PackageFormat::TarGz => write_atomic(path, |file| {
+                let level = match options.compression_level {
+                    Some(level) if options.normalize() => {
+                        Compression::new(level.clamp(0, 9) as u32)
+                    }
+                    _ => Compression::default(),
+                };
+                let mut gz = GzEncoder::new(file, level);
+                self.write_tar_to(&mut gz, board, options)?;
+                gz.finish()?;
+                Ok(())
+            }),
+            PackageFormat::Directory => self.write_dir(path, board, options),
+        }
+    }
+
+    pub fn write_zip(&self, path: &Path, board: &Board) -> Result<()> {
+        self.write_as(path, PackageFormat::Zip, board, &RepackOptions::default())
+    }
+
+    pub fn write_zip_to<W: Write>(
+        &self,
+        mut writer: W,
+        board: &Board,
+        options: &RepackOptions,
+    ) -> Result<()> {
+        let entries = self.ordered(options);
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+
+        for entry in &entries {
+            let file_options = zip_options(&entry.meta, options);
+            if entry.is_dir() {
+                zip.add_directory(entry.name.as_str(), file_options)?;
+                continue;
+            }
+
+            zip.start_file(entry.name.as_str(), file_options)?;
+            zip.write_all(&contents(entry, board)?)?;
+        }
+
+        if !options.normalize() {
+            zip.set_raw_comment(self.comment.clone().into_boxed_slice());
+        }
+        writer.write_all(zip.finish()?.get_ref())?;
+        Ok(())
+    }
+
+    pub fn write_tar_to<W: Write>(
+        &self,
+        writer: W,
+        board: &Board,
+        options: &RepackOptions,
+    ) -> Result<()> {
+        let mut tar = tar::Builder::new(writer);
+
+        for entry in self.ordered(options) {
+            let mut header = tar::Header::new_gnu();
+            let default_mode = if entry.is_dir() { 0o755 } else { 0o644 };
+            if options.normalize() {
+                header.set_mtime(NORMALIZED_UNIX_TIME);
+                header.set_mode(default_mode);
+            } else {
+                // 没有修改时间的条目取固定时间，不读时钟，同一种子的输出保持一致
+                let mtime = entry.meta.modified.map(|t| t.and_utc().timestamp());
+                header.set_mtime(
+                    mtime
+                        .and_then(|t| u64::try_from(t).ok())
+                        .unwrap_or(NORMALIZED_UNIX_TIME),
+                );
+                header.set_mode(entry.meta.unix_mode.unwrap_or(default_mode));
+            }
+
+            if entry.is_dir() {
+                header.set_entry_type(tar::EntryType::Directory);
+                header.set_size(0);
+                tar.append_data(&mut header, &entry.name, std::io::empty())?;
+                continue;
+            }
+
+            let data = contents(entry, board)?;
+            header.set_size(data.len() as u64);
+            tar.append_data(&mut header, &entry.name, data.as_ref())?;
+        }
+
+        tar.into_inner()?;
+        Ok(())
+    }
+
+    /// 先写入同级的临时目录，完成后重命名为 `path`，已有的同名目录被替换
+    fn write_dir(&self, path: &Path, board: &Board, options: &RepackOptions) -> Result<()> {
+        let parent = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let temp = tempfile::Builder::new()
+            .prefix(".ghostpcb-")
+            .tempdir_in(parent)?;
+
+        for entry in &self.entries {
+            let target = temp.path().join(&entry.name);
+            if entry.is_dir() {
+                fs::create_dir_all(&target)?;
+                continue;
+            }
+            if let Some(dir) = target.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            let mut file = File::create(&target)?;
+            file.write_all(&contents(entry, board)?)?;
+            if let (Some(modified), false) = (entry.meta.modified, options.normalize()) {
+                file.set_modified(modified.and_utc().into())?;
+            }
+        }
+
+        // 旧目录先移到一旁，新目录就位后再删除；移入失败时放回旧目录
+        let backup = path.exists().then(|| temp.path().with_extension("old"));
+        if let Some(backup) = &backup {
+            fs::rename(path, backup)?;
+        }
+        let staged = temp.keep();
+        if let Err(e) = fs::rename(&staged, path) {
+            let _ = fs::remove_dir_all(&staged);
+            if let Some(backup) = &backup {
+                if fs::rename(backup, path).is_err() {
+                    return Err(GhostPcbError::ProcessError(format!(
+                        "替换输出目录失败 ({})，原目录保留在 {}",
+                        e,
+                        backup.display()
+                    )));
+                }
+            }
+            return Err(e.into());
+        }
+        if let Some(backup) = backup {
+            // 新目录已就位，旧目录删不掉也不影响结果
+            let _ = fs::remove_dir_all(backup);
+        }
+        Ok(())
+    }
+
+    /// 写出时的条目顺序，规范化且要求排序时按名称排序
+    fn ordered(&self, options: &RepackOptions) -> Vec<&PackageEntry> {
+        let mut entries: Vec<&PackageEntry> = self.entries.iter().collect();
+        if options.normalize() && options.sort_entries {
+            entries.sort_by(|a, b| a.name.cmp(&b.name));
+        }
+        entries
+    }
+}
+
+/// 条目写出时的内容：板中有同名层时用层的内容
+fn contents<'a>(entry: &'a PackageEntry, board: &Board) -> Result<Cow<'a, [u8]>> {
+    Ok(match board.layers.iter().find(|l| l.name == entry.name) {
+        Some(layer) => Cow::Owned(layer.to_bytes()?),
+        None => Cow::Borrowed(&entry.data),
+    })
+}
+
+/// 逐个读入条目，累计条目数与解压后的大小
+struct EntryReader<'a> {
+    limits: &'a ArchiveLimits,
+    entries: Vec<PackageEntry>,
+    total: u64,
+}
+
+impl<'a> EntryReader<'a> {
+    fn new(limits: &'a ArchiveLimits) -> Self {
+        Self {
+            limits,
+            entries: Vec::new(),
+            total: 0,
+        }
+    }
+
+    fn push_dir(&mut self, mut name: String, meta: EntryMeta) -> Result<()> {
+        if !name.ends_with('/') {
+            name.push('/');
+        }
+        self.push(name, meta, 0, std::io::empty())
+    }
+
+    /// 目录名以 `/` 结尾，`size` 为声明的大小
+    fn push(&mut self, name: String, meta: EntryMeta, size: u64, reader: impl Read) -> Result<()> {
+        let limits = self.limits;
+        if self.entries.len() >= limits.max_entries {
+            return Err(unsafe_archive(format!(
+                "条目数超过上限 {}",
+                limits.max_entries
+            )));
+        }
+        if size > limits.max_file_size {
+            return Err(too_large(&name, limits.max_file_size));
+        }
+
+        // 声明的大小不可信，按实际解压的字节数计数
+        let mut data = Vec::with_capacity(size as usize);
+        reader
+            .take(limits.max_file_size + 1)
+            .read_to_end(&mut data)?;
+        if data.len() as u64 > limits.max_file_size {
+            return Err(too_large(&name, limits.max_file_size));
+        }
+        self.total += data.len() as u64;
+        if self.total > limits.max_total_size {
+            return Err(unsafe_archive(format!(
+                "解压后总大小超过上限 {} 字节",
+                limits.max_total_size
+            )));
+        }
+
+        self.entries.push(PackageEntry { name, data, meta });
+        Ok(())
+    }
+
+    fn finish(self) -> Vec<PackageEntry> {
+        self.entries
+    }
+}
+
+/// zip 库能写出的压缩方式，Deflate64、LZMA 只能读取
+fn is_writable(method: CompressionMethod) -> bool {
+    matches!(
+        method,
+        CompressionMethod::Stored
+            | CompressionMethod::Deflated
+            | CompressionMethod::Bzip2
+            | CompressionMethod::Zstd
+            | CompressionMethod::Xz
+    )
+}
+
+/// 条目是否由 Unix 系统写入，DOS/FAT 条目的权限位是由只读属性推算的
+fn made_by_unix(entry: &ZipFile) -> bool {
+    /// APPNOTE 4.4.2 中 Unix 的系统编号
+    const UNIX: u8 = 3;
+    entry.get_metadata().system as u8 == UNIX
+}
+
+/// 单个 ZIP 条目的写出选项
+///
+/// 保留模式下沿用原有的压缩方式、修改时间与权限；无法写出的压缩方式改用 Deflate，
+/// 见 [`Package::recompressed`]。
+fn zip_options(meta: &EntryMeta, options: &RepackOptions) -> SimpleFileOptions {
+    let base = SimpleFileOptions::default();
+    if options.normalize() {
+        let method = if options.compress {
+            CompressionMethod::Deflated
+        } else {
+            CompressionMethod::Stored
+        };
+        return base
+            .compression_method(method)
+            .compression_level(options.compression_level.filter(|_| options.compress))
+            .last_modified_time(DateTime::default());
+    }
+
+    let method = match meta.compression {
+        Some(method) if is_writable(method) => method,
+        _ => CompressionMethod::Deflated,
+    };
+    let mut file_options = base.compression_method(method);
+    if let Some(time) = meta.modified.and_then(to_zip_time) {
+        file_options = file_options.last_modified_time(time);
+    }
+    // DOS/FAT 条目读入时没有权限位，不替它补上 Unix 权限
+    if let Some(mode) = meta.unix_mode {
+        file_options = file_options.unix_permissions(mode);
+    }
+    file_options
+}
+
+fn from_zip_time(time: DateTime) -> Option<NaiveDateTime> {
+    NaiveDate::from_ymd_opt(time.year() as i32, time.month() as u32, time.day() as u32)?
+        .and_hms_opt(
+            time.hour() as u32,
+            time.minute() as u32,
+            time.second() as u32,
+        )
+}
+
+/// ZIP 只能表示 1980-2107 年、精度 2 秒的时间
+fn to_zip_time(time: NaiveDateTime) -> Option<DateTime> {
+    DateTime::from_date_and_time(
+        u16::try_from(time.year()).ok()?,
+        time.month() as u8,
+        time.day() as u8,
+        time.hour() as u8,
+        time.minute() as u8,
+        time.second() as u8,
+    )
+    .ok()
+}
+
+fn from_unix_time(seconds: u64) -> Option<NaiveDateTime> {
+    chrono::DateTime::from_timestamp(i64::try_from(seconds).ok()?, 0).map(|t| t.naive_utc())
+}
+
+/// 以 `/` 分隔的相对路径，为空或只有 `.` 时返回 `None`
+///
+/// 含 `..`、根目录或盘符时视为越界。
+fn relative_name(path: &Path) -> Result<Option<String>> {
+    let mut parts = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_str().ok_or_else(|| {
+                GhostPcbError::InvalidFileName(path.to_string_lossy().to_string())
+            })?),
+            Component::CurDir => {}
+            _ => {
+                return Err(unsafe_archive(format!(
+                    "路径越出压缩包: {}",
+                    path.display()
+                )))
+            }
+        }
+    }
+    Ok((!parts.is_empty()).then(|| parts.join("/")))
+}
+
+/// 以 `/`、`\` 或盘符开头的名称
+fn is_absolute(name: &str) -> bool {
+    name.starts_with('/') || name.starts_with('\\') || name.chars().nth(1) == Some(':')
+}
+
+fn too_large(name: &str, limit: u64) -> GhostPcbError {
+    unsafe_archive(format!("{} 解压后超过单文件上限 {} 字节", name, limit))
+}
+
+fn unsafe_archive(message: String) -> GhostPcbError {
+    GhostPcbError::UnsafeArchive(message)
+}
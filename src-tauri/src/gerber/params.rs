@@ -0,0 +1,367 @@
+use crate::gerber::error::{GhostPcbError, Result};
+use crate::gerber::profile::CapabilityProfile;
+use crate::gerber::types::GerberFileType;
+use serde::{Deserialize, Serialize};
+
+/// 各处理阶段的数值参数
+///
+/// 所有尺寸均为毫米。位移类参数为 `None` 时取工艺配置中的容差。
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StageParams {
+    pub displacement: DisplacementParams,
+    pub physical: PhysicalParams,
+    pub geometry: GeometryParams,
+    pub silkscreen: SilkscreenParams,
+    pub structure: StructureParams,
+    pub timestamp: TimestampParams,
+}
+
+/// 跨层一致位移
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DisplacementParams {
+    /// 最大位移，缺省为 feature_jitter_mm
+    pub amplitude_mm: Option<f64>,
+    /// 位移场的最短波长，越大越平滑
+    pub min_wavelength_mm: f64,
+}
+
+impl Default for DisplacementParams {
+    fn default() -> Self {
+        Self {
+            amplitude_mm: None,
+            min_wavelength_mm: 10.0,
+        }
+    }
+}
+
+/// 板框整体偏移
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PhysicalParams {
+    /// 偏移范围，缺省为 outline_offset_min_mm / outline_offset_max_mm
+    pub offset_min_mm: Option<f64>,
+    pub offset_max_mm: Option<f64>,
+    pub layers: Vec<GerberFileType>,
+}
+
+impl Default for PhysicalParams {
+    fn default() -> Self {
+        Self {
+            offset_min_mm: None,
+            offset_max_mm: None,
+            layers: vec![GerberFileType::BoardOutline],
+        }
+    }
+}
+
+/// 孔位与铜层的局部扰动，与之对位的阻焊、钢网层一起移动
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GeometryParams {
+    /// 钻孔偏移上限，缺省为 drill_jitter_mm
+    pub drill_jitter_mm: Option<f64>,
+    /// 铜层坐标偏移上限，缺省为 feature_jitter_mm
+    pub copper_jitter_mm: Option<f64>,
+    pub layers: Vec<GerberFileType>,
+}
+
+impl Default for GeometryParams {
+    fn default() -> Self {
+        Self {
+            drill_jitter_mm: None,
+            copper_jitter_mm: None,
+            layers: vec![
+                GerberFileType::TopLayer,
+                GerberFileType::BottomLayer,
+                GerberFileType::InnerLayer,
+                GerberFileType::Drill,
+            ],
+        }
+    }
+}
+
+/// 丝印层整体平移
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SilkscreenParams {
+    pub shift_min_mm: f64,
+    pub shift_max_mm: f64,
+    pub layers: Vec<GerberFileType>,
+}
+
+impl Default for SilkscreenParams {
+    fn default() -> Self {
+        Self {
+            shift_min_mm: 0.01,
+            shift_max_mm: 0.03,
+            layers: vec![
+                GerberFileType::TopSilkscreen,
+                GerberFileType::BottomSilkscreen,
+            ],
+        }
+    }
+}
+
+/// 文件结构混淆
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StructureParams {
+    /// 在文件头插入随机 Build ID 注释
+    pub build_id_comment: bool,
+    /// 每行之后重复当前 D-code 选择的概率
+    pub redundant_dcode_probability: f64,
+    pub layers: Vec<GerberFileType>,
+}
+
+impl Default for StructureParams {
+    fn default() -> Self {
+        Self {
+            build_id_comment: true,
+            redundant_dcode_probability: 0.05,
+            layers: GerberFileType::gerber_layers().to_vec(),
+        }
+    }
+}
+
+/// 时间戳修改
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimestampParams {
+    /// 新时间距今的天数范围
+    pub days_ago_min: u32,
+    pub days_ago_max: u32,
+    /// 新时间落在一天中的小时范围 [start, end)
+    pub hour_start: u32,
+    pub hour_end: u32,
+}
+
+impl Default for TimestampParams {
+    fn default() -> Self {
+        Self {
+            days_ago_min: 1,
+            days_ago_max: 29,
+            hour_start: 8,
+            hour_end: 18,
+        }
+    }
+}
+
+impl StageParams {
+    /// 检查参数自身是否合法，以及是否超出工艺配置允许的范围
+    pub fn validate(&self, profile: &CapabilityProfile) -> Result<()> {
+        let tol = &profile.tolerance;
+
+        let d = &self.displacement;
+        if let Some(amplitude) = d.amplitude_mm {
+            non_negative("displacement.amplitude_mm", amplitude)?;
+            if amplitude * 2.0 >= profile.min_space_mm {
+                return Err(invalid(
+                    "displacement.amplitude_mm 过大，可能导致线距低于 min_space_mm",
+                ));
+            }
+        }
+        positive("displacement.min_wavelength_mm", d.min_wavelength_mm)?;
+
+        let p = &self.physical;
+        let offset_min = p.offset_min_mm.unwrap_or(tol.outline_offset_min_mm);
+        let offset_max = p.offset_max_mm.unwrap_or(tol.outline_offset_max_mm);
+        non_negative("physical.offset_min_mm", offset_min)?;
+        non_negative("physical.offset_max_mm", offset_max)?;
+        ordered("physical.offset_min_mm", offset_min, offset_max)?;
+        if tol.outline_jitter_mm + offset_max >= profile.edge_clearance_mm {
+            return Err(invalid(
+                "physical.offset_max_mm 过大，可能导致板边距低于 edge_clearance_mm",
+            ));
+        }
+
+        let g = &self.geometry;
+        if let Some(drill) = g.drill_jitter_mm {
+            non_negative("geometry.drill_jitter_mm", drill)?;
+            if drill >= profile.min_annular_ring_mm {
+                return Err(invalid(
+                    "geometry.drill_jitter_mm 过大，可能导致焊环低于 min_annular_ring_mm",
+                ));
+            }
+        }
+        if let Some(copper) = g.copper_jitter_mm {
+            non_negative("geometry.copper_jitter_mm", copper)?;
+            if copper * 2.0 >= profile.min_space_mm {
+                return Err(invalid(
+                    "geometry.copper_jitter_mm 过大，可能导致线距低于 min_space_mm",
+                ));
+            }
+        }
+
+        let s = &self.silkscreen;
+        non_negative("silkscreen.shift_min_mm", s.shift_min_mm)?;
+        non_negative("silkscreen.shift_max_mm", s.shift_max_mm)?;
+        ordered("silkscreen.shift_min_mm", s.shift_min_mm, s.shift_max_mm)?;
+
+        let probability = self.structure.redundant_dcode_probability;
+        if !(probability.is_finite() && (0.0..=1.0).contains(&probability)) {
+            return Err(invalid(&format!(
+                "structure.redundant_dcode_probability 必须在 0 到 1 之间: {}",
+                probability
+            )));
+        }
+
+        let t = &self.timestamp;
+        if t.days_ago_min > t.days_ago_max {
+            return Err(invalid("timestamp.days_ago_min 不能大于 days_ago_max"));
+        }
+        if t.hour_start >= t.hour_end || t.hour_end > 24 {
+            return Err(invalid(
+                "timestamp 小时范围无效，应满足 hour_start < hour_end <= 24",
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// 规则中对阶段参数的部分覆盖，`None` 表示沿用之前的设置
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StageParamsPatch {
+    pub displacement: DisplacementPatch,
+    pub physical: PhysicalPatch,
+    pub geometry: GeometryPatch,
+    pub silkscreen: SilkscreenPatch,
+    pub structure: StructurePatch,
+    pub timestamp: TimestampPatch,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DisplacementPatch {
+    pub amplitude_mm: Option<f64>,
+    pub min_wavelength_mm: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PhysicalPatch {
+    pub offset_min_mm: Option<f64>,
+    pub offset_max_mm: Option<f64>,
+    pub layers: Option<Vec<GerberFileType>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GeometryPatch {
+    pub drill_jitter_mm: Option<f64>,
+    pub copper_jitter_mm: Option<f64>,
+    pub layers: Option<Vec<GerberFileType>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SilkscreenPatch {
+    pub shift_min_mm: Option<f64>,
+    pub shift_max_mm: Option<f64>,
+    pub layers: Option<Vec<GerberFileType>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StructurePatch {
+    pub build_id_comment: Option<bool>,
+    pub redundant_dcode_probability: Option<f64>,
+    pub layers: Option<Vec<GerberFileType>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimestampPatch {
+    pub days_ago_min: Option<u32>,
+    pub days_ago_max: Option<u32>,
+    pub hour_start: Option<u32>,
+    pub hour_end: Option<u32>,
+}
+
+impl StageParamsPatch {
+    /// 只覆盖给出的字段
+    pub fn apply(&self, params: &mut StageParams) {
+        let d = &self.displacement;
+        if d.amplitude_mm.is_some() {
+            params.displacement.amplitude_mm = d.amplitude_mm;
+        }
+        set(
+            &mut params.displacement.min_wavelength_mm,
+            &d.min_wavelength_mm,
+        );
+
+        let p = &self.physical;
+        if p.offset_min_mm.is_some() {
+            params.physical.offset_min_mm = p.offset_min_mm;
+        }
+        if p.offset_max_mm.is_some() {
+            params.physical.offset_max_mm = p.offset_max_mm;
+        }
+        set(&mut params.physical.layers, &p.layers);
+
+        let g = &self.geometry;
+        if g.drill_jitter_mm.is_some() {
+            params.geometry.drill_jitter_mm = g.drill_jitter_mm;
+        }
+        if g.copper_jitter_mm.is_some() {
+            params.geometry.copper_jitter_mm = g.copper_jitter_mm;
+        }
+        set(&mut params.geometry.layers, &g.layers);
+
+        let s = &self.silkscreen;
+        set(&mut params.silkscreen.shift_min_mm, &s.shift_min_mm);
+        set(&mut params.silkscreen.shift_max_mm, &s.shift_max_mm);
+        set(&mut params.silkscreen.layers, &s.layers);
+
+        let st = &self.structure;
+        set(&mut params.structure.build_id_comment, &st.build_id_comment);
+        set(
+            &mut params.structure.redundant_dcode_probability,
+            &st.redundant_dcode_probability,
+        );
+        set(&mut params.structure.layers, &st.layers);
+
+        let t = &self.timestamp;
+        set(&mut params.timestamp.days_ago_min, &t.days_ago_min);
+        set(&mut params.timestamp.days_ago_max, &t.days_ago_max);
+        set(&mut params.timestamp.hour_start, &t.hour_start);
+        set(&mut params.timestamp.hour_end, &t.hour_end);
+    }
+}
+
+fn set<T: Clone>(field: &mut T, value: &Option<T>) {
+    if let Some(v) = value {
+        *field = v.clone();
+    }
+}
+
+fn invalid(message: &str) -> GhostPcbError {
+    GhostPcbError::InvalidOptions(message.to_string())
+}
+
+fn non_negative(field: &str, value: f64) -> Result<()> {
+    if value.is_finite() && value >= 0.0 {
+        Ok(())
+    } else {
+        Err(invalid(&format!("{} 不能为负数: {}", field, value)))
+    }
+}
+
+fn positive(field: &str, value: f64) -> Result<()> {
+    if value.is_finite() && value > 0.0 {
+        Ok(())
+    } else {
+        Err(invalid(&format!("{} 必须为正数: {}", field, value)))
+    }
+}
+
+fn ordered(field_min: &str, min: f64, max: f64) -> Result<()> {
+    if min <= max {
+        Ok(())
+    } else {
+        Err(invalid(&format!("{} 不能大于对应的最大值", field_min)))
+    }
+}
@@ -1,35 +1,293 @@
+use crate::gerber::board::{Board, Layer};
+use crate::gerber::edit::{apply_edits, diff_lines, edits_match, Edit};
 use crate::gerber::error::Result;
 use crate::gerber::obfuscators::*;
+use crate::gerber::profile::CapabilityProfile;
+use crate::gerber::seed::Seed;
 use crate::gerber::types::{GerberFileType, ObfuscateOptions};
+use chrono::{Local, NaiveDateTime};
+use rayon::prelude::*;
+
+/// 管道中的一个处理阶段
+enum Stage {
+    /// 一次作用于整块板
+    Board(Box<dyn BoardTransform>),
+    /// 逐文件处理，可读取整板上下文
+    Layer(Box<dyn BoardObfuscator>),
+}
+
+impl Stage {
+    fn name(&self) -> &'static str {
+        match self {
+            Stage::Board(t) => t.name(),
+            Stage::Layer(o) => o.name(),
+        }
+    }
+}
+
+/// 某个阶段对一个文件的修改，只记录实际改动了内容的阶段
+pub type StageEdits = (&'static str, Vec<Edit>);
+
+fn stage_names(logs: Vec<Vec<StageEdits>>) -> Vec<Vec<&'static str>> {
+    logs.into_iter()
+        .map(|stages| stages.into_iter().map(|(name, _)| name).collect())
+        .collect()
+}
 
 /// 混淆处理管道
+///
+/// 各阶段按固定顺序执行：
+/// 跨层一致位移 → 外框偏移 → 孔位扰动 → 丝印平移 → 结构混淆 → 时间戳。
+/// 坐标类阶段在前，保证结构混淆插入的内容不会被再次变换。
 pub struct ObfuscationPipeline {
-    obfuscators: Vec<Box<dyn Obfuscator>>,
+    stages: Vec<Stage>,
+    profile: CapabilityProfile,
+    seed: Seed,
 }
 
 impl ObfuscationPipeline {
-    pub fn from_options(_options: &ObfuscateOptions) -> Self {
-        let mut obfuscators: Vec<Box<dyn Obfuscator>> = Vec::new();
+    /// 使用随机种子构建管道
+    pub fn from_options(options: &ObfuscateOptions, profile: &CapabilityProfile) -> Self {
+        Self::with_seed(options, profile, Seed::random())
+    }
 
-        // 全层逐坐标独立抖动 (先抖动，后丝印整体偏移)
-        obfuscators.push(Box::new(CoordinateJitterObfuscator::new()));
-        // 丝印层整体平移
-        obfuscators.push(Box::new(SilkscreenObfuscator::new()));
+    /// 以当前时间为基准构建管道
+    pub fn with_seed(options: &ObfuscateOptions, profile: &CapabilityProfile, seed: Seed) -> Self {
+        Self::with_clock(options, profile, seed, Local::now().naive_local())
+    }
 
-        Self { obfuscators }
+    /// 时间类阶段以 `now` 为当前时间，种子与 `now` 都相同时输出完全一致
+    pub fn with_clock(
+        options: &ObfuscateOptions,
+        profile: &CapabilityProfile,
+        seed: Seed,
+        now: NaiveDateTime,
+    ) -> Self {
+        let params = &options.params;
+
+        let mut stages = Vec::new();
+        if options.displacement {
+            stages.push(Stage::Board(Box::new(
+                CoherentJitterTransform::with_params(profile, &params.displacement),
+            )));
+        }
+        if options.physical {
+            stages.push(Stage::Layer(Box::new(TextObfuscatorAdapter::new(
+                PhysicalObfuscator::with_params(profile, &params.physical),
+            ))));
+        }
+        if options.geometry {
+            stages.push(Stage::Board(Box::new(GeometryObfuscator::with_params(
+                profile,
+                &params.geometry,
+            ))));
+        }
+        if options.silkscreen {
+            stages.push(Stage::Layer(Box::new(TextObfuscatorAdapter::new(
+                SilkscreenObfuscator::with_params(&params.silkscreen),
+            ))));
+        }
+        if options.structure {
+            stages.push(Stage::Layer(Box::new(TextObfuscatorAdapter::new(
+                StructureObfuscator::with_params(&params.structure),
+            ))));
+        }
+        if options.timestamp {
+            stages.push(Stage::Layer(Box::new(TextObfuscatorAdapter::new(
+                TimestampObfuscator::with_params(
+                    &params.timestamp,
+                    now,
+                    &mut seed.stream("时间戳修改"),
+                ),
+            ))));
+        }
+
+        Self {
+            stages,
+            profile: profile.clone(),
+            seed,
+        }
     }
 
+    /// 在末尾追加一个逐文件阶段
+    pub fn push(&mut self, obfuscator: Box<dyn BoardObfuscator>) {
+        self.stages.push(Stage::Layer(obfuscator));
+    }
+
+    /// 单独处理一个文件，板级变换只作用于该文件
     pub fn process(&self, content: &str, file_type: GerberFileType) -> Result<String> {
-        let mut result = content.to_string();
+        self.process_traced(content, file_type)
+            .map(|(result, _)| result)
+    }
+
+    /// 处理单个文件并返回实际改动了内容的阶段名称
+    pub fn process_traced(
+        &self,
+        content: &str,
+        file_type: GerberFileType,
+    ) -> Result<(String, Vec<&'static str>)> {
+        let mut board = Board::new(vec![Layer::new("", file_type, content.to_string())]);
+        let mut applied = self.process_board(&mut board)?;
+        let layer = board.layers.remove(0);
+        Ok((layer.content, applied.remove(0)))
+    }
+
+    /// 处理整块板，按层返回实际改动了内容的阶段名称
+    pub fn process_board(&self, board: &mut Board) -> Result<Vec<Vec<&'static str>>> {
+        self.process_board_logged(board).map(stage_names)
+    }
+
+    /// 处理整块板，按层返回每个阶段的逐行修改
+    pub fn process_board_logged(&self, board: &mut Board) -> Result<Vec<Vec<StageEdits>>> {
+        let all: Vec<usize> = (0..board.layers.len()).collect();
+        self.process_layers(board, &all)
+    }
+
+    /// 只处理 `indices` 指定的层，其余层保持不变但仍作为上下文可见
+    ///
+    /// 返回值与 `indices` 一一对应。
+    fn process_layers(&self, board: &mut Board, indices: &[usize]) -> Result<Vec<Vec<StageEdits>>> {
+        let mut applied = vec![Vec::new(); indices.len()];
+
+        for stage in &self.stages {
+            // 每个阶段、每个文件各用一条随机数流
+            match stage {
+                Stage::Board(transform) => {
+                    // 变换作用于整块板的副本，各组按同一块板生成相同的位移场，只取回本组的层
+                    let mut moved = board.clone();
+                    transform.apply(&mut moved, &mut self.seed.stream(transform.name()))?;
+                    for (&i, log) in indices.iter().zip(&mut applied) {
+                        let layer = &moved.layers[i];
+                        let edits = diff_lines(&board.layers[i].content, &layer.content);
+                        if !edits.is_empty() {
+                            log.push((transform.name(), edits));
+                            board.layers[i] = layer.clone();
+                        }
+                    }
+                }
+                Stage::Layer(obfuscator) => {
+                    // 先基于同一块板收集所有文件的修改，再统一应用
+                    let outline = board.extent();
+                    // 各文件的修改互不依赖，并行收集
+                    let shared: &Board = board;
+                    let edits = indices
+                        .par_iter()
+                        .map(|&i| {
+                            let layer = &shared.layers[i];
+                            let mut rng =
+                                self.seed
+                                    .stream(&format!("{}/{}", obfuscator.name(), layer.name));
+                            let mut context = BoardContext {
+                                board: shared,
+                                profile: &self.profile,
+                                outline,
+                                rng: &mut rng,
+                            };
+                            obfuscator.edit(layer, &mut context)
+                        })
+                        .collect::<Result<Vec<Vec<Edit>>>>()?;
+                    for ((&i, edits), log) in indices.iter().zip(edits).zip(&mut applied) {
+                        let layer = &mut board.layers[i];
+                        let after = apply_edits(&layer.content, &edits);
+                        // 记录的原文与文件不符时按实际结果重新比较，保证记录可逆
+                        let edits = if edits_match(&layer.content, &edits) {
+                            edits
+                        } else {
+                            diff_lines(&layer.content, &after)
+                        };
+                        if after != layer.content {
+                            log.push((obfuscator.name(), edits));
+                            layer.content = after;
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(applied)
+    }
+
+    /// 按每个文件各自生效的选项处理整块板
+    ///
+    /// 位移场对整块板只生成一次，取各层中最大的幅值；其余层按自身幅值等比缩小，
+    /// 幅值相同的层严格对齐。其余阶段按选项相同的层分组执行。
+    pub fn process_board_by_layer(
+        board: &mut Board,
+        options: &[ObfuscateOptions],
+        profile: &CapabilityProfile,
+        seed: Seed,
+        now: NaiveDateTime,
+    ) -> Result<Vec<Vec<&'static str>>> {
+        Self::process_board_by_layer_logged(board, options, profile, seed, now).map(stage_names)
+    }
+
+    /// 同 [`Self::process_board_by_layer`]，按层返回每个阶段的逐行修改
+    pub fn process_board_by_layer_logged(
+        board: &mut Board,
+        options: &[ObfuscateOptions],
+        profile: &CapabilityProfile,
+        seed: Seed,
+        now: NaiveDateTime,
+    ) -> Result<Vec<Vec<StageEdits>>> {
+        let mut applied = vec![Vec::new(); board.layers.len()];
+
+        let jitters: Vec<Option<CoherentJitterTransform>> = options
+            .iter()
+            .map(|o| {
+                o.displacement
+                    .then(|| CoherentJitterTransform::with_params(profile, &o.params.displacement))
+            })
+            .collect();
+        let widest = jitters
+            .iter()
+            .flatten()
+            .max_by(|a, b| a.amplitude().as_mm().total_cmp(&b.amplitude().as_mm()));
+        if let Some(widest) = widest {
+            let field = widest.field(board, &mut seed.stream(widest.name()));
+            let full = widest.amplitude().as_mm();
+            for ((layer, jitter), log) in board.layers.iter_mut().zip(&jitters).zip(&mut applied) {
+                let Some(jitter) = jitter else { continue };
+                let scale = if full > 0.0 {
+                    jitter.amplitude().as_mm() / full
+                } else {
+                    0.0
+                };
+                let before = layer.content.clone();
+                layer.apply_field(&field.scaled(scale));
+                let edits = diff_lines(&before, &layer.content);
+                if !edits.is_empty() {
+                    log.push((widest.name(), edits));
+                }
+            }
+        }
+
+        // 选项相同的层共用一条管道，其余层作为上下文可见
+        let mut groups: Vec<(ObfuscateOptions, Vec<usize>)> = Vec::new();
+        for (i, o) in options.iter().enumerate() {
+            let layer_options = ObfuscateOptions {
+                displacement: false,
+                rules: Vec::new(),
+                ..o.clone()
+            };
+            match groups.iter_mut().find(|(g, _)| *g == layer_options) {
+                Some((_, indices)) => indices.push(i),
+                None => groups.push((layer_options, vec![i])),
+            }
+        }
 
-        for obfuscator in &self.obfuscators {
-            result = obfuscator.obfuscate(&result, file_type)?;
+        for (group_options, indices) in groups {
+            let pipeline = Self::with_clock(&group_options, profile, seed, now);
+            let group_applied = pipeline.process_layers(board, &indices)?;
+            for (i, log) in indices.into_iter().zip(group_applied) {
+                applied[i].extend(log);
+            }
         }
 
-        Ok(result)
+        Ok(applied)
     }
 
+    /// 按执行顺序列出已启用的阶段
     pub fn obfuscator_names(&self) -> Vec<&'static str> {
-        self.obfuscators.iter().map(|o| o.name()).collect()
+        self.stages.iter().map(Stage::name).collect()
     }
 }
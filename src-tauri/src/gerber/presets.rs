@@ -0,0 +1,256 @@
+use crate::gerber::error::{GhostPcbError, Result};
+use crate::gerber::output::write_file_atomic;
+use crate::gerber::profile::CapabilityProfile;
+use crate::gerber::types::ObfuscateOptions;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 当前预设格式版本
+///
+/// 1: 只有五个开关；2: 增加 params；3: 增加 displacement 与 rules。
+pub const PRESET_SCHEMA_VERSION: u32 = 3;
+
+/// 命名的选项预设
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Preset {
+    #[serde(default)]
+    pub schema_version: u32,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// 工艺配置名称或路径，缺省为 standard
+    #[serde(default)]
+    pub profile: Option<String>,
+    pub options: ObfuscateOptions,
+}
+
+impl Preset {
+    pub fn new(name: &str, options: ObfuscateOptions) -> Self {
+        Self {
+            schema_version: PRESET_SCHEMA_VERSION,
+            name: name.to_string(),
+            description: String::new(),
+            profile: None,
+            options,
+        }
+    }
+
+    /// 从 JSON 或 TOML 文本解析，旧版本格式自动迁移
+    pub fn parse(text: &str, format: PresetFormat) -> Result<Self> {
+        let value: Value = match format {
+            PresetFormat::Json => {
+                serde_json::from_str(text).map_err(|e| invalid(&e.to_string()))?
+            }
+            PresetFormat::Toml => toml::from_str(text).map_err(|e| invalid(&e.to_string()))?,
+        };
+        let preset: Self =
+            serde_json::from_value(migrate(value)?).map_err(|e| invalid(&e.to_string()))?;
+        Ok(preset)
+    }
+
+    pub fn to_text(&self, format: PresetFormat) -> Result<String> {
+        match format {
+            PresetFormat::Json => {
+                serde_json::to_string_pretty(self).map_err(|e| invalid(&e.to_string()))
+            }
+            PresetFormat::Toml => toml::to_string_pretty(self).map_err(|e| invalid(&e.to_string())),
+        }
+    }
+
+    /// 检查名称，并按预设指定的工艺配置校验选项
+    pub fn validate(&self) -> Result<()> {
+        validate_name(&self.name)?;
+        let profile = CapabilityProfile::resolve(self.profile.as_deref())?;
+        self.options.validate(&profile)
+    }
+}
+
+/// 导入导出的文件格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PresetFormat {
+    Json,
+    Toml,
+}
+
+impl PresetFormat {
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let ext = path
+            .extension()
+            .and_then(|s| s.to_str())
+            .unwrap_or("")
+            .to_lowercase();
+        match ext.as_str() {
+            "json" => Ok(Self::Json),
+            "toml" => Ok(Self::Toml),
+            _ => Err(invalid(&format!("不支持的预设格式: {}", path.display()))),
+        }
+    }
+}
+
+/// 把旧版本的预设升级到当前格式
+///
+/// 早期导出的文件只有选项本身，没有外层的名称与版本号；
+/// 缺少的选项字段按当前默认值补齐。
+fn migrate(value: Value) -> Result<Value> {
+    let mut value = match value {
+        Value::Object(map) if !map.contains_key("options") && map.contains_key("timestamp") => {
+            serde_json::json!({ "schema_version": 1, "name": "", "options": map })
+        }
+        Value::Object(map) => Value::Object(map),
+        _ => return Err(invalid("预设文件应为对象")),
+    };
+
+    let version = value
+        .get("schema_version")
+        .and_then(Value::as_u64)
+        .unwrap_or(1) as u32;
+    if version > PRESET_SCHEMA_VERSION {
+        return Err(invalid(&format!(
+            "预设格式版本 {} 高于当前支持的 {}，请升级程序",
+            version, PRESET_SCHEMA_VERSION
+        )));
+    }
+
+    let defaults =
+        serde_json::to_value(ObfuscateOptions::default()).map_err(|e| invalid(&e.to_string()))?;
+    match value.get_mut("options") {
+        Some(options @ Value::Object(_)) => fill_missing(options, &defaults),
+        _ => return Err(invalid("缺少 options")),
+    }
+    value["schema_version"] = Value::from(PRESET_SCHEMA_VERSION);
+
+    Ok(value)
+}
+
+/// 递归补齐对象中缺少的字段
+fn fill_missing(target: &mut Value, defaults: &Value) {
+    if let (Value::Object(target), Value::Object(defaults)) = (target, defaults) {
+        for (key, default) in defaults {
+            match target.get_mut(key) {
+                Some(existing) => fill_missing(existing, default),
+                None => {
+                    target.insert(key.clone(), default.clone());
+                }
+            }
+        }
+    }
+}
+
+fn validate_name(name: &str) -> Result<()> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(invalid("名称不能为空"));
+    }
+    if name.starts_with('.')
+        || name
+            .chars()
+            .any(|c| c.is_control() || "/\\:*?\"<>|".contains(c))
+    {
+        return Err(invalid(&format!("名称包含不允许的字符: {}", name)));
+    }
+    Ok(())
+}
+
+fn invalid(message: &str) -> GhostPcbError {
+    GhostPcbError::InvalidPreset(message.to_string())
+}
+
+/// 保存在应用配置目录中的预设，每个预设一个 JSON 文件
+pub struct PresetStore {
+    dir: PathBuf,
+}
+
+impl PresetStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn path_of(&self, name: &str) -> Result<PathBuf> {
+        validate_name(name)?;
+        Ok(self.dir.join(format!("{}.json", name.trim())))
+    }
+
+    /// 按名称排序列出所有预设，无法解析的文件跳过
+    pub fn list(&self) -> Result<Vec<Preset>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut presets = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|s| s.to_str()) != Some("json") {
+                continue;
+            }
+            if let Ok(preset) = Self::read(&path) {
+                presets.push(preset);
+            }
+        }
+        presets.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(presets)
+    }
+
+    pub fn load(&self, name: &str) -> Result<Preset> {
+        let path = self.path_of(name)?;
+        if !path.exists() {
+            return Err(invalid(&format!("预设不存在: {}", name)));
+        }
+        Self::read(&path)
+    }
+
+    /// 校验后保存，同名预设被覆盖
+    pub fn save(&self, preset: &Preset) -> Result<()> {
+        preset.validate()?;
+        let preset = Preset {
+            schema_version: PRESET_SCHEMA_VERSION,
+            name: preset.name.trim().to_string(),
+            ..preset.clone()
+        };
+
+        fs::create_dir_all(&self.dir)?;
+        write_file_atomic(
+            &self.path_of(&preset.name)?,
+            preset.to_text(PresetFormat::Json)?.as_bytes(),
+        )?;
+        Ok(())
+    }
+
+    pub fn delete(&self, name: &str) -> Result<()> {
+        let path = self.path_of(name)?;
+        if !path.exists() {
+            return Err(invalid(&format!("预设不存在: {}", name)));
+        }
+        fs::remove_file(path)?;
+        Ok(())
+    }
+
+    /// 导入 JSON/TOML 文件；文件中没有名称时取文件名
+    pub fn import(&self, path: &Path) -> Result<Preset> {
+        let mut preset = Self::read(path)?;
+        if preset.name.trim().is_empty() {
+            preset.name = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or("imported")
+                .to_string();
+        }
+        self.save(&preset)?;
+        self.load(&preset.name)
+    }
+
+    /// 按目标扩展名导出为 JSON 或 TOML
+    pub fn export(&self, name: &str, path: &Path) -> Result<()> {
+        let format = PresetFormat::from_path(path)?;
+        let preset = self.load(name)?;
+        write_file_atomic(path, preset.to_text(format)?.as_bytes())?;
+        Ok(())
+    }
+
+    fn read(path: &Path) -> Result<Preset> {
+        let format = PresetFormat::from_path(path)?;
+        let text = fs::read_to_string(path)?;
+        Preset::parse(&text, format)
+    }
+}
@@ -1,67 +1,459 @@
+use crate::gerber::board::{Board, Layer};
+use crate::gerber::changelog::{ChangeLog, FileLog, StageLog};
+use crate::gerber::edit::diff_lines;
 use crate::gerber::error::{GhostPcbError, Result};
-use crate::gerber::pipeline::ObfuscationPipeline;
+use crate::gerber::lint::{lint_gerber, Diagnostic};
+use crate::gerber::package::{package_stem, path_beside, Package, PackageFormat, RepackOptions};
+use crate::gerber::pipeline::{ObfuscationPipeline, StageEdits};
+use crate::gerber::profile::CapabilityProfile;
+use crate::gerber::progress::{new_job_id, CancelToken, NoProgress, ProgressEvent, ProgressSink};
+use crate::gerber::report::{measure_displacement, LayerReport, PackageReport};
+use crate::gerber::seed::Seed;
 use crate::gerber::signature::{
-    apply_lceda_signature, disguise_as_easyeda, looks_like_easyeda_export,
+    apply_lceda_signature_encoded, disguise_as_easyeda, looks_like_easyeda_export,
 };
-use crate::gerber::types::{GerberFileType, ObfuscateOptions, ProcessRequest, ProcessResult};
-use chrono::{Duration, Local};
+use crate::gerber::types::{
+    ObfuscateOptions, PlannedFile, ProcessPlan, ProcessRequest, ProcessResult,
+};
+use crate::gerber::validation::{validate_board, validate_layer, ValidationIssue};
+use chrono::{Duration, Local, NaiveDateTime};
 use rand::Rng;
-use std::fs::{self, File};
-use std::io::{Read, Write};
+use rayon::prelude::*;
+use std::fs;
 use std::path::{Path, PathBuf};
-use tempfile::TempDir;
-use zip::write::SimpleFileOptions;
-use zip::{ZipArchive, ZipWriter};
+use std::sync::Mutex;
 
 pub struct GerberProcessor;
 
+/// 生成单个变体所需的共享输入
+struct VariantJob<'a> {
+    package: &'a Package,
+    board: &'a Board,
+    request: &'a ProcessRequest,
+    profile: &'a CapabilityProfile,
+    disguise_non_easyeda: bool,
+    /// 无法处理、原样保留的文件的警告
+    passthrough: &'a [String],
+    output_dir: &'a Path,
+    seed: Seed,
+    /// 本次处理作为“现在”的时间
+    now: NaiveDateTime,
+    job_id: &'a str,
+    progress: &'a dyn ProgressSink,
+    cancel: &'a CancelToken,
+    /// 已完整写出的文件，取消或回滚时删除
+    written: Mutex<Vec<PathBuf>>,
+}
+
+impl VariantJob<'_> {
+    /// 生成第 `i` 个变体，非试运行时写出输出包并返回其路径
+    ///
+    /// 文件命名: Gerber_PCB{序号}_YYYY-MM-DD，扩展名随输出的容器类型
+    fn run(&self, i: u32) -> Result<(PackageReport, Option<String>)> {
+        self.cancel.check()?;
+        let variant_seed = self.seed.variant(i);
+
+        // 随机日期 (过去 30 天内)
+        let days_ago: i64 = variant_seed.stream("文件名").gen_range(1..=30);
+        let random_date = (self.now - Duration::days(days_ago))
+            .format("%Y-%m-%d")
+            .to_string();
+
+        let format = self.request.output_format.unwrap_or(self.package.format);
+        let output_filename = format!("Gerber_PCB{}_{}{}", i, random_date, format.extension());
+        let output_path = self.output_dir.join(&output_filename);
+
+        let mut report = PackageReport {
+            output_file: output_path.to_string_lossy().to_string(),
+            profile: self.profile.name.clone(),
+            seed: self.seed.value(),
+            variant: i,
+            reference_time: self.now,
+            ..Default::default()
+        };
+        for name in self.package.unrecognized() {
+            report
+                .warnings
+                .push(format!("未识别的文件，原样保留: {}", name));
+        }
+        report.warnings.extend_from_slice(self.passthrough);
+        report
+            .warnings
+            .extend(repack_warnings(self.package, format, &self.request.repack));
+
+        let package_warnings = report.warnings.len();
+
+        let mut variant = self.board.clone();
+        let change_log = GerberProcessor::transform_board(
+            &mut variant,
+            &self.request.options,
+            self.disguise_non_easyeda,
+            self.profile,
+            variant_seed,
+            &mut report,
+            &mut |file| {
+                self.progress.emit(ProgressEvent::File {
+                    job_id: self.job_id.to_string(),
+                    variant: i,
+                    file: file.to_string(),
+                });
+                self.cancel.check()
+            },
+        )?;
+        for message in &report.warnings[package_warnings..] {
+            self.progress.emit(ProgressEvent::Warning {
+                job_id: self.job_id.to_string(),
+                message: format!("变体 {}: {}", i, message),
+            });
+        }
+
+        let output_file = if self.request.dry_run {
+            None
+        } else {
+            // 每个文件都先写入临时文件再重命名，完成后登记
+            self.package
+                .write_as(&output_path, format, &variant, &self.request.repack)?;
+            self.written(output_path.clone());
+            report.write_beside(&output_path, self.request.html_report)?;
+            self.written(path_beside(&output_path, "report.json"));
+            if self.request.html_report {
+                self.written(path_beside(&output_path, "report.html"));
+            }
+            self.written(change_log.write_beside(&output_path)?);
+            Some(output_path.to_string_lossy().to_string())
+        };
+
+        self.progress.emit(ProgressEvent::Variant {
+            job_id: self.job_id.to_string(),
+            variant: i,
+            output_file: output_file.clone(),
+        });
+        Ok((report, output_file))
+    }
+
+    fn written(&self, path: PathBuf) {
+        self.written.lock().unwrap().push(path);
+    }
+
+    /// 删除本任务写出的所有文件
+    ///
+    /// 输出目录由本任务创建时整个删除；目录原已存在则只删除本任务的文件，
+    /// 不影响之前生成的结果。
+    fn remove_outputs(&self, created_dir: bool) {
+        let written: Vec<PathBuf> = self.written.lock().unwrap().drain(..).collect();
+        if created_dir {
+            let _ = fs::remove_dir_all(self.output_dir);
+            return;
+        }
+        for path in written {
+            if path.is_dir() {
+                let _ = fs::remove_dir_all(path);
+            } else {
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+}
+
 impl GerberProcessor {
-    /// 处理 Gerber ZIP 文件
+    /// 处理 Gerber 包（目录、ZIP、tar 或 tar.gz）
     pub fn process(request: &ProcessRequest) -> Result<ProcessResult> {
+        Self::process_with(request, &NoProgress, &CancelToken::new())
+    }
+
+    /// 处理 Gerber 包，发出进度事件并响应取消
+    ///
+    /// 输入包只读取、解析一次，每个变体都从同一块板的副本生成，直接写入输出 ZIP。
+    /// 取消在文件之间生效，已写出的输出文件会被删除；其他错误按
+    /// `rollback_on_error` 决定是否回滚本次的全部输出。
+    pub fn process_with(
+        request: &ProcessRequest,
+        progress: &dyn ProgressSink,
+        cancel: &CancelToken,
+    ) -> Result<ProcessResult> {
+        let job_id = request.job_id.clone().unwrap_or_else(new_job_id);
+        let result = Self::run_job(request, &job_id, progress, cancel);
+        let (success, message) = match &result {
+            Ok(result) => (true, result.message.clone()),
+            Err(e) => (false, e.to_string()),
+        };
+        progress.emit(ProgressEvent::Finished {
+            job_id,
+            success,
+            message,
+        });
+        result
+    }
+
+    fn run_job(
+        request: &ProcessRequest,
+        job_id: &str,
+        progress: &dyn ProgressSink,
+        cancel: &CancelToken,
+    ) -> Result<ProcessResult> {
         let input_path = Path::new(&request.input_path);
 
         if !input_path.exists() {
             return Err(GhostPcbError::FileNotFound(request.input_path.clone()));
         }
 
-        // 确定输出目录
-        let output_dir = Self::get_output_dir(input_path, request.output_dir.as_deref())?;
-        fs::create_dir_all(&output_dir)?;
-
-        let mut output_files = Vec::new();
-        let mut rng = rand::thread_rng();
+        let profile = CapabilityProfile::resolve(request.profile.as_deref())?;
+        profile.validate()?;
+        request.options.validate(&profile)?;
+
+        // 预检：有语法错误的文件在严格模式下中止，否则原样保留
+        let package = Package::open_with(input_path, &request.archive_limits)?;
+        let mut board = package.board();
+        for layer in &board.layers {
+            request
+                .options
+                .validate_layer(&layer.name, layer.file_type, &profile)?;
+        }
+        let mut passthrough = Vec::new();
+
+        let diagnostics = Self::lint_layers(&board.layers);
+        let errors: Vec<&Diagnostic> = diagnostics.iter().filter(|d| d.is_error()).collect();
+        if !errors.is_empty() && request.strict {
+            let summary = errors
+                .iter()
+                .take(5)
+                .map(|d| format!("{}:{} [{}] {}", d.file, d.line, d.code, d.message))
+                .collect::<Vec<_>>()
+                .join("; ");
+            return Err(GhostPcbError::LintFailed(format!(
+                "{} 个错误: {}",
+                errors.len(),
+                summary
+            )));
+        }
+        for layer in &board.layers {
+            let mut file_errors = errors.iter().filter(|d| d.file == layer.name);
+            if let Some(first) = file_errors.next() {
+                passthrough.push(format!(
+                    "无法处理，原样保留: {} 有 {} 个语法错误，首个在第 {} 行 [{}] {}",
+                    layer.name,
+                    file_errors.count() + 1,
+                    first.line,
+                    first.code,
+                    first.message
+                ));
+            }
+        }
+        board
+            .layers
+            .retain(|layer| !errors.iter().any(|d| d.file == layer.name));
+        let layers = &board.layers;
+
+        let validation = Self::validate_layers(layers, &profile);
+
+        progress.emit(ProgressEvent::Started {
+            job_id: job_id.to_string(),
+            variants: request.count,
+            files: layers.len(),
+        });
+        let package_warnings = diagnostics
+            .iter()
+            .map(|d| format!("{}:{} [{}] {}", d.file, d.line, d.code, d.message))
+            .chain(
+                package
+                    .unrecognized()
+                    .map(|name| format!("未识别的文件，原样保留: {}", name)),
+            )
+            .chain(repack_warnings(
+                &package,
+                request.output_format.unwrap_or(package.format),
+                &request.repack,
+            ));
+        for message in package_warnings.chain(passthrough.iter().cloned()) {
+            progress.emit(ProgressEvent::Warning {
+                job_id: job_id.to_string(),
+                message,
+            });
+        }
 
-        // 生成指定数量的混淆文件
-        // 文件命名: Gerber_PCB{序号}_YYYY-MM-DD.zip
-        for i in 1..=request.count {
-            // 随机日期 (过去 30 天内)
-            let days_ago: i64 = rng.gen_range(1..=30);
-            let random_date = (Local::now() - Duration::days(days_ago))
-                .format("%Y-%m-%d")
-                .to_string();
+        // 确定输出目录，试运行时不创建
+        let now = request
+            .reference_time
+            .unwrap_or_else(|| Local::now().naive_local());
+        let output_dir = Self::get_output_dir(input_path, request.output_dir.as_deref(), now)?;
+        let created_dir = !request.dry_run && !output_dir.exists();
+        if !request.dry_run {
+            fs::create_dir_all(&output_dir)?;
+        }
 
-            let output_filename = format!("Gerber_PCB{}_{}.zip", i, random_date);
-            let output_path = output_dir.join(&output_filename);
+        let obfuscators = Self::stage_names(&request.options, layers, &profile);
+        let source_is_easyeda = layers
+            .iter()
+            .any(|l| !l.file_type.is_drill() && looks_like_easyeda_export(&l.content));
+
+        let seed = request.seed.map(Seed::new).unwrap_or_else(Seed::random);
+        let job = VariantJob {
+            package: &package,
+            board: &board,
+            request,
+            profile: &profile,
+            disguise_non_easyeda: !source_is_easyeda,
+            passthrough: &passthrough,
+            output_dir: &output_dir,
+            seed,
+            now,
+            job_id,
+            progress,
+            cancel,
+            written: Mutex::new(Vec::new()),
+        };
 
-            Self::process_single(input_path, &output_path, &request.options)?;
-            output_files.push(output_path.to_string_lossy().to_string());
-        }
+        // 各变体只依赖自己的种子，并行生成后按序号收集，结果与顺序执行相同
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(request.threads.unwrap_or(0))
+            .build()
+            .map_err(|e| GhostPcbError::ProcessError(e.to_string()))?;
+        let variants = pool
+            .install(|| {
+                (1..=request.count)
+                    .into_par_iter()
+                    .map(|i| job.run(i))
+                    .collect::<Result<Vec<_>>>()
+            })
+            .inspect_err(|e| {
+                if request.rollback_on_error || matches!(e, GhostPcbError::Cancelled) {
+                    job.remove_outputs(created_dir);
+                }
+            })?;
+        let (reports, output_files): (Vec<PackageReport>, Vec<Option<String>>) =
+            variants.into_iter().unzip();
+        let output_files: Vec<String> = output_files.into_iter().flatten().collect();
+
+        let (message, plan) = if request.dry_run {
+            let plan = ProcessPlan {
+                output_dir: output_dir.to_string_lossy().to_string(),
+                files: Self::plan_files(&package, &board, &request.options, &profile),
+            };
+            (
+                format!("试运行完成，将生成 {} 个混淆文件", request.count),
+                Some(plan),
+            )
+        } else {
+            (format!("成功生成 {} 个混淆文件", request.count), None)
+        };
 
         Ok(ProcessResult {
             success: true,
             output_files,
-            message: format!("成功生成 {} 个混淆文件", request.count),
+            message,
+            seed: seed.value(),
+            obfuscators,
+            validation,
+            diagnostics,
+            warnings: passthrough,
+            reports,
+            plan,
         })
     }
 
+    /// 列出包内每个文件及按规则展开后对其启用的阶段
+    fn plan_files(
+        package: &Package,
+        board: &Board,
+        options: &ObfuscateOptions,
+        profile: &CapabilityProfile,
+    ) -> Vec<PlannedFile> {
+        package
+            .entries
+            .iter()
+            .filter(|entry| !entry.is_dir())
+            .map(|entry| {
+                let file = entry.name.clone();
+                let file_type = entry.file_type();
+                let recognized = entry.is_recognized();
+                // 无法处理的文件原样保留，不经过任何阶段
+                let processed = board.layers.iter().any(|l| l.name == file);
+                let stages = if processed {
+                    ObfuscationPipeline::from_options(&options.for_layer(&file, file_type), profile)
+                        .obfuscator_names()
+                        .into_iter()
+                        .map(String::from)
+                        .collect()
+                } else {
+                    Vec::new()
+                };
+                PlannedFile {
+                    file,
+                    file_type,
+                    recognized,
+                    stages,
+                }
+            })
+            .collect()
+    }
+
+    /// 按规则展开到每个文件后，至少作用于一个文件的阶段
+    fn stage_names(
+        options: &ObfuscateOptions,
+        layers: &[Layer],
+        profile: &CapabilityProfile,
+    ) -> Vec<String> {
+        let resolved: Vec<ObfuscateOptions> = layers
+            .iter()
+            .map(|l| options.for_layer(&l.name, l.file_type))
+            .collect();
+        let any = |flag: fn(&ObfuscateOptions) -> bool| resolved.iter().any(flag);
+        let merged = ObfuscateOptions {
+            displacement: any(|o| o.displacement),
+            timestamp: any(|o| o.timestamp),
+            silkscreen: any(|o| o.silkscreen),
+            geometry: any(|o| o.geometry),
+            structure: any(|o| o.structure),
+            physical: any(|o| o.physical),
+            ..options.clone()
+        };
+
+        ObfuscationPipeline::from_options(&merged, profile)
+            .obfuscator_names()
+            .into_iter()
+            .map(String::from)
+            .collect()
+    }
+
+    /// 对输入包做语法检查，不生成任何输出
+    pub fn lint(input_path: &Path) -> Result<Vec<Diagnostic>> {
+        if !input_path.exists() {
+            return Err(GhostPcbError::FileNotFound(
+                input_path.to_string_lossy().to_string(),
+            ));
+        }
+        let board = Package::open(input_path)?.board();
+        Ok(Self::lint_layers(&board.layers))
+    }
+
+    fn lint_layers(layers: &[Layer]) -> Vec<Diagnostic> {
+        layers
+            .iter()
+            .filter(|l| !l.file_type.is_drill())
+            .flat_map(|l| lint_gerber(&l.name, &l.content))
+            .collect()
+    }
+
+    /// 按工艺配置校验输入包中的各层文件
+    fn validate_layers(layers: &[Layer], profile: &CapabilityProfile) -> Vec<ValidationIssue> {
+        layers
+            .iter()
+            .flat_map(|l| validate_layer(&l.name, &l.content, l.file_type, profile))
+            .collect()
+    }
+
     /// 获取输出目录
     /// 无论是否有自定义目录，都会创建 GhostPCB_日期_原文件名 文件夹
-    fn get_output_dir(input_path: &Path, custom_dir: Option<&str>) -> Result<PathBuf> {
-        let original_name = input_path
-            .file_stem()
-            .and_then(|s| s.to_str())
-            .unwrap_or("gerber");
-        let date = Local::now().format("%Y%m%d").to_string();
+    fn get_output_dir(
+        input_path: &Path,
+        custom_dir: Option<&str>,
+        now: NaiveDateTime,
+    ) -> Result<PathBuf> {
+        let original_name = match package_stem(input_path) {
+            "" => "gerber",
+            stem => stem,
+        };
+        let date = now.format("%Y%m%d").to_string();
         let dir_name = format!("GhostPCB_{}_{}", date, original_name);
 
         let base_dir = if let Some(dir) = custom_dir {
@@ -74,157 +466,153 @@ impl GerberProcessor {
         Ok(base_dir.join(dir_name))
     }
 
-    /// 处理单个文件
-    fn process_single(
-        input_path: &Path,
-        output_path: &Path,
+    /// 在内存中生成一个输出包的全部文件内容
+    ///
+    /// 所有可识别的文件先组成一块板，按各自匹配的规则整体变换，再逐个签名与校验，
+    /// 最后校验孔环、板边距等跨层约束。时间类修改以 `report.reference_time` 为当前时间。
+    /// 每个文件经过的所有修改都记入返回的修改记录，每处理完一个文件调用一次 `on_file`。
+    fn transform_board(
+        board: &mut Board,
         options: &ObfuscateOptions,
-    ) -> Result<()> {
-        let temp_dir = TempDir::new()?;
-
-        // 解压 ZIP
-        Self::extract_zip(input_path, temp_dir.path())?;
-
-        // 创建处理管道
-        let pipeline = ObfuscationPipeline::from_options(options);
-        let source_is_easyeda = Self::detect_easyeda_source(temp_dir.path())?;
-
-        // 处理所有文件
-        Self::process_directory(temp_dir.path(), &pipeline, !source_is_easyeda)?;
-
-        // 重新打包
-        Self::create_zip(temp_dir.path(), output_path)?;
-
-        Ok(())
-    }
-
-    /// 解压 ZIP 文件
-    fn extract_zip(zip_path: &Path, dest_dir: &Path) -> Result<()> {
-        let file = File::open(zip_path)?;
-        let mut archive = ZipArchive::new(file)?;
-
-        for i in 0..archive.len() {
-            let mut file = archive.by_index(i)?;
-            let outpath = dest_dir.join(file.name());
-
-            if file.name().ends_with('/') {
-                fs::create_dir_all(&outpath)?;
-            } else {
-                if let Some(p) = outpath.parent() {
-                    fs::create_dir_all(p)?;
-                }
-                let mut outfile = File::create(&outpath)?;
-                std::io::copy(&mut file, &mut outfile)?;
-            }
-        }
-
-        Ok(())
-    }
-
-    /// 处理目录中的所有 Gerber 文件
-    fn process_directory(
-        dir: &Path,
-        pipeline: &ObfuscationPipeline,
         disguise_non_easyeda: bool,
-    ) -> Result<()> {
-        for entry in walkdir::WalkDir::new(dir) {
-            let entry = entry.map_err(|e| GhostPcbError::IoError(e.into()))?;
-            let path = entry.path();
-
-            if path.is_file() {
-                let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("");
-
-                let file_type = GerberFileType::from_extension(ext);
-
-                // 只处理已知的 Gerber 文件类型
-                if !matches!(file_type, GerberFileType::Unknown) {
-                    Self::process_file(path, file_type, pipeline, disguise_non_easyeda)?;
-                }
+        profile: &CapabilityProfile,
+        seed: Seed,
+        report: &mut PackageReport,
+        on_file: &mut dyn FnMut(&str) -> Result<()>,
+    ) -> Result<ChangeLog> {
+        let originals: Vec<String> = board.layers.iter().map(|l| l.content.clone()).collect();
+        let layer_options: Vec<ObfuscateOptions> = board
+            .layers
+            .iter()
+            .map(|l| options.for_layer(&l.name, l.file_type))
+            .collect();
+        let applied = ObfuscationPipeline::process_board_by_layer_logged(
+            board,
+            &layer_options,
+            profile,
+            seed,
+            report.reference_time,
+        )?;
+
+        let mut change_log = ChangeLog {
+            output_file: report.output_file.clone(),
+            files: Vec::new(),
+        };
+        for ((layer, original), stages) in board.layers.iter_mut().zip(&originals).zip(applied) {
+            let (content, log) = Self::process_file(
+                layer,
+                original,
+                stages,
+                disguise_non_easyeda,
+                profile,
+                seed,
+                report,
+            )?;
+            layer.content = content;
+            if !log.stages.is_empty() {
+                change_log.files.push(log);
             }
+            on_file(&layer.name)?;
         }
+        report.validation.extend(validate_board(board, profile));
 
-        Ok(())
+        Ok(change_log)
     }
 
-    /// 处理单个文件
+    /// 对板级处理后的单个文件做签名、统计和校验，返回最终内容与修改记录
     fn process_file(
-        path: &Path,
-        file_type: GerberFileType,
-        pipeline: &ObfuscationPipeline,
+        layer: &Layer,
+        original: &str,
+        applied: Vec<StageEdits>,
         disguise_non_easyeda: bool,
-    ) -> Result<()> {
-        let mut content = String::new();
-        File::open(path)?.read_to_string(&mut content)?;
-
-        let mut processed = pipeline.process(&content, file_type)?;
+        profile: &CapabilityProfile,
+        seed: Seed,
+        report: &mut PackageReport,
+    ) -> Result<(String, FileLog)> {
+        let name = layer.name.as_str();
+        let file_type = layer.file_type;
+        let mut processed = layer.content.clone();
+        let mut transforms: Vec<String> = applied.iter().map(|(s, _)| s.to_string()).collect();
+        let mut log = FileLog {
+            file: name.to_string(),
+            encoding: layer.encoding,
+            stages: applied
+                .into_iter()
+                .map(|(stage, edits)| StageLog {
+                    stage: stage.to_string(),
+                    edits,
+                })
+                .collect(),
+        };
+        let mut record = |stage: &str, before: &str, after: &str| {
+            let edits = diff_lines(before, after);
+            if !edits.is_empty() {
+                log.stages.push(StageLog {
+                    stage: stage.to_string(),
+                    edits,
+                });
+            }
+        };
 
         if !file_type.is_drill() {
             if disguise_non_easyeda {
-                let layer_name = path
+                let layer_name = Path::new(name)
                     .file_name()
                     .and_then(|n| n.to_str())
+                    .filter(|n| layer.encoding.can_encode(n))
                     .unwrap_or("UnknownLayer");
-                processed = disguise_as_easyeda(&processed, layer_name);
+                let disguised = disguise_as_easyeda(&processed, layer_name, report.reference_time);
+                record("EasyEDA 文件头伪装", &processed, &disguised);
+                processed = disguised;
+                transforms.push("EasyEDA 文件头伪装".to_string());
             }
-            processed = apply_lceda_signature(&processed, false);
+            let mut rng = seed.stream(&format!("立创签名/{}", name));
+            let signed =
+                apply_lceda_signature_encoded(&processed, layer.encoding, false, &mut rng)?;
+            record("立创签名", &processed, &signed);
+            processed = signed;
+            transforms.push("立创签名".to_string());
         }
 
-        let mut file = File::create(path)?;
-        file.write_all(processed.as_bytes())?;
-
-        Ok(())
-    }
-
-    fn detect_easyeda_source(dir: &Path) -> Result<bool> {
-        for entry in walkdir::WalkDir::new(dir) {
-            let entry = entry.map_err(|e| GhostPcbError::IoError(e.into()))?;
-            let path = entry.path();
-
-            if !path.is_file() {
-                continue;
-            }
-
-            let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("");
-            let file_type = GerberFileType::from_extension(ext);
-
-            if matches!(file_type, GerberFileType::Unknown | GerberFileType::Drill) {
-                continue;
-            }
-
-            let mut text = String::new();
-            File::open(path)?.read_to_string(&mut text)?;
-            if looks_like_easyeda_export(&text) {
-                return Ok(true);
-            }
-        }
-
-        Ok(false)
+        let (coordinates_total, coordinates_changed, max_displacement_mm) =
+            match measure_displacement(original, &processed, file_type) {
+                Some(d) => (d.total, d.changed, d.max_mm),
+                None => {
+                    report
+                        .warnings
+                        .push(format!("{}: 处理前后坐标数量不一致，无法统计位移", name));
+                    (0, 0, 0.0)
+                }
+            };
+
+        report.layers.push(LayerReport {
+            file: name.to_string(),
+            file_type,
+            transforms,
+            coordinates_total,
+            coordinates_changed,
+            max_displacement_mm,
+        });
+        report
+            .validation
+            .extend(validate_layer(name, &processed, file_type, profile));
+
+        Ok((processed, log))
     }
+}
 
-    /// 创建 ZIP 文件
-    fn create_zip(source_dir: &Path, zip_path: &Path) -> Result<()> {
-        let file = File::create(zip_path)?;
-        let mut zip = ZipWriter::new(file);
-        let options =
-            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
-
-        for entry in walkdir::WalkDir::new(source_dir) {
-            let entry = entry.map_err(|e| GhostPcbError::IoError(e.into()))?;
-            let path = entry.path();
-            let name = path
-                .strip_prefix(source_dir)
-                .map_err(|_| GhostPcbError::ProcessError("路径处理错误".to_string()))?;
-
-            if path.is_file() {
-                zip.start_file(name.to_string_lossy(), options)?;
-                let mut f = File::open(path)?;
-                std::io::copy(&mut f, &mut zip)?;
-            } else if !name.as_os_str().is_empty() {
-                zip.add_directory(name.to_string_lossy(), options)?;
-            }
-        }
-
-        zip.finish()?;
-        Ok(())
-    }
+/// 输出包无法保留原有元数据的条目
+fn repack_warnings<'a>(
+    package: &'a Package,
+    format: PackageFormat,
+    repack: &'a RepackOptions,
+) -> impl Iterator<Item = String> + 'a {
+    package
+        .recompressed(format, repack)
+        .map(|(name, method)| format!("无法按 {} 方式写回，改用 Deflate 压缩: {}", method, name))
+        .chain(
+            package
+                .dropped_comments(repack)
+                .map(|name| format!("条目注释无法写回，已丢弃: {}", name)),
+        )
 }
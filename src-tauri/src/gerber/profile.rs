@@ -0,0 +1,246 @@
+use crate::gerber::error::{GhostPcbError, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// 默认使用的内置配置名称
+pub const DEFAULT_PROFILE: &str = "standard";
+
+/// 板厂工艺能力配置
+///
+/// 所有尺寸均为毫米。校验器与各混淆阶段都从这里读取限制，
+/// 而不是在代码中写死常量。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CapabilityProfile {
+    pub name: String,
+    pub description: String,
+    /// 最小线宽
+    pub min_trace_mm: f64,
+    /// 最小线距
+    pub min_space_mm: f64,
+    /// 最小钻孔直径
+    pub min_drill_mm: f64,
+    /// 最小焊环宽度
+    pub min_annular_ring_mm: f64,
+    /// 最小丝印线宽
+    pub min_silk_width_mm: f64,
+    /// 导体到板边的最小距离
+    pub edge_clearance_mm: f64,
+    /// 最大板子尺寸
+    pub max_board_width_mm: f64,
+    pub max_board_height_mm: f64,
+    /// 各类坐标允许的最大位移
+    pub tolerance: PositionTolerance,
+}
+
+/// 坐标位移容差（毫米）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PositionTolerance {
+    /// 铜层、阻焊、钢网、丝印坐标的抖动上限
+    pub feature_jitter_mm: f64,
+    /// 板框坐标的抖动上限
+    pub outline_jitter_mm: f64,
+    /// 钻孔坐标的抖动上限
+    pub drill_jitter_mm: f64,
+    /// 板框整体偏移范围
+    pub outline_offset_min_mm: f64,
+    pub outline_offset_max_mm: f64,
+}
+
+impl Default for PositionTolerance {
+    fn default() -> Self {
+        Self {
+            feature_jitter_mm: 0.003,
+            outline_jitter_mm: 0.005,
+            drill_jitter_mm: 0.02,
+            outline_offset_min_mm: 0.005,
+            outline_offset_max_mm: 0.01,
+        }
+    }
+}
+
+impl Default for CapabilityProfile {
+    fn default() -> Self {
+        Self::standard()
+    }
+}
+
+impl CapabilityProfile {
+    /// 常规双面/四层板工艺
+    pub fn standard() -> Self {
+        Self {
+            name: "standard".to_string(),
+            description: "常规工艺 (6/6mil, 0.3mm 孔)".to_string(),
+            min_trace_mm: 0.127,
+            min_space_mm: 0.127,
+            min_drill_mm: 0.3,
+            min_annular_ring_mm: 0.13,
+            min_silk_width_mm: 0.153,
+            edge_clearance_mm: 0.3,
+            max_board_width_mm: 100.0,
+            max_board_height_mm: 100.0,
+            tolerance: PositionTolerance::default(),
+        }
+    }
+
+    /// 高精度工艺，允许更细的线路，位移也更保守
+    pub fn advanced() -> Self {
+        Self {
+            name: "advanced".to_string(),
+            description: "高精度工艺 (3.5/3.5mil, 0.15mm 孔)".to_string(),
+            min_trace_mm: 0.09,
+            min_space_mm: 0.09,
+            min_drill_mm: 0.15,
+            min_annular_ring_mm: 0.075,
+            min_silk_width_mm: 0.1,
+            edge_clearance_mm: 0.2,
+            max_board_width_mm: 500.0,
+            max_board_height_mm: 500.0,
+            tolerance: PositionTolerance {
+                feature_jitter_mm: 0.002,
+                outline_jitter_mm: 0.004,
+                drill_jitter_mm: 0.01,
+                outline_offset_min_mm: 0.004,
+                outline_offset_max_mm: 0.008,
+            },
+        }
+    }
+
+    /// 所有内置配置
+    pub fn builtin_profiles() -> Vec<Self> {
+        vec![Self::standard(), Self::advanced()]
+    }
+
+    pub fn builtin(name: &str) -> Option<Self> {
+        Self::builtin_profiles()
+            .into_iter()
+            .find(|p| p.name.eq_ignore_ascii_case(name))
+    }
+
+    /// 按名称或路径解析配置
+    ///
+    /// `None` 返回默认配置；内置名称优先，其次按 TOML/JSON 文件加载。
+    pub fn resolve(spec: Option<&str>) -> Result<Self> {
+        let spec = match spec.map(str::trim).filter(|s| !s.is_empty()) {
+            Some(s) => s,
+            None => return Ok(Self::default()),
+        };
+
+        if let Some(profile) = Self::builtin(spec) {
+            return Ok(profile);
+        }
+
+        let path = Path::new(spec);
+        if !path.exists() {
+            return Err(GhostPcbError::InvalidProfile(format!(
+                "未知的配置: {}",
+                spec
+            )));
+        }
+        Self::load(path)
+    }
+
+    /// 从 TOML 或 JSON 文件加载配置
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let ext = path
+            .extension()
+            .and_then(|s| s.to_str())
+            .unwrap_or("")
+            .to_lowercase();
+
+        let mut profile: Self = match ext.as_str() {
+            "toml" => {
+                toml::from_str(&text).map_err(|e| GhostPcbError::InvalidProfile(e.to_string()))?
+            }
+            "json" => serde_json::from_str(&text)
+                .map_err(|e| GhostPcbError::InvalidProfile(e.to_string()))?,
+            _ => {
+                return Err(GhostPcbError::InvalidProfile(format!(
+                    "不支持的配置格式: {}",
+                    path.display()
+                )))
+            }
+        };
+
+        if profile.name.is_empty() || profile.name == DEFAULT_PROFILE {
+            profile.name = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or("custom")
+                .to_string();
+        }
+
+        profile.validate()?;
+        Ok(profile)
+    }
+
+    /// 检查配置自身是否自洽
+    pub fn validate(&self) -> Result<()> {
+        let positive = [
+            ("min_trace_mm", self.min_trace_mm),
+            ("min_space_mm", self.min_space_mm),
+            ("min_drill_mm", self.min_drill_mm),
+            ("min_annular_ring_mm", self.min_annular_ring_mm),
+            ("min_silk_width_mm", self.min_silk_width_mm),
+            ("edge_clearance_mm", self.edge_clearance_mm),
+            ("max_board_width_mm", self.max_board_width_mm),
+            ("max_board_height_mm", self.max_board_height_mm),
+        ];
+        for (field, value) in positive {
+            if !(value.is_finite() && value > 0.0) {
+                return Err(GhostPcbError::InvalidProfile(format!(
+                    "{} 必须为正数: {}",
+                    field, value
+                )));
+            }
+        }
+
+        let tol = &self.tolerance;
+        let non_negative = [
+            ("feature_jitter_mm", tol.feature_jitter_mm),
+            ("outline_jitter_mm", tol.outline_jitter_mm),
+            ("drill_jitter_mm", tol.drill_jitter_mm),
+            ("outline_offset_min_mm", tol.outline_offset_min_mm),
+            ("outline_offset_max_mm", tol.outline_offset_max_mm),
+        ];
+        for (field, value) in non_negative {
+            if !(value.is_finite() && value >= 0.0) {
+                return Err(GhostPcbError::InvalidProfile(format!(
+                    "{} 不能为负数: {}",
+                    field, value
+                )));
+            }
+        }
+
+        if tol.outline_offset_min_mm > tol.outline_offset_max_mm {
+            return Err(GhostPcbError::InvalidProfile(
+                "outline_offset_min_mm 不能大于 outline_offset_max_mm".to_string(),
+            ));
+        }
+
+        // 相邻两个图形相向移动时，间距最多缩小两倍抖动量
+        if tol.feature_jitter_mm * 2.0 >= self.min_space_mm {
+            return Err(GhostPcbError::InvalidProfile(
+                "feature_jitter_mm 过大，可能导致线距低于 min_space_mm".to_string(),
+            ));
+        }
+
+        // 孔相对焊盘的偏移会直接吃掉焊环
+        if tol.drill_jitter_mm >= self.min_annular_ring_mm {
+            return Err(GhostPcbError::InvalidProfile(
+                "drill_jitter_mm 过大，可能导致焊环低于 min_annular_ring_mm".to_string(),
+            ));
+        }
+
+        if tol.outline_jitter_mm + tol.outline_offset_max_mm >= self.edge_clearance_mm {
+            return Err(GhostPcbError::InvalidProfile(
+                "板框位移过大，可能导致板边距低于 edge_clearance_mm".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,119 @@
+use crate::gerber::error::{GhostPcbError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// 处理过程中发出的进度事件
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ProgressEvent {
+    Started {
+        job_id: String,
+        variants: u32,
+        files: usize,
+    },
+    /// 某个变体中的一个文件处理完毕
+    File {
+        job_id: String,
+        variant: u32,
+        file: String,
+    },
+    /// 一个变体生成完毕，试运行时没有输出文件
+    Variant {
+        job_id: String,
+        variant: u32,
+        output_file: Option<String>,
+    },
+    Warning {
+        job_id: String,
+        message: String,
+    },
+    Finished {
+        job_id: String,
+        success: bool,
+        message: String,
+    },
+}
+
+/// 生成随机的任务 ID
+pub fn new_job_id() -> String {
+    format!("{:016x}", rand::random::<u64>())
+}
+
+/// 进度事件的接收方，可能在多个工作线程上同时调用
+pub trait ProgressSink: Send + Sync {
+    fn emit(&self, event: ProgressEvent);
+}
+
+impl<F: Fn(ProgressEvent) + Send + Sync> ProgressSink for F {
+    fn emit(&self, event: ProgressEvent) {
+        self(event)
+    }
+}
+
+/// 丢弃所有事件
+pub struct NoProgress;
+
+impl ProgressSink for NoProgress {
+    fn emit(&self, _event: ProgressEvent) {}
+}
+
+/// 取消标记，在文件之间检查
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// 已取消时返回 [`GhostPcbError::Cancelled`]
+    pub fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            Err(GhostPcbError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// 正在运行的任务，按任务 ID 查找取消标记
+#[derive(Debug, Default)]
+pub struct JobRegistry {
+    jobs: Mutex<HashMap<String, CancelToken>>,
+}
+
+impl JobRegistry {
+    pub fn register(&self, job_id: &str) -> CancelToken {
+        let token = CancelToken::new();
+        self.jobs
+            .lock()
+            .unwrap()
+            .insert(job_id.to_string(), token.clone());
+        token
+    }
+
+    /// 取消任务，任务不存在或已结束时返回 false
+    pub fn cancel(&self, job_id: &str) -> bool {
+        match self.jobs.lock().unwrap().get(job_id) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn finish(&self, job_id: &str) {
+        self.jobs.lock().unwrap().remove(job_id);
+    }
+}
@@ -0,0 +1,217 @@
+use crate::gerber::error::Result;
+use crate::gerber::output::write_file_atomic;
+use crate::gerber::package::path_beside;
+use crate::gerber::types::GerberFileType;
+use crate::gerber::units::{CoordinateFormat, Length};
+use crate::gerber::validation::{Severity, ValidationIssue};
+use chrono::NaiveDateTime;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// 单个输出包的校验报告
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PackageReport {
+    pub output_file: String,
+    pub profile: String,
+    /// 本次处理的随机种子与包的序号（从 1 开始），两者相同即可重新生成该包
+    #[serde(default)]
+    pub seed: u64,
+    #[serde(default)]
+    pub variant: u32,
+    /// 本次处理作为“现在”的时间，文件名、文件头与时间戳都以它为准；
+    /// 与种子一起传回请求即可逐字节重现该包
+    #[serde(default)]
+    pub reference_time: NaiveDateTime,
+    pub layers: Vec<LayerReport>,
+    /// 输出文件相对工艺配置的校验结果
+    pub validation: Vec<ValidationIssue>,
+    pub warnings: Vec<String>,
+}
+
+/// 单个层文件的变更统计
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LayerReport {
+    pub file: String,
+    pub file_type: GerberFileType,
+    /// 实际改动了该文件的处理阶段
+    pub transforms: Vec<String>,
+    pub coordinates_total: usize,
+    pub coordinates_changed: usize,
+    pub max_displacement_mm: f64,
+}
+
+impl PackageReport {
+    pub fn has_errors(&self) -> bool {
+        self.validation
+            .iter()
+            .any(|issue| issue.severity == Severity::Error)
+    }
+
+    /// 在输出包旁写入 `<包名>.report.json`，可选同时写入 HTML
+    pub fn write_beside(&self, zip_path: &Path, html: bool) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| crate::gerber::error::GhostPcbError::ProcessError(e.to_string()))?;
+        write_file_atomic(&path_beside(zip_path, "report.json"), json.as_bytes())?;
+
+        if html {
+            write_file_atomic(
+                &path_beside(zip_path, "report.html"),
+                self.to_html().as_bytes(),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    pub fn to_html(&self) -> String {
+        let mut html = String::new();
+        html.push_str("<!DOCTYPE html>\n<html lang=\"zh-CN\">\n<head>\n<meta charset=\"utf-8\">\n");
+        html.push_str(&format!(
+            "<title>GhostPCB 报告 - {}</title>\n",
+            escape_html(&self.output_file)
+        ));
+        html.push_str(
+            "<style>body{font-family:sans-serif;margin:24px}table{border-collapse:collapse}\
+             td,th{border:1px solid #ccc;padding:4px 8px;text-align:left}\
+             .error{color:#c00}.warning{color:#b60}</style>\n</head>\n<body>\n",
+        );
+        html.push_str(&format!(
+            "<h1>{}</h1>\n<p>工艺配置: {}</p>\n<p>随机种子: {} (第 {} 个包)</p>\n",
+            escape_html(&self.output_file),
+            escape_html(&self.profile),
+            self.seed,
+            self.variant
+        ));
+
+        html.push_str("<h2>各层变更</h2>\n<table>\n<tr><th>文件</th><th>类型</th><th>处理阶段</th><th>改动坐标</th><th>最大位移 (mm)</th></tr>\n");
+        for layer in &self.layers {
+            html.push_str(&format!(
+                "<tr><td>{}</td><td>{:?}</td><td>{}</td><td>{} / {}</td><td>{:.4}</td></tr>\n",
+                escape_html(&layer.file),
+                layer.file_type,
+                escape_html(&layer.transforms.join(", ")),
+                layer.coordinates_changed,
+                layer.coordinates_total,
+                layer.max_displacement_mm
+            ));
+        }
+        html.push_str("</table>\n");
+
+        html.push_str("<h2>校验结果</h2>\n");
+        if self.validation.is_empty() {
+            html.push_str("<p>无</p>\n");
+        } else {
+            html.push_str("<ul>\n");
+            for issue in &self.validation {
+                let class = match issue.severity {
+                    Severity::Error => "error",
+                    Severity::Warning => "warning",
+                };
+                html.push_str(&format!(
+                    "<li class=\"{}\">{} [{}] {}</li>\n",
+                    class,
+                    escape_html(&issue.file),
+                    escape_html(&issue.code),
+                    escape_html(&issue.message)
+                ));
+            }
+            html.push_str("</ul>\n");
+        }
+
+        html.push_str("<h2>警告</h2>\n");
+        if self.warnings.is_empty() {
+            html.push_str("<p>无</p>\n");
+        } else {
+            html.push_str("<ul>\n");
+            for warning in &self.warnings {
+                html.push_str(&format!("<li>{}</li>\n", escape_html(warning)));
+            }
+            html.push_str("</ul>\n");
+        }
+
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// 坐标对比结果
+pub struct Displacement {
+    pub total: usize,
+    pub changed: usize,
+    pub max_mm: f64,
+}
+
+/// 按顺序对比处理前后的坐标，统计改动数量和最大位移
+///
+/// 只统计带坐标的操作行；处理阶段不会增删坐标行，
+/// 数量不一致时返回 `None`。
+pub fn measure_displacement(
+    before: &str,
+    after: &str,
+    file_type: GerberFileType,
+) -> Option<Displacement> {
+    let source = resolve_points(before, file_type);
+    let result = resolve_points(after, file_type);
+
+    if source.len() != result.len() {
+        return None;
+    }
+
+    let mut changed = 0;
+    let mut max_mm: f64 = 0.0;
+    for (a, b) in source.iter().zip(result.iter()) {
+        if a != b {
+            changed += 1;
+            max_mm = max_mm.max((b.0 - a.0).hypot(b.1 - a.1).as_mm());
+        }
+    }
+
+    Some(Displacement {
+        total: source.len(),
+        changed,
+        max_mm,
+    })
+}
+
+/// 解析所有操作点的绝对坐标（缺省轴沿用上一个值）
+fn resolve_points(content: &str, file_type: GerberFileType) -> Vec<(Length, Length)> {
+    let coord_re = Regex::new(r"([XY])([+-]?[\d.]+)").unwrap();
+    let (format, comment_prefixes): (CoordinateFormat, &[&str]) = if file_type.is_drill() {
+        (CoordinateFormat::excellon(content), &[";", "%", "M", "T"])
+    } else {
+        (CoordinateFormat::gerber(content), &["%", "G04"])
+    };
+
+    let mut points = Vec::new();
+    let mut current = (Length::ZERO, Length::ZERO);
+    for line in content.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || comment_prefixes.iter().any(|p| trimmed.starts_with(p)) {
+            continue;
+        }
+
+        let mut found = false;
+        for caps in coord_re.captures_iter(trimmed) {
+            let value = format.parse(&caps[2]).unwrap_or(Length::ZERO);
+            if &caps[1] == "X" {
+                current.0 = value;
+            } else {
+                current.1 = value;
+            }
+            found = true;
+        }
+        if found {
+            points.push(current);
+        }
+    }
+
+    points
+}
@@ -0,0 +1,122 @@
+use crate::gerber::error::{GhostPcbError, Result};
+use crate::gerber::params::StageParamsPatch;
+use crate::gerber::types::{GerberFileType, ObfuscateOptions};
+use glob::{MatchOptions, Pattern};
+use serde::{Deserialize, Serialize};
+
+/// 规则中用于匹配文件的层类别
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LayerClass {
+    /// 所有铜层
+    Copper,
+    /// 顶层与底层铜
+    OuterCopper,
+    InnerCopper,
+    Silkscreen,
+    SolderMask,
+    Paste,
+    Outline,
+    Drill,
+    /// 文档层与钻孔图 (.GDL/.GDD)
+    Document,
+}
+
+impl LayerClass {
+    pub fn contains(self, file_type: GerberFileType) -> bool {
+        use GerberFileType::*;
+        match self {
+            Self::Copper => file_type.is_copper_layer(),
+            Self::OuterCopper => matches!(file_type, TopLayer | BottomLayer),
+            Self::InnerCopper => matches!(file_type, InnerLayer),
+            Self::Silkscreen => file_type.is_silkscreen(),
+            Self::SolderMask => matches!(file_type, TopSolderMask | BottomSolderMask),
+            Self::Paste => matches!(file_type, TopPaste | BottomPaste),
+            Self::Outline => file_type.is_outline(),
+            Self::Drill => file_type.is_drill(),
+            Self::Document => matches!(file_type, Document),
+        }
+    }
+}
+
+/// 各阶段的开关，`None` 表示沿用之前的设置
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StageToggles {
+    pub displacement: Option<bool>,
+    pub timestamp: Option<bool>,
+    pub silkscreen: Option<bool>,
+    pub geometry: Option<bool>,
+    pub structure: Option<bool>,
+    pub physical: Option<bool>,
+}
+
+/// 按层生效的处理规则
+///
+/// `layers` 与 `files` 为空时不作限制，都给出时须同时满足。
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LayerRule {
+    /// 命中任一类别即可
+    pub layers: Vec<LayerClass>,
+    /// 文件名通配符，如 `*.GKO`，与包内路径或文件名比较，不区分大小写
+    pub files: Vec<String>,
+    pub stages: StageToggles,
+    /// 覆盖命中文件的阶段参数，未给出的字段沿用之前的设置
+    pub params: Option<StageParamsPatch>,
+}
+
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: false,
+    require_literal_separator: false,
+    require_literal_leading_dot: false,
+};
+
+impl LayerRule {
+    pub fn matches(&self, name: &str, file_type: GerberFileType) -> bool {
+        if !self.layers.is_empty() && !self.layers.iter().any(|c| c.contains(file_type)) {
+            return false;
+        }
+        if self.files.is_empty() {
+            return true;
+        }
+
+        let file_name = name.rsplit('/').next().unwrap_or(name);
+        self.files.iter().any(|pattern| {
+            Pattern::new(pattern)
+                .map(|p| {
+                    p.matches_with(name, MATCH_OPTIONS) || p.matches_with(file_name, MATCH_OPTIONS)
+                })
+                .unwrap_or(false)
+        })
+    }
+
+    /// 把规则叠加到选项上
+    pub fn apply(&self, options: &mut ObfuscateOptions) {
+        let s = &self.stages;
+        let set = |flag: &mut bool, value: Option<bool>| {
+            if let Some(v) = value {
+                *flag = v;
+            }
+        };
+        set(&mut options.displacement, s.displacement);
+        set(&mut options.timestamp, s.timestamp);
+        set(&mut options.silkscreen, s.silkscreen);
+        set(&mut options.geometry, s.geometry);
+        set(&mut options.structure, s.structure);
+        set(&mut options.physical, s.physical);
+
+        if let Some(params) = &self.params {
+            params.apply(&mut options.params);
+        }
+    }
+
+    /// 检查通配符语法
+    pub fn validate_patterns(&self) -> Result<()> {
+        for pattern in &self.files {
+            Pattern::new(pattern).map_err(|e| {
+                GhostPcbError::InvalidOptions(format!("文件通配符无效 {}: {}", pattern, e))
+            })?;
+        }
+        Ok(())
+    }
+}
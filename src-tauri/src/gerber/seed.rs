@@ -0,0 +1,52 @@
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+/// 随机生成的种子不超过 JS 可精确表示的最大整数，便于前端显示与回填
+const MAX_SAFE_INTEGER: u64 = (1 << 53) - 1;
+
+/// 一次处理的随机种子
+///
+/// 每个输出包、包内每个文件的每个阶段都从种子派生独立的随机数流，
+/// 启用或关闭某个阶段、增减文件都不会改变其余部分的结果。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Seed(u64);
+
+impl Seed {
+    pub fn new(value: u64) -> Self {
+        Self(value)
+    }
+
+    pub fn random() -> Self {
+        Self(rand::thread_rng().gen_range(0..=MAX_SAFE_INTEGER))
+    }
+
+    pub fn value(self) -> u64 {
+        self.0
+    }
+
+    /// 第 `index` 个输出包的种子
+    pub fn variant(self, index: u32) -> Self {
+        Self(splitmix64(self.0 ^ splitmix64(index as u64)))
+    }
+
+    /// 按名称派生的随机数流，同一种子与名称总是得到相同的序列
+    pub fn stream(self, label: &str) -> ChaCha8Rng {
+        let mut rng = ChaCha8Rng::seed_from_u64(self.0);
+        rng.set_stream(fnv1a(label));
+        rng
+    }
+}
+
+fn splitmix64(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^ (x >> 31)
+}
+
+/// 与平台和编译器版本无关的字符串散列
+fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xCBF2_9CE4_8422_2325, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x0000_0100_0000_01B3)
+    })
+}
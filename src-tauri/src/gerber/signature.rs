@@ -1,4 +1,8 @@
-use rand::Rng;
+use crate::gerber::encoding::TextEncoding;
+use crate::gerber::error::Result;
+use crate::gerber::layout::{insert_line, split_eol, split_lines, TextLayout};
+use chrono::NaiveDateTime;
+use rand::{Rng, RngCore};
 use regex::Regex;
 use std::collections::{HashMap, HashSet};
 
@@ -12,89 +16,76 @@ struct AddLine {
     line: String,
 }
 
-#[derive(Debug, Clone, Copy)]
-struct TextLayout {
-    has_trailing_newline: bool,
-    eol: &'static str,
-}
-
 pub fn looks_like_easyeda_export(content: &str) -> bool {
     content.contains("EasyEDA Pro")
 }
 
-pub fn disguise_as_easyeda(content: &str, layer_name: &str) -> String {
+/// 加上 EasyEDA Pro 的文件头，导出时间取 `generated`
+pub fn disguise_as_easyeda(content: &str, layer_name: &str, generated: NaiveDateTime) -> String {
     if looks_like_easyeda_export(content) {
         return content.to_string();
     }
 
-    let (lines, layout) = split_text(content);
-    let mut result = Vec::with_capacity(lines.len() + 5);
-    let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S");
-
-    result.push(format!("G04 Layer: {}*", layer_name));
-    result.push(format!("G04 EasyEDA Pro v3.2.91, {}*", now));
-    result.push("G04 Gerber Generator version 0.3*".to_string());
-    result.push("G04 Scale: 100 percent, Rotated: No, Reflected: No*".to_string());
-    result.push("G04 Generated by one-click*".to_string());
-    result.extend(lines);
+    let layout = TextLayout::detect(content);
+    let now = generated.format("%Y-%m-%d %H:%M:%S");
+    let header = [
+        format!("G04 Layer: {}*", layer_name),
+        format!("G04 EasyEDA Pro v3.2.91, {}*", now),
+        "G04 Gerber Generator version 0.3*".to_string(),
+        "G04 Scale: 100 percent, Rotated: No, Reflected: No*".to_string(),
+        "G04 Generated by one-click*".to_string(),
+    ];
+
+    let mut result: String = header.iter().map(|line| layout.line(line)).collect();
+    result.push_str(content);
+    result
+}
 
-    join_text(&result, layout)
+pub fn apply_lceda_signature(content: &str, imported_mode: bool, rng: &mut dyn RngCore) -> String {
+    apply_lceda_signature_encoded(content, TextEncoding::Utf8, imported_mode, rng)
+        .expect("UTF-8 总能编码")
 }
 
-pub fn apply_lceda_signature(content: &str, imported_mode: bool) -> String {
-    let (mut lines, layout) = split_text(content);
-    lines = strip_all_unused_adds(&lines);
+/// 同 [`apply_lceda_signature`]，校验值按文件实际写出的字节计算；内容无法按 `encoding` 写回时报错
+pub fn apply_lceda_signature_encoded(
+    content: &str,
+    encoding: TextEncoding,
+    imported_mode: bool,
+    rng: &mut dyn RngCore,
+) -> Result<String> {
+    // 各行保留自己的行尾，新插入的签名行沿用文件的换行风格
+    let layout = TextLayout::detect(content);
+    let lines: Vec<String> = split_lines(content)
+        .into_iter()
+        .map(str::to_string)
+        .collect();
+    let lines = strip_all_unused_adds(&lines);
 
     let add_lines = collect_add_lines(&lines);
     if add_lines.is_empty() {
-        return join_text(&lines, layout);
+        return Ok(lines.concat());
     }
 
-    let mut rng = rand::thread_rng();
     let pick = 5 + rng.gen_range(0..5);
     let selected = &add_lines[pick.min(add_lines.len() - 1)];
     let selected_id = selected.id;
 
     let mut shifted = shift_aperture_ids(&lines, selected_id);
-    let shifted_text = join_text(&shifted, layout);
+    let shifted_text = shifted.concat();
 
     let hash_base = if imported_mode {
         format!("494d{}", shifted_text)
     } else {
         shifted_text
     };
-    let pair = md5_pair(&hash_base);
+    let pair = md5_pair(&encoding.encode(&hash_base)?);
 
-    let signature_line = build_signature_line(&selected.line, selected_id, &pair, &mut rng);
+    let (selected_line, _) = split_eol(&selected.line);
+    let signature_line = build_signature_line(selected_line, selected_id, &pair, rng);
     let insert_at = find_insert_index(&shifted, selected_id);
-    shifted.insert(insert_at, signature_line);
-
-    join_text(&shifted, layout)
-}
-
-fn split_text(content: &str) -> (Vec<String>, TextLayout) {
-    let lines = content.lines().map(|line| line.to_string()).collect();
-    let has_trailing_newline = content.ends_with('\n');
-    let eol = if content.contains("\r\n") {
-        "\r\n"
-    } else {
-        "\n"
-    };
-    (
-        lines,
-        TextLayout {
-            has_trailing_newline,
-            eol,
-        },
-    )
-}
+    insert_line(&mut shifted, insert_at, &signature_line, layout);
 
-fn join_text(lines: &[String], layout: TextLayout) -> String {
-    let mut text = lines.join(layout.eol);
-    if layout.has_trailing_newline && !text.is_empty() {
-        text.push_str(layout.eol);
-    }
-    text
+    Ok(shifted.concat())
 }
 
 fn collect_add_lines(lines: &[String]) -> Vec<AddLine> {
@@ -206,8 +197,8 @@ fn rewrite_prefixed_id(
     Some(format!("{}{}{}", prefix, new_id, suffix))
 }
 
-fn md5_pair(input: &str) -> String {
-    let digest = md5::compute(input.as_bytes());
+fn md5_pair(input: &[u8]) -> String {
+    let digest = md5::compute(input);
     format!("{:02}", digest.0[15] % 100)
 }
 
@@ -215,7 +206,7 @@ fn build_signature_line(
     selected_line: &str,
     selected_id: u16,
     pair: &str,
-    rng: &mut impl Rng,
+    rng: &mut dyn RngCore,
 ) -> String {
     let number_re = Regex::new(r",([\d.]+)").expect("valid number regex");
     let base_line = if number_re.is_match(selected_line) {
@@ -0,0 +1,642 @@
+use crate::gerber::layout::split_eol;
+use crate::gerber::lint::CONTOUR_ROUNDING_GAP;
+use crate::gerber::units::CoordinateFormat;
+use regex::Regex;
+use std::f64::consts::{FRAC_PI_2, TAU};
+
+/// Gerber 内部单位的坐标点
+pub type Point = (i64, i64);
+
+/// 插补模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interpolation {
+    Linear,
+    Clockwise,
+    CounterClockwise,
+}
+
+/// 圆弧象限模式 (G74/G75)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuadrantMode {
+    Single,
+    Multi,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperationKind {
+    /// D01
+    Draw,
+    /// D02
+    Move,
+    /// D03
+    Flash,
+}
+
+/// 解析后的单个操作，坐标均已按模态规则补全
+#[derive(Debug, Clone)]
+pub struct Operation {
+    /// 从 0 开始的行号
+    pub line: usize,
+    pub kind: OperationKind,
+    pub interpolation: Interpolation,
+    pub quadrant: QuadrantMode,
+    pub start: Point,
+    pub end: Point,
+    /// 文件中写出的 I/J，未写出的分量为 0
+    pub offset: Point,
+    pub has_x: bool,
+    pub has_y: bool,
+    pub in_region: bool,
+}
+
+impl Operation {
+    pub fn is_arc(&self) -> bool {
+        self.kind == OperationKind::Draw && self.interpolation != Interpolation::Linear
+    }
+
+    /// 整圆：多象限模式下起点与终点重合
+    pub fn is_full_circle(&self) -> bool {
+        self.is_arc() && self.quadrant == QuadrantMode::Multi && self.start == self.end
+    }
+
+    /// 圆心的绝对坐标
+    ///
+    /// 单象限模式下 I/J 不带符号，从四个候选圆心中选出
+    /// 扫角不超过 90° 且首尾半径最接近的一个。
+    pub fn center(&self) -> Option<(f64, f64)> {
+        if !self.is_arc() {
+            return None;
+        }
+
+        let (sx, sy) = (self.start.0 as f64, self.start.1 as f64);
+        let (i, j) = (self.offset.0 as f64, self.offset.1 as f64);
+
+        if self.quadrant == QuadrantMode::Multi {
+            return Some((sx + i, sy + j));
+        }
+
+        let candidates = [
+            (sx + i.abs(), sy + j.abs()),
+            (sx - i.abs(), sy + j.abs()),
+            (sx + i.abs(), sy - j.abs()),
+            (sx - i.abs(), sy - j.abs()),
+        ];
+        candidates
+            .iter()
+            .filter(|c| {
+                sweep_angle(**c, self.start, self.end, self.interpolation) <= FRAC_PI_2 + 1e-6
+            })
+            .min_by(|a, b| {
+                let da = radius_mismatch(**a, self.start, self.end);
+                let db = radius_mismatch(**b, self.start, self.end);
+                da.total_cmp(&db)
+            })
+            .copied()
+            .or(Some(candidates[0]))
+    }
+}
+
+fn radius_mismatch(center: (f64, f64), start: Point, end: Point) -> f64 {
+    (distance(center, start) - distance(center, end)).abs()
+}
+
+fn distance(center: (f64, f64), p: Point) -> f64 {
+    let dx = p.0 as f64 - center.0;
+    let dy = p.1 as f64 - center.1;
+    (dx * dx + dy * dy).sqrt()
+}
+
+/// 按插补方向从起点扫到终点的角度，范围 [0, 2π)
+fn sweep_angle(center: (f64, f64), start: Point, end: Point, interpolation: Interpolation) -> f64 {
+    let a0 = (start.1 as f64 - center.1).atan2(start.0 as f64 - center.0);
+    let a1 = (end.1 as f64 - center.1).atan2(end.0 as f64 - center.0);
+    let raw = match interpolation {
+        Interpolation::Clockwise => a0 - a1,
+        _ => a1 - a0,
+    };
+    raw.rem_euclid(TAU)
+}
+
+/// 一条指令字中坐标值的原始写法，用于保持补零宽度
+#[derive(Default, Clone)]
+struct CoordText {
+    x: Option<String>,
+    y: Option<String>,
+    i: Option<String>,
+    j: Option<String>,
+}
+
+struct OperationWord {
+    text: String,
+    prefix: String,
+    coords: CoordText,
+    suffix: String,
+    d_code: Option<u32>,
+}
+
+/// 逐行扫描 Gerber 文本并维护模态状态
+struct Scanner {
+    format: CoordinateFormat,
+    code_re: Regex,
+    word_re: Regex,
+    axis_re: Regex,
+    interpolation: Interpolation,
+    quadrant: QuadrantMode,
+    in_region: bool,
+    last_kind: OperationKind,
+    point: Point,
+}
+
+impl Scanner {
+    fn new(format: CoordinateFormat) -> Self {
+        Self {
+            format,
+            code_re: Regex::new(r"^G(\d+)").unwrap(),
+            word_re: Regex::new(r"^((?:[XYIJ][+-]?\d+)+)(D\d+)?(.*)$").unwrap(),
+            axis_re: Regex::new(r"([XYIJ])([+-]?\d+)").unwrap(),
+            interpolation: Interpolation::Linear,
+            quadrant: QuadrantMode::Single,
+            in_region: false,
+            last_kind: OperationKind::Draw,
+            point: (0, 0),
+        }
+    }
+
+    /// 吃掉指令字开头的 G 码并更新模态状态，返回剩余部分
+    fn consume_codes<'w>(&mut self, word: &'w str) -> (&'w str, &'w str) {
+        let mut rest = word;
+        while let Some(caps) = self.code_re.captures(rest) {
+            match caps[1].parse::<u32>().unwrap_or(u32::MAX) {
+                1 => self.interpolation = Interpolation::Linear,
+                2 => self.interpolation = Interpolation::Clockwise,
+                3 => self.interpolation = Interpolation::CounterClockwise,
+                36 => self.in_region = true,
+                37 => self.in_region = false,
+                74 => self.quadrant = QuadrantMode::Single,
+                75 => self.quadrant = QuadrantMode::Multi,
+                _ => {}
+            }
+            rest = &rest[caps[0].len()..];
+        }
+        (&word[..word.len() - rest.len()], rest)
+    }
+
+    fn split_operation(&self, prefix: &str, rest: &str) -> Option<OperationWord> {
+        let caps = self.word_re.captures(rest)?;
+        let mut coords = CoordText::default();
+        for axis in self.axis_re.captures_iter(&caps[1]) {
+            let value = Some(axis[2].to_string());
+            match &axis[1] {
+                "X" => coords.x = value,
+                "Y" => coords.y = value,
+                "I" => coords.i = value,
+                _ => coords.j = value,
+            }
+        }
+        let d_code = caps
+            .get(2)
+            .and_then(|m| m.as_str()[1..].parse::<u32>().ok());
+        Some(OperationWord {
+            text: format!("{}{}", prefix, rest),
+            prefix: prefix.to_string(),
+            coords,
+            suffix: format!(
+                "{}{}",
+                caps.get(2).map(|m| m.as_str()).unwrap_or(""),
+                &caps[3]
+            ),
+            d_code,
+        })
+    }
+
+    fn resolve(&mut self, line: usize, word: &OperationWord) -> Operation {
+        let kind = match word.d_code {
+            Some(1) => OperationKind::Draw,
+            Some(2) => OperationKind::Move,
+            Some(3) => OperationKind::Flash,
+            _ => self.last_kind,
+        };
+        self.last_kind = kind;
+
+        let parse = |v: &Option<String>| v.as_ref().and_then(|s| self.format.parse_raw(s));
+        let end = (
+            parse(&word.coords.x).unwrap_or(self.point.0),
+            parse(&word.coords.y).unwrap_or(self.point.1),
+        );
+        let offset = (
+            parse(&word.coords.i).unwrap_or(0),
+            parse(&word.coords.j).unwrap_or(0),
+        );
+
+        let op = Operation {
+            line,
+            kind,
+            interpolation: self.interpolation,
+            quadrant: self.quadrant,
+            start: self.point,
+            end,
+            offset,
+            has_x: word.coords.x.is_some(),
+            has_y: word.coords.y.is_some(),
+            in_region: self.in_region,
+        };
+        self.point = end;
+        op
+    }
+}
+
+/// 解析文件中的所有操作
+pub fn parse_operations(content: &str) -> Vec<Operation> {
+    let mut ops = Vec::new();
+    scan(content, |op, _| {
+        ops.push(op.clone());
+        None
+    });
+    ops
+}
+
+/// 对每个操作点应用坐标变换，并重算圆弧的 I/J
+///
+/// `map` 接收原始操作和当前点（已变换）的新位置，返回该操作终点的新位置。
+/// 圆弧的圆心会被重新放到新起点与新终点的中垂线上，保证首尾半径在
+/// 坐标分辨率内一致；单象限模式下写回不带符号的 I/J。多象限整圆保持闭合。
+/// G36/G37 区域内的每条轮廓作为整体处理，见 [`Contour`]。
+/// 行尾和未改动的内容原样保留。
+pub fn transform_operations<F>(content: &str, mut map: F) -> String
+where
+    F: FnMut(&Operation, Point) -> Point,
+{
+    let format = CoordinateFormat::gerber(content);
+    let ends = plan_endpoints(&parse_operations(content), &mut map);
+    let mut index = 0;
+    let mut current_new: Point = (0, 0);
+
+    scan(content, |op, word| {
+        let new_start = current_new;
+        let new_end = ends[index];
+        index += 1;
+
+        let new_offset = if op.is_arc() {
+            Some(recompute_offset(op, new_start, new_end))
+        } else {
+            None
+        };
+
+        let previous_new = current_new;
+        current_new = new_end;
+
+        let mut text = word.prefix.clone();
+        if op.has_x || new_end.0 != previous_new.0 {
+            text.push('X');
+            text.push_str(&format.format_raw(new_end.0, word.coords.x.as_deref()));
+        }
+        if op.has_y || new_end.1 != previous_new.1 {
+            text.push('Y');
+            text.push_str(&format.format_raw(new_end.1, word.coords.y.as_deref()));
+        }
+        match new_offset {
+            Some(offset) => {
+                if word.coords.i.is_some() || offset.0 != 0 {
+                    text.push('I');
+                    text.push_str(&format.format_raw(offset.0, word.coords.i.as_deref()));
+                }
+                if word.coords.j.is_some() || offset.1 != 0 {
+                    text.push('J');
+                    text.push_str(&format.format_raw(offset.1, word.coords.j.as_deref()));
+                }
+            }
+            None => {
+                if let Some(i) = &word.coords.i {
+                    text.push('I');
+                    text.push_str(i);
+                }
+                if let Some(j) = &word.coords.j {
+                    text.push('J');
+                    text.push_str(j);
+                }
+            }
+        }
+        text.push_str(&word.suffix);
+        (text != word.text).then_some(text)
+    })
+}
+
+/// 按顺序计算每个操作的新终点
+fn plan_endpoints<F>(ops: &[Operation], map: &mut F) -> Vec<Point>
+where
+    F: FnMut(&Operation, Point) -> Point,
+{
+    let mut ends: Vec<Point> = Vec::with_capacity(ops.len());
+    let mut current: Point = (0, 0);
+    let mut contour: Option<Contour> = None;
+
+    for (index, op) in ops.iter().enumerate() {
+        // 轮廓遇到 D02、闪烁或离开区域即结束，需在后续操作取当前点之前收尾
+        let continues = op.in_region && op.kind == OperationKind::Draw;
+        if !continues {
+            if let Some(finished) = contour.take() {
+                finished.finish(ops, &mut ends);
+                current = ends[ends.len() - 1];
+            }
+        }
+
+        let mut end = map(op, current);
+        if op.is_full_circle() {
+            end = current;
+        }
+        ends.push(end);
+
+        if op.in_region && contour.is_none() {
+            contour = match op.kind {
+                OperationKind::Move => Some(Contour {
+                    first: index,
+                    start_old: op.end,
+                    start_new: end,
+                }),
+                OperationKind::Draw => Some(Contour {
+                    first: index,
+                    start_old: op.start,
+                    start_new: current,
+                }),
+                OperationKind::Flash => None,
+            };
+        }
+        current = end;
+    }
+
+    if let Some(finished) = contour.take() {
+        finished.finish(ops, &mut ends);
+    }
+
+    ends
+}
+
+/// 区域 (G36/G37) 中的一条轮廓
+///
+/// 轮廓从 D02 或区域内第一个 D01 开始，到下一个 D02 或 G37 结束。
+/// 原本闭合的轮廓变换后首尾仍然重合；若逐点变换后轮廓自相交，
+/// 则退回按起点位移整体平移，保持原有形状。
+struct Contour {
+    /// 第一个属于该轮廓的操作下标
+    first: usize,
+    start_old: Point,
+    start_new: Point,
+}
+
+impl Contour {
+    fn finish(&self, ops: &[Operation], ends: &mut [Point]) {
+        let last = ends.len() - 1;
+
+        // 保留原有的首尾差，原本闭合的轮廓因此严格闭合
+        let gap = (
+            ops[last].end.0 - self.start_old.0,
+            ops[last].end.1 - self.start_old.1,
+        );
+        if gap.0.abs() <= CONTOUR_ROUNDING_GAP && gap.1.abs() <= CONTOUR_ROUNDING_GAP {
+            ends[last] = (self.start_new.0 + gap.0, self.start_new.1 + gap.1);
+        }
+
+        if self.is_simple(ops, ends) {
+            return;
+        }
+
+        let shift = (
+            self.start_new.0 - self.start_old.0,
+            self.start_new.1 - self.start_old.1,
+        );
+        for (op, end) in ops[self.first..].iter().zip(ends[self.first..].iter_mut()) {
+            *end = (op.end.0 + shift.0, op.end.1 + shift.1);
+        }
+    }
+
+    /// 变换后的轮廓是否为简单多边形（圆弧按折线近似）
+    fn is_simple(&self, ops: &[Operation], ends: &[Point]) -> bool {
+        let mut points: Vec<(f64, f64)> = vec![to_f64(self.start_new)];
+        let mut previous = self.start_new;
+
+        for (op, end) in ops[self.first..].iter().zip(ends[self.first..].iter()) {
+            if op.kind != OperationKind::Draw {
+                continue;
+            }
+            if op.is_arc() {
+                let arc = Operation {
+                    start: previous,
+                    end: *end,
+                    offset: recompute_offset(op, previous, *end),
+                    ..op.clone()
+                };
+                points.extend(sample_arc(&arc));
+            }
+            points.push(to_f64(*end));
+            previous = *end;
+        }
+
+        points.dedup();
+        is_simple_polyline(&points)
+    }
+}
+
+/// 每段圆弧在自相交检查中插入的中间点数
+const ARC_SAMPLES: usize = 4;
+
+fn to_f64(p: Point) -> (f64, f64) {
+    (p.0 as f64, p.1 as f64)
+}
+
+/// 圆弧内部的采样点（不含首尾）
+fn sample_arc(arc: &Operation) -> Vec<(f64, f64)> {
+    let center = match arc.center() {
+        Some(c) => c,
+        None => return Vec::new(),
+    };
+    let mut sweep = sweep_angle(center, arc.start, arc.end, arc.interpolation);
+    if arc.start == arc.end && arc.quadrant == QuadrantMode::Multi {
+        sweep = TAU;
+    }
+    let radius = distance(center, arc.start);
+    let a0 = (arc.start.1 as f64 - center.1).atan2(arc.start.0 as f64 - center.0);
+    let direction = if arc.interpolation == Interpolation::Clockwise {
+        -1.0
+    } else {
+        1.0
+    };
+
+    (1..=ARC_SAMPLES)
+        .map(|k| {
+            let a = a0 + direction * sweep * k as f64 / (ARC_SAMPLES + 1) as f64;
+            (center.0 + radius * a.cos(), center.1 + radius * a.sin())
+        })
+        .collect()
+}
+
+/// 折线（首尾相同即闭合）中不相邻的边是否互不接触
+fn is_simple_polyline(points: &[(f64, f64)]) -> bool {
+    let segments = points.len().saturating_sub(1);
+    if segments < 3 {
+        return true;
+    }
+    let closed = points[0] == points[segments];
+
+    for i in 0..segments {
+        for j in (i + 2)..segments {
+            if closed && i == 0 && j == segments - 1 {
+                continue;
+            }
+            if segments_touch(points[i], points[i + 1], points[j], points[j + 1]) {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+fn segments_touch(a: (f64, f64), b: (f64, f64), c: (f64, f64), d: (f64, f64)) -> bool {
+    if a.0.max(b.0) < c.0.min(d.0)
+        || c.0.max(d.0) < a.0.min(b.0)
+        || a.1.max(b.1) < c.1.min(d.1)
+        || c.1.max(d.1) < a.1.min(b.1)
+    {
+        return false;
+    }
+
+    let d1 = orientation(c, d, a);
+    let d2 = orientation(c, d, b);
+    let d3 = orientation(a, b, c);
+    let d4 = orientation(a, b, d);
+
+    if d1 * d2 < 0.0 && d3 * d4 < 0.0 {
+        return true;
+    }
+    (d1 == 0.0 && on_segment(c, d, a))
+        || (d2 == 0.0 && on_segment(c, d, b))
+        || (d3 == 0.0 && on_segment(a, b, c))
+        || (d4 == 0.0 && on_segment(a, b, d))
+}
+
+fn orientation(a: (f64, f64), b: (f64, f64), p: (f64, f64)) -> f64 {
+    (b.0 - a.0) * (p.1 - a.1) - (b.1 - a.1) * (p.0 - a.0)
+}
+
+fn on_segment(a: (f64, f64), b: (f64, f64), p: (f64, f64)) -> bool {
+    p.0 >= a.0.min(b.0) && p.0 <= a.0.max(b.0) && p.1 >= a.1.min(b.1) && p.1 <= a.1.max(b.1)
+}
+
+/// 扫描所有操作指令字，回调返回 `Some` 时用其替换该指令字
+fn scan<F>(content: &str, mut visit: F) -> String
+where
+    F: FnMut(&Operation, &OperationWord) -> Option<String>,
+{
+    let mut scanner = Scanner::new(CoordinateFormat::gerber(content));
+    let mut result = String::with_capacity(content.len());
+    let mut in_extended = false;
+
+    for (index, raw_line) in content.split_inclusive('\n').enumerate() {
+        let (body, eol) = split_eol(raw_line);
+        let trimmed = body.trim();
+
+        if in_extended {
+            in_extended = !trimmed.ends_with('%');
+            result.push_str(raw_line);
+            continue;
+        }
+        if trimmed.starts_with('%') {
+            in_extended = trimmed.len() == 1 || !trimmed.ends_with('%');
+            result.push_str(raw_line);
+            continue;
+        }
+        if trimmed.is_empty() || trimmed.starts_with("G04") {
+            result.push_str(raw_line);
+            continue;
+        }
+
+        let mut rewritten = String::with_capacity(body.len());
+        let mut changed = false;
+        let leading = &body[..body.len() - body.trim_start().len()];
+        rewritten.push_str(leading);
+
+        let words: Vec<&str> = body.trim_start().split('*').collect();
+        for (n, word) in words.iter().enumerate() {
+            if n > 0 {
+                rewritten.push('*');
+            }
+            if word.starts_with("G04") {
+                rewritten.push_str(word);
+                continue;
+            }
+
+            let (prefix, rest) = scanner.consume_codes(word);
+            let parsed = match scanner.split_operation(prefix, rest) {
+                Some(p) => p,
+                None => {
+                    rewritten.push_str(word);
+                    continue;
+                }
+            };
+
+            let op = scanner.resolve(index, &parsed);
+            match visit(&op, &parsed) {
+                Some(text) => {
+                    rewritten.push_str(&text);
+                    changed = true;
+                }
+                None => rewritten.push_str(word),
+            }
+        }
+
+        if changed {
+            result.push_str(&rewritten);
+            result.push_str(eol);
+        } else {
+            result.push_str(raw_line);
+        }
+    }
+
+    result
+}
+
+/// 在新起点和新终点的中垂线上重新确定圆心，返回新的 I/J
+fn recompute_offset(op: &Operation, new_start: Point, new_end: Point) -> Point {
+    // 两端点位移相同即整体平移，圆弧形状不变
+    let start_shift = (new_start.0 - op.start.0, new_start.1 - op.start.1);
+    let end_shift = (new_end.0 - op.end.0, new_end.1 - op.end.1);
+    if start_shift == end_shift {
+        return op.offset;
+    }
+
+    let center = match op.center() {
+        Some(c) => c,
+        None => return op.offset,
+    };
+
+    // 圆心先随两端点的平均位移平移，再投影到新的中垂线上
+    let shift = (
+        (start_shift.0 + end_shift.0) as f64 / 2.0,
+        (start_shift.1 + end_shift.1) as f64 / 2.0,
+    );
+    let moved = (center.0 + shift.0, center.1 + shift.1);
+
+    let new_center = if new_start == new_end {
+        moved
+    } else {
+        let mid = (
+            (new_start.0 + new_end.0) as f64 / 2.0,
+            (new_start.1 + new_end.1) as f64 / 2.0,
+        );
+        let chord = (
+            (new_end.0 - new_start.0) as f64,
+            (new_end.1 - new_start.1) as f64,
+        );
+        let len = (chord.0 * chord.0 + chord.1 * chord.1).sqrt();
+        let normal = (-chord.1 / len, chord.0 / len);
+        let t = (moved.0 - mid.0) * normal.0 + (moved.1 - mid.1) * normal.1;
+        (mid.0 + normal.0 * t, mid.1 + normal.1 * t)
+    };
+
+    let i = (new_center.0 - new_start.0 as f64).round() as i64;
+    let j = (new_center.1 - new_start.1 as f64).round() as i64;
+
+    match op.quadrant {
+        QuadrantMode::Multi => (i, j),
+        QuadrantMode::Single => (i.abs(), j.abs()),
+    }
+}
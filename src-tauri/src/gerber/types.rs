@@ -1,7 +1,16 @@
+use crate::gerber::error::{GhostPcbError, Result};
+use crate::gerber::lint::Diagnostic;
+use crate::gerber::package::{ArchiveLimits, PackageFormat, RepackOptions};
+use crate::gerber::params::StageParams;
+use crate::gerber::profile::CapabilityProfile;
+use crate::gerber::report::PackageReport;
+use crate::gerber::rules::LayerRule;
+use crate::gerber::validation::ValidationIssue;
+use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 
 /// Gerber 文件类型
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum GerberFileType {
     TopLayer,         // .GTL
     BottomLayer,      // .GBL
@@ -13,6 +22,7 @@ pub enum GerberFileType {
     BottomPaste,      // .GBP
     BoardOutline,     // .GKO
     InnerLayer,       // .G1-.Gn
+    Document,         // .GDL/.GDD
     Drill,            // .DRL
     Unknown,
 }
@@ -29,12 +39,30 @@ impl GerberFileType {
             "GTP" => Self::TopPaste,
             "GBP" => Self::BottomPaste,
             "GKO" => Self::BoardOutline,
+            "GDL" | "GDD" => Self::Document,
             "DRL" => Self::Drill,
             s if s.starts_with('G') && s.len() >= 2 => Self::InnerLayer,
             _ => Self::Unknown,
         }
     }
 
+    /// 除钻孔外的所有 Gerber 层
+    pub fn gerber_layers() -> &'static [Self] {
+        &[
+            Self::TopLayer,
+            Self::BottomLayer,
+            Self::TopSilkscreen,
+            Self::BottomSilkscreen,
+            Self::TopSolderMask,
+            Self::BottomSolderMask,
+            Self::TopPaste,
+            Self::BottomPaste,
+            Self::BoardOutline,
+            Self::InnerLayer,
+            Self::Document,
+        ]
+    }
+
     pub fn is_silkscreen(&self) -> bool {
         matches!(self, Self::TopSilkscreen | Self::BottomSilkscreen)
     }
@@ -53,27 +81,91 @@ impl GerberFileType {
 }
 
 /// 混淆配置选项
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ObfuscateOptions {
+    /// 跨层一致位移
+    #[serde(default = "default_true")]
+    pub displacement: bool,
     pub timestamp: bool,
     pub silkscreen: bool,
     pub geometry: bool,
     pub structure: bool,
     pub physical: bool,
+    /// 各阶段的数值参数
+    #[serde(default)]
+    pub params: StageParams,
+    /// 按层覆盖开关与参数，按顺序叠加，后面的规则优先
+    #[serde(default)]
+    pub rules: Vec<LayerRule>,
+}
+
+fn default_true() -> bool {
+    true
 }
 
 impl Default for ObfuscateOptions {
     fn default() -> Self {
         Self {
+            displacement: true,
             timestamp: false,
             silkscreen: true,
             geometry: false,
             structure: false,
             physical: false,
+            params: StageParams::default(),
+            rules: Vec::new(),
         }
     }
 }
 
+impl ObfuscateOptions {
+    /// 某个文件实际生效的选项，结果中不再包含规则
+    pub fn for_layer(&self, name: &str, file_type: GerberFileType) -> Self {
+        let mut options = Self {
+            rules: Vec::new(),
+            ..self.clone()
+        };
+        for rule in self.rules.iter().filter(|r| r.matches(name, file_type)) {
+            rule.apply(&mut options);
+        }
+        options
+    }
+
+    /// 检查全局参数与各规则中的参数
+    pub fn validate(&self, profile: &CapabilityProfile) -> Result<()> {
+        self.params.validate(profile)?;
+        for rule in &self.rules {
+            rule.validate_patterns()?;
+            if let Some(patch) = &rule.params {
+                let mut params = self.params.clone();
+                patch.apply(&mut params);
+                params.validate(profile)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 检查某个文件叠加所有匹配规则后实际生效的参数
+    ///
+    /// 多条规则各自合法，叠加后仍可能得到颠倒的范围。
+    pub fn validate_layer(
+        &self,
+        name: &str,
+        file_type: GerberFileType,
+        profile: &CapabilityProfile,
+    ) -> Result<()> {
+        self.for_layer(name, file_type)
+            .params
+            .validate(profile)
+            .map_err(|e| match e {
+                GhostPcbError::InvalidOptions(message) => {
+                    GhostPcbError::InvalidOptions(format!("{}: {}", name, message))
+                }
+                e => e,
+            })
+    }
+}
+
 /// 处理请求
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProcessRequest {
@@ -81,6 +173,67 @@ pub struct ProcessRequest {
     pub output_dir: Option<String>,
     pub count: u32,
     pub options: ObfuscateOptions,
+    /// 工艺能力配置：内置名称或 TOML/JSON 文件路径，缺省为 standard
+    #[serde(default)]
+    pub profile: Option<String>,
+    /// 除 JSON 报告外，是否额外生成 HTML 报告
+    #[serde(default)]
+    pub html_report: bool,
+    /// 随机种子，相同的种子、输入与选项生成相同的坐标变换；缺省时随机选取
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// 试运行：只在内存中解析、处理和校验，返回计划而不写出任何文件
+    #[serde(default)]
+    pub dry_run: bool,
+    /// 并行生成时的最大工作线程数，缺省或为 0 时取 CPU 核数
+    #[serde(default)]
+    pub threads: Option<usize>,
+    /// 任务 ID，用于进度事件与取消；缺省时自动生成
+    #[serde(default)]
+    pub job_id: Option<String>,
+    /// 读取输入包时的条目数与解压大小限制
+    #[serde(default)]
+    pub archive_limits: ArchiveLimits,
+    /// 出错时删除本次已生成的所有输出；关闭后保留已完成的包
+    #[serde(default = "default_true")]
+    pub rollback_on_error: bool,
+    /// 严格模式：任一文件有语法错误即中止，适合 CI；
+    /// 关闭时这些文件原样保留并记为警告
+    #[serde(default)]
+    pub strict: bool,
+    /// 输出的容器类型，缺省与输入相同
+    #[serde(default)]
+    pub output_format: Option<PackageFormat>,
+    /// 输出包的打包方式，缺省保留输入的条目元数据
+    #[serde(default)]
+    pub repack: RepackOptions,
+    /// 作为“现在”的时间，缺省取本机当前时间；重现此前的输出时传入报告中记录的值
+    #[serde(default)]
+    pub reference_time: Option<NaiveDateTime>,
+}
+
+/// 与反序列化时省略可选字段的请求相同，只生成一个变体
+impl Default for ProcessRequest {
+    fn default() -> Self {
+        Self {
+            input_path: String::new(),
+            output_dir: None,
+            count: 1,
+            options: ObfuscateOptions::default(),
+            profile: None,
+            html_report: false,
+            seed: None,
+            dry_run: false,
+            threads: None,
+            job_id: None,
+            archive_limits: ArchiveLimits::default(),
+            rollback_on_error: true,
+            strict: false,
+            output_format: None,
+            repack: RepackOptions::default(),
+            reference_time: None,
+        }
+    }
 }
 
 /// 处理结果
@@ -89,4 +242,45 @@ pub struct ProcessResult {
     pub success: bool,
     pub output_files: Vec<String>,
     pub message: String,
+    /// 实际使用的随机种子
+    #[serde(default)]
+    pub seed: u64,
+    /// 按执行顺序排列的已启用处理阶段
+    #[serde(default)]
+    pub obfuscators: Vec<String>,
+    /// 输入文件相对工艺配置的校验结果
+    #[serde(default)]
+    pub validation: Vec<ValidationIssue>,
+    /// 预检阶段的语法警告
+    #[serde(default)]
+    pub diagnostics: Vec<Diagnostic>,
+    /// 无法处理、原样保留的文件
+    #[serde(default)]
+    pub warnings: Vec<String>,
+    /// 每个输出包的变更与校验报告，顺序与 output_files 一致；
+    /// 试运行时为预览结果，output_file 是将要生成的路径
+    #[serde(default)]
+    pub reports: Vec<PackageReport>,
+    /// 试运行时的处理计划
+    #[serde(default)]
+    pub plan: Option<ProcessPlan>,
+}
+
+/// 试运行得到的处理计划
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProcessPlan {
+    /// 将要创建的输出目录
+    pub output_dir: String,
+    /// 输入包中每个文件的处理方式
+    pub files: Vec<PlannedFile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlannedFile {
+    pub file: String,
+    pub file_type: GerberFileType,
+    /// 未识别的文件原样保留
+    pub recognized: bool,
+    /// 按规则展开后对该文件启用的阶段
+    pub stages: Vec<String>,
 }
@@ -0,0 +1,312 @@
+use regex::Regex;
+use std::ops::{Add, Mul, Neg, Sub};
+
+const MM_PER_INCH: f64 = 25.4;
+
+/// 长度，内部统一以毫米存储
+///
+/// 与文件坐标之间的换算一律通过 [`CoordinateFormat`]，
+/// 保证 `%MOIN*%` 与 `%MOMM*%` 文件的行为一致。
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+pub struct Length(f64);
+
+impl Length {
+    pub const ZERO: Length = Length(0.0);
+
+    pub const fn mm(value: f64) -> Self {
+        Self(value)
+    }
+
+    pub const fn inch(value: f64) -> Self {
+        Self(value * MM_PER_INCH)
+    }
+
+    pub const fn mil(value: f64) -> Self {
+        Self(value * MM_PER_INCH / 1000.0)
+    }
+
+    pub fn as_mm(self) -> f64 {
+        self.0
+    }
+
+    pub fn as_inch(self) -> f64 {
+        self.0 / MM_PER_INCH
+    }
+
+    pub fn as_mil(self) -> f64 {
+        self.0 / MM_PER_INCH * 1000.0
+    }
+
+    /// 按指定单位取值
+    pub fn in_unit(self, unit: Unit) -> f64 {
+        match unit {
+            Unit::Millimeter => self.as_mm(),
+            Unit::Inch => self.as_inch(),
+        }
+    }
+
+    pub fn from_unit(value: f64, unit: Unit) -> Self {
+        match unit {
+            Unit::Millimeter => Self::mm(value),
+            Unit::Inch => Self::inch(value),
+        }
+    }
+
+    pub fn min(self, other: Length) -> Self {
+        Self(self.0.min(other.0))
+    }
+
+    pub fn max(self, other: Length) -> Self {
+        Self(self.0.max(other.0))
+    }
+
+    pub fn abs(self) -> Self {
+        Self(self.0.abs())
+    }
+
+    pub fn hypot(self, other: Length) -> Self {
+        Self(self.0.hypot(other.0))
+    }
+}
+
+impl Add for Length {
+    type Output = Length;
+    fn add(self, rhs: Length) -> Length {
+        Length(self.0 + rhs.0)
+    }
+}
+
+impl Sub for Length {
+    type Output = Length;
+    fn sub(self, rhs: Length) -> Length {
+        Length(self.0 - rhs.0)
+    }
+}
+
+impl Neg for Length {
+    type Output = Length;
+    fn neg(self) -> Length {
+        Length(-self.0)
+    }
+}
+
+impl Mul<f64> for Length {
+    type Output = Length;
+    fn mul(self, rhs: f64) -> Length {
+        Length(self.0 * rhs)
+    }
+}
+
+/// 文件坐标单位
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    Millimeter,
+    Inch,
+}
+
+/// 不带小数点的坐标以哪一端对齐
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZeroPadding {
+    /// 保留前导零 (Excellon LZ、Gerber FST)，按整数位数定位小数点
+    Leading,
+    /// 保留末尾零 (Gerber FSLA、Excellon TZ)，按小数位数定位小数点
+    Trailing,
+}
+
+/// 文件的坐标格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CoordinateFormat {
+    pub unit: Unit,
+    pub integers: u32,
+    pub decimals: u32,
+    pub zeros: ZeroPadding,
+}
+
+impl CoordinateFormat {
+    /// 读取 Gerber 文件的 `%FS…*%` 与 `%MO…*%`，缺少格式声明时返回 `None`
+    pub fn detect_gerber(content: &str) -> Option<Self> {
+        let format_re = Regex::new(r"%FS([LT])AX(\d)(\d)Y(\d)(\d)\*%").unwrap();
+        let caps = format_re.captures(content)?;
+        Some(Self {
+            unit: Self::gerber_unit(content),
+            integers: caps[2].parse().unwrap_or(4),
+            decimals: caps[3].parse().unwrap_or(5),
+            zeros: if &caps[1] == "T" {
+                ZeroPadding::Leading
+            } else {
+                ZeroPadding::Trailing
+            },
+        })
+    }
+
+    /// 同 [`Self::detect_gerber`]，缺少格式声明时按 4.5 毫米处理
+    pub fn gerber(content: &str) -> Self {
+        Self::detect_gerber(content).unwrap_or(Self {
+            unit: Self::gerber_unit(content),
+            integers: 4,
+            decimals: 5,
+            zeros: ZeroPadding::Trailing,
+        })
+    }
+
+    fn gerber_unit(content: &str) -> Unit {
+        if content.contains("%MOIN*%") {
+            Unit::Inch
+        } else if content.contains("%MOMM*%") {
+            Unit::Millimeter
+        } else if content.lines().any(|l| l.trim_start().starts_with("G70")) {
+            Unit::Inch
+        } else {
+            Unit::Millimeter
+        }
+    }
+
+    /// 读取 Excellon 文件头，如 `METRIC,LZ,0000.00000` 或 `INCH,TZ`
+    pub fn excellon(content: &str) -> Self {
+        let header_re = Regex::new(r"^(METRIC|INCH)(?:,(LZ|TZ))?(?:,(0*)\.(0*))?").unwrap();
+
+        for line in content.lines() {
+            let caps = match header_re.captures(line.trim()) {
+                Some(c) => c,
+                None => continue,
+            };
+            let unit = if &caps[1] == "INCH" {
+                Unit::Inch
+            } else {
+                Unit::Millimeter
+            };
+            // 未声明时公制按 3.3、英制按 2.4
+            let (default_int, default_dec) = match unit {
+                Unit::Millimeter => (3, 3),
+                Unit::Inch => (2, 4),
+            };
+            let count = |i: usize, default: u32| {
+                caps.get(i)
+                    .map(|m| m.as_str().len() as u32)
+                    .filter(|n| *n > 0)
+                    .unwrap_or(default)
+            };
+            return Self {
+                unit,
+                integers: count(3, default_int),
+                decimals: count(4, default_dec),
+                zeros: match caps.get(2).map(|m| m.as_str()) {
+                    Some("LZ") => ZeroPadding::Leading,
+                    _ => ZeroPadding::Trailing,
+                },
+            };
+        }
+
+        Self {
+            unit: Unit::Millimeter,
+            integers: 3,
+            decimals: 3,
+            zeros: ZeroPadding::Trailing,
+        }
+    }
+
+    /// 长度转换为整数坐标
+    pub fn to_raw(&self, length: Length) -> i64 {
+        (length.in_unit(self.unit) * 10_f64.powi(self.decimals as i32)).round() as i64
+    }
+
+    pub fn from_raw(&self, raw: i64) -> Length {
+        Length::from_unit(raw as f64 / 10_f64.powi(self.decimals as i32), self.unit)
+    }
+
+    /// 解析坐标或尺寸文本，支持带小数点与省略小数点两种写法
+    pub fn parse(&self, text: &str) -> Option<Length> {
+        let text = text.trim();
+        if text.contains('.') {
+            return text
+                .parse::<f64>()
+                .ok()
+                .map(|v| Length::from_unit(v, self.unit));
+        }
+
+        let negative = text.starts_with('-');
+        let digits = text.trim_start_matches(['+', '-']);
+        let raw = digits.parse::<i64>().ok()?;
+        let value = match self.zeros {
+            ZeroPadding::Trailing => raw as f64 / 10_f64.powi(self.decimals as i32),
+            ZeroPadding::Leading => {
+                raw as f64 / 10_f64.powi(digits.len() as i32 - self.integers as i32)
+            }
+        };
+        let value = if negative { -value } else { value };
+        Some(Length::from_unit(value, self.unit))
+    }
+
+    /// 解析不带小数点的整数坐标文本
+    ///
+    /// 省略末尾零 (Gerber FST) 时按总位数在右侧补零，结果与 [`Self::to_raw`] 同一刻度。
+    pub fn parse_raw(&self, text: &str) -> Option<i64> {
+        let text = text.trim();
+        let negative = text.starts_with('-');
+        let digits = text.trim_start_matches(['+', '-']);
+        let width = (self.integers + self.decimals) as usize;
+        let raw = match self.zeros {
+            ZeroPadding::Leading if digits.len() < width => {
+                format!("{:0<width$}", digits, width = width).parse::<i64>()
+            }
+            _ => digits.parse::<i64>(),
+        }
+        .ok()?;
+        Some(if negative { -raw } else { raw })
+    }
+
+    /// 把整数坐标写回文本，是 [`Self::parse_raw`] 的逆操作
+    ///
+    /// 省略末尾零时去掉末尾的零；省略前导零时沿用原写法的前导零宽度。
+    pub fn format_raw(&self, raw: i64, template: Option<&str>) -> String {
+        let digits = raw.unsigned_abs().to_string();
+        let digits = match self.zeros {
+            ZeroPadding::Leading => {
+                let width = (self.integers + self.decimals) as usize;
+                let padded = format!("{:0>width$}", digits, width = width);
+                let trimmed = padded.trim_end_matches('0');
+                if trimmed.is_empty() {
+                    "0".to_string()
+                } else {
+                    trimmed.to_string()
+                }
+            }
+            ZeroPadding::Trailing => {
+                let width = template
+                    .map(|t| t.trim_start_matches(['+', '-']))
+                    .filter(|t| t.len() > 1 && t.starts_with('0'))
+                    .map(str::len)
+                    .unwrap_or(0);
+                format!("{:0>width$}", digits, width = width)
+            }
+        };
+        if raw < 0 {
+            format!("-{}", digits)
+        } else {
+            digits
+        }
+    }
+
+    /// 按原始写法格式化坐标：有小数点时保持至少原有精度，否则按格式补零
+    pub fn format(&self, length: Length, template: &str) -> String {
+        if let Some((_, fraction)) = template.split_once('.') {
+            let precision = fraction.len().max(self.decimals as usize);
+            return format!("{:.prec$}", length.in_unit(self.unit), prec = precision);
+        }
+
+        let raw = self.to_raw(length);
+        let digits = raw.unsigned_abs().to_string();
+        let digits = match self.zeros {
+            ZeroPadding::Leading => {
+                let width = (self.integers + self.decimals) as usize;
+                format!("{:0>width$}", digits, width = width)
+            }
+            ZeroPadding::Trailing => digits,
+        };
+        if raw < 0 {
+            format!("-{}", digits)
+        } else {
+            digits
+        }
+    }
+}
@@ -0,0 +1,459 @@
+use crate::gerber::board::{Board, BoardPoint, Extent};
+use crate::gerber::layout::split_eol;
+use crate::gerber::profile::CapabilityProfile;
+use crate::gerber::transform::{parse_operations, OperationKind};
+use crate::gerber::types::GerberFileType;
+use crate::gerber::units::{CoordinateFormat, Length};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// 校验问题级别
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// 超出工艺能力，板厂很可能无法生产
+    Error,
+    /// 接近或低于推荐值，需要人工确认
+    Warning,
+}
+
+/// 单条校验结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidationIssue {
+    pub file: String,
+    pub severity: Severity,
+    pub code: String,
+    pub message: String,
+}
+
+impl ValidationIssue {
+    fn new(file: &str, severity: Severity, code: &str, message: String) -> Self {
+        Self {
+            file: file.to_string(),
+            severity,
+            code: code.to_string(),
+            message,
+        }
+    }
+}
+
+/// 按工艺能力校验单个文件
+pub fn validate_layer(
+    file_name: &str,
+    content: &str,
+    file_type: GerberFileType,
+    profile: &CapabilityProfile,
+) -> Vec<ValidationIssue> {
+    let mut issues = Vec::new();
+
+    if file_type.is_drill() {
+        check_drill_sizes(file_name, content, profile, &mut issues);
+        return issues;
+    }
+
+    if file_type.is_outline() {
+        check_board_size(file_name, content, profile, &mut issues);
+    }
+
+    if file_type.is_silkscreen() {
+        check_draw_widths(
+            file_name,
+            content,
+            Length::mm(profile.min_silk_width_mm),
+            "silk-width",
+            "丝印线宽",
+            &mut issues,
+        );
+    }
+
+    if file_type.is_copper_layer() {
+        check_draw_widths(
+            file_name,
+            content,
+            Length::mm(profile.min_trace_mm),
+            "trace-width",
+            "走线线宽",
+            &mut issues,
+        );
+    }
+
+    issues
+}
+
+fn check_board_size(
+    file_name: &str,
+    content: &str,
+    profile: &CapabilityProfile,
+    issues: &mut Vec<ValidationIssue>,
+) {
+    let coord_re = Regex::new(r"X(-?\d+)Y(-?\d+)").unwrap();
+    let format = CoordinateFormat::gerber(content);
+    let mut bounds: Option<(i64, i64, i64, i64)> = None;
+
+    for line in content.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('%') || trimmed.starts_with("G04") {
+            continue;
+        }
+        if let Some(caps) = coord_re.captures(trimmed) {
+            if let (Some(x), Some(y)) = (format.parse_raw(&caps[1]), format.parse_raw(&caps[2])) {
+                bounds = Some(match bounds {
+                    Some((min_x, min_y, max_x, max_y)) => {
+                        (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y))
+                    }
+                    None => (x, y, x, y),
+                });
+            }
+        }
+    }
+
+    let (min_x, min_y, max_x, max_y) = match bounds {
+        Some(b) => b,
+        None => return,
+    };
+
+    let width = format.from_raw(max_x - min_x);
+    let height = format.from_raw(max_y - min_y);
+    let max_width = Length::mm(profile.max_board_width_mm);
+    let max_height = Length::mm(profile.max_board_height_mm);
+
+    // 允许板子旋转 90° 放置
+    let fits = (width <= max_width && height <= max_height)
+        || (width <= max_height && height <= max_width);
+    if !fits {
+        issues.push(ValidationIssue::new(
+            file_name,
+            Severity::Error,
+            "board-size",
+            format!(
+                "板子尺寸 {:.2}×{:.2}mm 超出 {} 的上限 {:.0}×{:.0}mm",
+                width.as_mm(),
+                height.as_mm(),
+                profile.name,
+                profile.max_board_width_mm,
+                profile.max_board_height_mm
+            ),
+        ));
+    }
+}
+
+fn check_drill_sizes(
+    file_name: &str,
+    content: &str,
+    profile: &CapabilityProfile,
+    issues: &mut Vec<ValidationIssue>,
+) {
+    let tool_re = Regex::new(r"^T(\d+)C([\d.]+)").unwrap();
+    let format = CoordinateFormat::excellon(content);
+    let min_drill = Length::mm(profile.min_drill_mm);
+
+    for line in content.lines() {
+        let caps = match tool_re.captures(line.trim()) {
+            Some(c) => c,
+            None => continue,
+        };
+        let diameter = match format.parse(&caps[2]) {
+            Some(d) => d,
+            None => continue,
+        };
+        if diameter + Length::mm(1e-6) < min_drill {
+            issues.push(ValidationIssue::new(
+                file_name,
+                Severity::Error,
+                "min-drill",
+                format!(
+                    "刀具 T{} 孔径 {:.3}mm 小于最小钻孔 {:.3}mm",
+                    &caps[1],
+                    diameter.as_mm(),
+                    min_drill.as_mm()
+                ),
+            ));
+        }
+    }
+}
+
+/// 检查用于画线 (D01) 的圆形光圈宽度
+fn check_draw_widths(
+    file_name: &str,
+    content: &str,
+    min_width: Length,
+    code: &str,
+    label: &str,
+    issues: &mut Vec<ValidationIssue>,
+) {
+    let add_re = Regex::new(r"^%ADD(\d+)C,([\d.]+)").unwrap();
+    let select_re = Regex::new(r"^(?:G54)?D(\d+)\*").unwrap();
+    let unit = CoordinateFormat::gerber(content).unit;
+
+    let mut diameters: HashMap<u32, Length> = HashMap::new();
+    let mut reported: HashSet<u32> = HashSet::new();
+    let mut current: Option<u32> = None;
+
+    for line in content.lines() {
+        let trimmed = line.trim();
+
+        if let Some(caps) = add_re.captures(trimmed) {
+            if let (Ok(id), Ok(d)) = (caps[1].parse::<u32>(), caps[2].parse::<f64>()) {
+                diameters.insert(id, Length::from_unit(d, unit));
+            }
+            continue;
+        }
+
+        if let Some(caps) = select_re.captures(trimmed) {
+            current = caps[1].parse::<u32>().ok().filter(|id| *id >= 10);
+            continue;
+        }
+
+        if !trimmed.ends_with("D01*") {
+            continue;
+        }
+
+        let id = match current {
+            Some(id) => id,
+            None => continue,
+        };
+        let diameter = match diameters.get(&id) {
+            Some(d) => *d,
+            None => continue,
+        };
+
+        // 0 宽度光圈常用于区域轮廓或板框，不视为线宽
+        if diameter > Length::ZERO && diameter + Length::mm(1e-6) < min_width && reported.insert(id)
+        {
+            issues.push(ValidationIssue::new(
+                file_name,
+                Severity::Warning,
+                code,
+                format!(
+                    "D{} {} {:.3}mm 小于推荐最小值 {:.3}mm",
+                    id,
+                    label,
+                    diameter.as_mm(),
+                    min_width.as_mm()
+                ),
+            ));
+        }
+    }
+}
+
+/// 判定焊盘与钻孔同心的距离上限
+const PAD_MATCH_DISTANCE_MM: f64 = 0.05;
+
+/// 按工艺能力校验整块板的跨层约束：孔环宽度和铜到板边的距离
+///
+/// 单个文件看不出这两项，需要在处理后的板上把钻孔与焊盘、铜层与板框对照检查。
+pub fn validate_board(board: &Board, profile: &CapabilityProfile) -> Vec<ValidationIssue> {
+    let mut issues = Vec::new();
+    let hits: Vec<(BoardPoint, Length)> = board
+        .layers
+        .iter()
+        .filter(|l| l.file_type.is_drill())
+        .flat_map(|l| drill_hits(&l.content))
+        .collect();
+    let outline: Vec<BoardPoint> = board
+        .layers
+        .iter()
+        .filter(|l| l.file_type.is_outline())
+        .flat_map(|l| l.points())
+        .collect();
+    let outline = Extent::from_points(&outline);
+
+    for layer in board.copper_stack() {
+        let features = copper_features(&layer.content);
+        check_annular_ring(&layer.name, &features, &hits, profile, &mut issues);
+        if let Some(outline) = outline {
+            check_edge_clearance(&layer.name, &features, outline, profile, &mut issues);
+        }
+    }
+
+    issues
+}
+
+/// 铜层上的一个图形：中心点、操作类型和光圈最小宽度（区域轮廓与未知光圈为 0）
+struct CopperFeature {
+    point: BoardPoint,
+    kind: OperationKind,
+    size: Length,
+}
+
+/// 钻孔文件中每个孔的位置与孔径
+fn drill_hits(content: &str) -> Vec<(BoardPoint, Length)> {
+    let tool_re = Regex::new(r"^T(\d+)C([\d.]+)").unwrap();
+    let select_re = Regex::new(r"^T(\d+)$").unwrap();
+    let coord_re = Regex::new(r"([XY])([+-]?[\d.]+)").unwrap();
+    let format = CoordinateFormat::excellon(content);
+
+    let mut tools: HashMap<u32, Length> = HashMap::new();
+    let mut current_tool: Option<u32> = None;
+    let mut current = (Length::ZERO, Length::ZERO);
+    let mut hits = Vec::new();
+
+    for line in content.lines() {
+        let trimmed = line.trim();
+        if let Some(caps) = tool_re.captures(trimmed) {
+            if let (Ok(id), Some(d)) = (caps[1].parse::<u32>(), format.parse(&caps[2])) {
+                tools.insert(id, d);
+            }
+            continue;
+        }
+        if let Some(caps) = select_re.captures(trimmed) {
+            current_tool = caps[1].parse::<u32>().ok();
+            continue;
+        }
+        // 槽孔 (G85) 不是圆孔，不参与孔环检查
+        if !(trimmed.starts_with('X') || trimmed.starts_with('Y')) || trimmed.contains("G85") {
+            continue;
+        }
+        for caps in coord_re.captures_iter(trimmed) {
+            let value = format.parse(&caps[2]).unwrap_or(Length::ZERO);
+            if &caps[1] == "X" {
+                current.0 = value;
+            } else {
+                current.1 = value;
+            }
+        }
+        if let Some(d) = current_tool.and_then(|t| tools.get(&t)) {
+            hits.push((current, *d));
+        }
+    }
+
+    hits
+}
+
+/// 铜层中的所有操作点及其光圈宽度
+fn copper_features(content: &str) -> Vec<CopperFeature> {
+    let add_re = Regex::new(r"^%ADD(\d+)([CROP]),([\d.]+)(?:X([\d.]+))?").unwrap();
+    let select_re = Regex::new(r"^(?:G54)?D(\d+)$").unwrap();
+    let format = CoordinateFormat::gerber(content);
+
+    let mut sizes: HashMap<u32, Length> = HashMap::new();
+    // 每行结束时选中的光圈
+    let mut selected: Vec<Option<u32>> = Vec::new();
+    let mut current: Option<u32> = None;
+    for raw_line in content.split_inclusive('\n') {
+        let trimmed = split_eol(raw_line).0.trim();
+        if let Some(caps) = add_re.captures(trimmed) {
+            let first = caps[3].parse::<f64>().ok();
+            let second = caps.get(4).and_then(|m| m.as_str().parse::<f64>().ok());
+            let size = match (&caps[2], first, second) {
+                ("R" | "O", Some(w), Some(h)) => Some(w.min(h)),
+                (_, Some(d), _) => Some(d),
+                _ => None,
+            };
+            if let (Ok(id), Some(size)) = (caps[1].parse::<u32>(), size) {
+                sizes.insert(id, Length::from_unit(size, format.unit));
+            }
+        } else if !trimmed.starts_with('%') {
+            for word in trimmed.split('*') {
+                if let Some(caps) = select_re.captures(word) {
+                    current = caps[1].parse::<u32>().ok().filter(|id| *id >= 10);
+                }
+            }
+        }
+        selected.push(current);
+    }
+
+    parse_operations(content)
+        .iter()
+        .filter(|op| op.kind != OperationKind::Move)
+        .map(|op| {
+            let size = match selected.get(op.line).copied().flatten() {
+                Some(id) if !op.in_region => sizes.get(&id).copied().unwrap_or(Length::ZERO),
+                _ => Length::ZERO,
+            };
+            CopperFeature {
+                point: (format.from_raw(op.end.0), format.from_raw(op.end.1)),
+                kind: op.kind,
+                size,
+            }
+        })
+        .collect()
+}
+
+/// 检查本层与钻孔同心的焊盘，孔环宽度取焊盘半宽减去孔半径和偏心距离
+fn check_annular_ring(
+    file_name: &str,
+    features: &[CopperFeature],
+    hits: &[(BoardPoint, Length)],
+    profile: &CapabilityProfile,
+    issues: &mut Vec<ValidationIssue>,
+) {
+    let pads: Vec<&CopperFeature> = features
+        .iter()
+        .filter(|f| f.kind == OperationKind::Flash && f.size > Length::ZERO)
+        .collect();
+    let match_distance = Length::mm(PAD_MATCH_DISTANCE_MM);
+    let min_ring = Length::mm(profile.min_annular_ring_mm);
+
+    let mut worst: Option<(Length, BoardPoint)> = None;
+    for (hole, drill) in hits {
+        // 同一位置有多个焊盘时取最大的一个，没有焊盘的孔视为非金属化孔
+        let ring = pads
+            .iter()
+            .filter_map(|pad| {
+                let offset = (pad.point.0 - hole.0).hypot(pad.point.1 - hole.1);
+                (offset <= match_distance).then(|| (pad.size - *drill) * 0.5 - offset)
+            })
+            .reduce(Length::max);
+        if let Some(ring) = ring {
+            if worst.is_none_or(|(w, _)| ring < w) {
+                worst = Some((ring, *hole));
+            }
+        }
+    }
+
+    if let Some((ring, at)) = worst {
+        if ring + Length::mm(1e-6) < min_ring {
+            issues.push(ValidationIssue::new(
+                file_name,
+                Severity::Error,
+                "annular-ring",
+                format!(
+                    "({:.3}, {:.3})mm 处孔环 {:.3}mm 小于最小孔环 {:.3}mm",
+                    at.0.as_mm(),
+                    at.1.as_mm(),
+                    ring.as_mm(),
+                    min_ring.as_mm()
+                ),
+            ));
+        }
+    }
+}
+
+/// 检查铜层图形外缘到板框外接矩形的最小距离
+fn check_edge_clearance(
+    file_name: &str,
+    features: &[CopperFeature],
+    outline: Extent,
+    profile: &CapabilityProfile,
+    issues: &mut Vec<ValidationIssue>,
+) {
+    let min_clearance = Length::mm(profile.edge_clearance_mm);
+    let clearance = features
+        .iter()
+        .map(|f| {
+            let half = f.size * 0.5;
+            (f.point.0 - half - outline.min_x)
+                .min(f.point.1 - half - outline.min_y)
+                .min(outline.max_x - f.point.0 - half)
+                .min(outline.max_y - f.point.1 - half)
+        })
+        .reduce(Length::min);
+
+    if let Some(clearance) = clearance {
+        if clearance + Length::mm(1e-6) < min_clearance {
+            issues.push(ValidationIssue::new(
+                file_name,
+                Severity::Warning,
+                "edge-clearance",
+                format!(
+                    "铜到板边最近 {:.3}mm，小于 {} 的要求 {:.3}mm",
+                    clearance.as_mm(),
+                    profile.name,
+                    min_clearance.as_mm()
+                ),
+            ));
+        }
+    }
+}
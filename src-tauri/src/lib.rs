@@ -10,9 +10,20 @@ pub fn run() {
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_updater::Builder::new().build())
         .plugin(tauri_plugin_process::init())
+        .manage(gerber::progress::JobRegistry::default())
         .invoke_handler(tauri::generate_handler![
             commands::process_gerber,
+            commands::cancel_job,
+            commands::lint_gerber,
+            commands::revert_package,
             commands::get_default_options,
+            commands::list_profiles,
+            commands::list_presets,
+            commands::load_preset,
+            commands::save_preset,
+            commands::delete_preset,
+            commands::import_preset,
+            commands::export_preset,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -24,7 +24,7 @@ fn test_process_gerber_zip() {
         input_path: zip_path.to_string_lossy().to_string(),
         output_dir: Some(output_base.to_string_lossy().to_string()),
         count: 2,
-        options: ObfuscateOptions::default(),
+        ..Default::default()
     };
 
     let result = ghostpcb_lib::gerber::GerberProcessor::process(&request);
@@ -34,6 +34,7 @@ fn test_process_gerber_zip() {
     let result = result.unwrap();
     assert!(result.success);
     assert_eq!(result.output_files.len(), 2);
+    assert_eq!(result.obfuscators, vec!["跨层一致位移", "丝印层扰动"]);
 
     for file in &result.output_files {
         let path = Path::new(file);
@@ -63,9 +64,34 @@ fn test_process_gerber_zip() {
             "输出目录应以 GhostPCB_ 开头: {}",
             parent
         );
+        assert!(
+            path.with_extension("report.json").exists(),
+            "输出包旁应生成 JSON 报告: {}",
+            file
+        );
         println!("✅ 生成: {}", file);
     }
 
+    assert_eq!(result.reports.len(), 2, "每个输出包应有一份报告");
+    for report in &result.reports {
+        assert!(
+            !report.has_errors(),
+            "输出校验不应有错误: {:?}",
+            report.validation
+        );
+        let outline = report
+            .layers
+            .iter()
+            .find(|l| l.file.ends_with(".GKO"))
+            .expect("报告应包含板框层");
+        assert!(outline.coordinates_total > 0);
+        assert!(
+            outline.max_displacement_mm <= 0.0075,
+            "板框位移应在容差内: {}",
+            outline.max_displacement_mm
+        );
+    }
+
     println!("✅ 测试通过！消息: {}", result.message);
     println!("📁 输出基础目录: {}", output_base.display());
 
@@ -81,11 +107,30 @@ fn test_timestamp_obfuscator() {
     let input = "G04 EasyEDA Pro v3.2.58, 2026-01-05 14:09:15*\nG04 Test*";
     let obfuscator = TimestampObfuscator::new();
     let result = obfuscator
-        .obfuscate(input, GerberFileType::Unknown)
+        .obfuscate(input, GerberFileType::Unknown, &mut rand::thread_rng())
         .unwrap();
 
     assert!(!result.contains("2026-01-05 14:09:15"), "时间戳未被替换");
     assert!(result.contains("G04 EasyEDA Pro"), "其他内容不应改变");
+
+    // 新时间只取决于基准时间与随机数流
+    let now = chrono::NaiveDate::from_ymd_opt(2025, 3, 14)
+        .unwrap()
+        .and_hms_opt(12, 0, 0)
+        .unwrap();
+    let params = ghostpcb_lib::gerber::params::TimestampParams::default();
+    let stamp = |seed: u64| {
+        let mut rng = ghostpcb_lib::gerber::seed::Seed::new(seed).stream("时间戳修改");
+        TimestampObfuscator::with_params(&params, now, &mut rng)
+            .obfuscate(input, GerberFileType::Unknown, &mut rand::thread_rng())
+            .unwrap()
+    };
+    assert_eq!(stamp(5), stamp(5));
+    assert!(
+        stamp(5).contains("2025-0"),
+        "新时间应早于基准时间: {}",
+        stamp(5)
+    );
     println!("原始: {}", input);
     println!("混淆后: {}", result);
 }
@@ -105,7 +150,11 @@ G03X397Y1179I5J0D01*"#;
     let obfuscator = SilkscreenObfuscator::new();
 
     let result = obfuscator
-        .obfuscate(input, GerberFileType::TopSilkscreen)
+        .obfuscate(
+            input,
+            GerberFileType::TopSilkscreen,
+            &mut rand::thread_rng(),
+        )
         .unwrap();
     assert_ne!(input, result, "丝印层坐标应该被修改");
 
@@ -147,7 +196,7 @@ G03X397Y1179I5J0D01*"#;
     assert!(result.contains("I5J0"), "圆弧中心偏移量 I/J 不应被修改");
 
     let result2 = obfuscator
-        .obfuscate(input, GerberFileType::TopLayer)
+        .obfuscate(input, GerberFileType::TopLayer, &mut rand::thread_rng())
         .unwrap();
     assert_eq!(input.trim(), result2.trim(), "非丝印层不应被修改");
 }
@@ -159,7 +208,9 @@ fn test_geometry_obfuscator_drill() {
 
     let input = ";TYPE=PLATED\nT01\nX25.24994Y8.763\nX23.876Y9.906";
     let obfuscator = GeometryObfuscator::new();
-    let result = obfuscator.obfuscate(input, GerberFileType::Drill).unwrap();
+    let result = obfuscator
+        .obfuscate(input, GerberFileType::Drill, &mut rand::thread_rng())
+        .unwrap();
 
     assert!(!result.contains("X25.24994Y8.763"), "钻孔坐标应该被修改");
     assert!(result.contains(";TYPE=PLATED"), "头部注释不应改变");
@@ -175,7 +226,7 @@ fn test_structure_obfuscator() {
     let input = "G04 Layer: TopLayer*\nD10*\nX100Y200D01*";
     let obfuscator = StructureObfuscator::new();
     let result = obfuscator
-        .obfuscate(input, GerberFileType::TopLayer)
+        .obfuscate(input, GerberFileType::TopLayer, &mut rand::thread_rng())
         .unwrap();
 
     assert!(result.contains("Build ID:"), "应该添加随机 Build ID");
@@ -261,7 +312,7 @@ D15*
 X6000Y6000D03*
 M02*"#;
 
-    let output = apply_lceda_signature(input, false);
+    let output = apply_lceda_signature(input, false, &mut rand::thread_rng());
     assert!(
         verify_signature(&output),
         "签名校验失败，输出内容：\n{}",
@@ -274,7 +325,12 @@ fn test_disguise_for_non_easyeda() {
     use ghostpcb_lib::gerber::signature::disguise_as_easyeda;
 
     let input = "%FSLAX45Y45*%\n%MOMM*%\n%ADD10C,0.2*%\nM02*\n";
-    let output = disguise_as_easyeda(input, "Gerber_TopLayer.GTL");
+    let generated = chrono::NaiveDate::from_ymd_opt(2025, 3, 14)
+        .unwrap()
+        .and_hms_opt(9, 26, 53)
+        .unwrap();
+    let output = disguise_as_easyeda(input, "Gerber_TopLayer.GTL", generated);
+    assert!(output.contains("G04 EasyEDA Pro v3.2.91, 2025-03-14 09:26:53*"));
 
     assert!(
         output.contains("EasyEDA Pro v3.2.91"),
@@ -285,3 +341,1998 @@ fn test_disguise_for_non_easyeda() {
         "应注入 one-click 头注释"
     );
 }
+
+#[test]
+fn test_capability_profile_limits() {
+    use ghostpcb_lib::gerber::profile::CapabilityProfile;
+    use ghostpcb_lib::gerber::types::GerberFileType;
+    use ghostpcb_lib::gerber::validation::{validate_layer, Severity};
+
+    let dir = std::env::temp_dir().join("ghostpcb_profile_test");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("tiny.toml");
+    std::fs::write(
+        &path,
+        "max_board_width_mm = 10.0\nmax_board_height_mm = 10.0\n\n[tolerance]\ndrill_jitter_mm = 0.01\n",
+    )
+    .unwrap();
+
+    let profile = CapabilityProfile::resolve(Some(path.to_str().unwrap())).unwrap();
+    assert_eq!(profile.name, "tiny", "未命名配置应使用文件名");
+    assert_eq!(profile.max_board_width_mm, 10.0);
+    assert_eq!(profile.tolerance.drill_jitter_mm, 0.01);
+    assert_eq!(profile.min_drill_mm, 0.3, "未指定的字段应取默认值");
+
+    let outline =
+        "%FSLAX45Y45*%\n%MOMM*%\nG01X0Y0D02*\nG01X2000000Y0D01*\nG01X2000000Y1500000D01*\nM02*\n";
+    let issues = validate_layer("board.GKO", outline, GerberFileType::BoardOutline, &profile);
+    assert!(
+        issues
+            .iter()
+            .any(|i| i.code == "board-size" && i.severity == Severity::Error),
+        "20×15mm 的板子应超出 10×10mm 上限: {:?}",
+        issues
+    );
+
+    let mut loose = CapabilityProfile::standard();
+    loose.tolerance.drill_jitter_mm = 0.5;
+    assert!(loose.validate().is_err(), "钻孔位移大于焊环时应拒绝配置");
+    assert!(CapabilityProfile::resolve(Some("no-such-profile")).is_err());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_board_level_validation() {
+    use ghostpcb_lib::gerber::board::{Board, Layer};
+    use ghostpcb_lib::gerber::profile::CapabilityProfile;
+    use ghostpcb_lib::gerber::types::GerberFileType;
+    use ghostpcb_lib::gerber::validation::{validate_board, Severity};
+
+    let drill = "M48\nMETRIC,LZ,0000.00000\nT01C0.50000\n%\nT01\nX10.0Y10.0\nX30.0Y20.0\nM30\n";
+    let outline = "%FSLAX45Y45*%\n%MOMM*%\nG01X0Y0D02*\nX5000000Y0D01*\nX5000000Y4000000D01*\nX0Y4000000D01*\nX0Y0D01*\nM02*\n";
+    let board = |copper: &str| {
+        Board::new(vec![
+            Layer::new("a.GTL", GerberFileType::TopLayer, copper.to_string()),
+            Layer::new("a.DRL", GerberFileType::Drill, drill.to_string()),
+            Layer::new("a.GKO", GerberFileType::BoardOutline, outline.to_string()),
+        ])
+    };
+    let profile = CapabilityProfile::standard();
+
+    let good = "%FSLAX45Y45*%\n%MOMM*%\n%ADD10C,1.0*%\n%ADD11C,0.2*%\nG54D10*\nX1000000Y1000000D03*\nX3000000Y2000000D03*\nG54D11*\nX1000000Y1000000D02*\nX3000000Y2000000D01*\nM02*\n";
+    let issues = validate_board(&board(good), &profile);
+    assert!(issues.is_empty(), "合格的板子不应报告问题: {:?}", issues);
+
+    // 0.7mm 焊盘配 0.5mm 孔只剩 0.1mm 孔环；走线距右侧板边 0.05mm
+    let tight = "%FSLAX45Y45*%\n%MOMM*%\n%ADD10C,1.0*%\n%ADD11R,0.7X0.9*%\n%ADD12C,0.2*%\nG54D10*\nX1000000Y1000000D03*\nG54D11*\nX3000000Y2000000D03*\nG54D12*\nX3000000Y2000000D02*\nX4985000Y2000000D01*\nM02*\n";
+    let issues = validate_board(&board(tight), &profile);
+    let ring = issues
+        .iter()
+        .find(|i| i.code == "annular-ring")
+        .expect("孔环不足应报告");
+    assert_eq!(ring.severity, Severity::Error);
+    assert_eq!(ring.file, "a.GTL");
+    assert!(ring.message.contains("0.100mm"), "{}", ring.message);
+    let edge = issues
+        .iter()
+        .find(|i| i.code == "edge-clearance")
+        .expect("铜离板边过近应报告");
+    assert!(edge.message.contains("0.050mm"), "{}", edge.message);
+}
+
+#[test]
+fn test_gerber_linter() {
+    use ghostpcb_lib::gerber::lint::lint_gerber;
+    use ghostpcb_lib::gerber::GerberProcessor;
+
+    let zip_path = Path::new(TEST_GERBER_ZIP);
+    if zip_path.exists() {
+        let diagnostics = GerberProcessor::lint(zip_path).unwrap();
+        let errors: Vec<_> = diagnostics.iter().filter(|d| d.is_error()).collect();
+        assert!(errors.is_empty(), "示例文件不应有语法错误: {:?}", errors);
+    }
+
+    let input = "%FSLAX45Y45*%
+%ADD10R,0.5X0.5*%
+G54D10*
+G03X100Y100I50J0D01*
+D11*
+G36*
+G01X0Y0D02*
+G01X100Y0D01*
+G01X100Y100D01*
+G37*
+";
+    let diagnostics = lint_gerber("broken.GTL", input);
+    let codes: Vec<&str> = diagnostics.iter().map(|d| d.code.as_str()).collect();
+    for expected in [
+        "missing-mo",
+        "missing-m02",
+        "undefined-aperture",
+        "arc-non-circular",
+        "open-contour",
+        "deprecated-command",
+    ] {
+        assert!(
+            codes.contains(&expected),
+            "应报告 {}: {:?}",
+            expected,
+            codes
+        );
+    }
+    assert!(!codes.contains(&"missing-fs"));
+
+    let undefined = diagnostics
+        .iter()
+        .find(|d| d.code == "undefined-aperture")
+        .unwrap();
+    assert_eq!(undefined.line, 5, "诊断应指向 D11* 所在行");
+}
+
+#[test]
+fn test_displacement_report() {
+    use ghostpcb_lib::gerber::report::{measure_displacement, LayerReport, PackageReport};
+    use ghostpcb_lib::gerber::types::GerberFileType;
+
+    let before = "%FSLAX45Y45*%\n%MOMM*%\nG01X100000Y100000D02*\nG01X200000Y100000D01*\nG01Y300000D01*\nM02*\n";
+    let after = "%FSLAX45Y45*%\n%MOMM*%\nG01X100300Y100400D02*\nG01X200000Y100000D01*\nG01Y300000D01*\nM02*\n";
+
+    let d = measure_displacement(before, after, GerberFileType::TopLayer).unwrap();
+    assert_eq!(d.total, 3);
+    assert_eq!(d.changed, 1);
+    assert!(
+        (d.max_mm - 0.005).abs() < 1e-9,
+        "位移应为 0.005mm: {}",
+        d.max_mm
+    );
+
+    let report = PackageReport {
+        output_file: "Gerber_PCB1.zip".to_string(),
+        profile: "standard".to_string(),
+        layers: vec![LayerReport {
+            file: "<Top>.GTL".to_string(),
+            file_type: GerberFileType::TopLayer,
+            transforms: vec!["全层坐标抖动".to_string()],
+            coordinates_total: d.total,
+            coordinates_changed: d.changed,
+            max_displacement_mm: d.max_mm,
+        }],
+        ..Default::default()
+    };
+    let html = report.to_html();
+    assert!(html.contains("&lt;Top&gt;.GTL"), "HTML 报告应转义文件名");
+    assert!(html.contains("全层坐标抖动"));
+}
+
+#[test]
+fn test_arc_offsets_follow_jitter() {
+    use ghostpcb_lib::gerber::transform::{parse_operations, transform_operations};
+
+    let content = "%FSLAX45Y45*%\n%MOMM*%\nG75*\nG01X1850000Y5700000D02*\nG03X1550000Y6000000I-300000J0D01*\nG02X1550000Y6000000I0J-200000D01*\nG74*\nG01X1000000Y1000000D02*\nG03X900000Y1100000I100000J0D01*\nM02*\n";
+
+    // 每个点各自偏移不同的量，模拟逐坐标抖动
+    let mut n = 0;
+    let result = transform_operations(content, |op, _| {
+        n += 1;
+        (op.end.0 + n * 37, op.end.1 - n * 23)
+    });
+
+    let ops = parse_operations(&result);
+    for op in ops.iter().filter(|op| op.is_arc()) {
+        let center = op.center().unwrap();
+        let r0 = ((op.start.0 as f64 - center.0).powi(2) + (op.start.1 as f64 - center.1).powi(2))
+            .sqrt();
+        let r1 =
+            ((op.end.0 as f64 - center.0).powi(2) + (op.end.1 as f64 - center.1).powi(2)).sqrt();
+        assert!(
+            (r0 - r1).abs() <= 1.5,
+            "第 {} 行圆弧首尾半径不一致: {} vs {}",
+            op.line + 1,
+            r0,
+            r1
+        );
+    }
+
+    let full_circle = ops.iter().find(|op| op.line == 5).unwrap();
+    assert!(full_circle.is_full_circle(), "整圆变换后应保持闭合");
+
+    // 单象限模式下 I/J 保持无符号
+    let single = ops.iter().find(|op| op.line == 8).unwrap();
+    assert!(single.offset.0 >= 0 && single.offset.1 >= 0);
+}
+
+#[test]
+fn test_region_contours_stay_closed() {
+    use ghostpcb_lib::gerber::transform::{parse_operations, transform_operations, OperationKind};
+
+    let content = "%FSLAX45Y45*%\n%MOMM*%\nG75*\nG36*\nG01X0Y0D02*\nX1000Y0D01*\nX1000Y1000D01*\nX0Y1000D01*\nX0Y0D01*\nG37*\nM02*\n";
+
+    // 逐点不同的位移，闭合点会被拉回起点
+    let mut n = 0;
+    let result = transform_operations(content, |op, _| {
+        n += 1;
+        (op.end.0 + n * 3, op.end.1 - n * 2)
+    });
+    let ops = parse_operations(&result);
+    let start = ops
+        .iter()
+        .find(|op| op.kind == OperationKind::Move)
+        .unwrap();
+    assert_eq!(start.end, (3, -2));
+    assert_eq!(ops.last().unwrap().end, start.end, "轮廓应保持闭合");
+    assert_eq!(ops[1].end, (1006, -4));
+
+    // 位移会让轮廓自相交时退回整体平移
+    let result = transform_operations(content, |op, _| {
+        if op.end == (1000, 0) {
+            (-500, 500)
+        } else {
+            (op.end.0 + 7, op.end.1 + 7)
+        }
+    });
+    let ops = parse_operations(&result);
+    let points: Vec<_> = ops.iter().map(|op| op.end).collect();
+    assert_eq!(
+        points,
+        vec![(7, 7), (1007, 7), (1007, 1007), (7, 1007), (7, 7)]
+    );
+}
+
+#[test]
+fn test_board_field_keeps_layers_registered() {
+    use ghostpcb_lib::gerber::board::{Board, DisplacementField, Layer};
+    use ghostpcb_lib::gerber::types::GerberFileType;
+    use ghostpcb_lib::gerber::units::Length;
+
+    let copper = "%FSLAX45Y45*%\n%MOMM*%\n%ADD10C,1.0*%\nG54D10*\nX1000000Y1000000D03*\nX3000000Y2000000D03*\nM02*\n";
+    let mask = "%FSLAX45Y45*%\n%MOMM*%\n%ADD10C,1.1*%\nG54D10*\nX1000000Y1000000D03*\nX3000000Y2000000D03*\nM02*\n";
+    let drill = "M48\nMETRIC,LZ,0000.00000\nT01C0.50000\n%\nT01\nX10.0Y10.0\nX30.0Y20.0\nM30\n";
+    let outline = "%FSLAX45Y45*%\n%MOMM*%\nG01X0Y0D02*\nX5000000Y0D01*\nX5000000Y4000000D01*\nX0Y4000000D01*\nX0Y0D01*\nM02*\n";
+
+    let mut board = Board::new(vec![
+        Layer::new("a.GTL", GerberFileType::TopLayer, copper.to_string()),
+        Layer::new("a.GTS", GerberFileType::TopSolderMask, mask.to_string()),
+        Layer::new("a.DRL", GerberFileType::Drill, drill.to_string()),
+        Layer::new("a.GKO", GerberFileType::BoardOutline, outline.to_string()),
+    ]);
+    let extent = board.extent().unwrap();
+    assert!((extent.width().as_mm() - 50.0).abs() < 1e-9);
+
+    let amplitude = Length::mm(0.003);
+    let field = DisplacementField::random(
+        Some(extent),
+        amplitude,
+        Length::mm(10.0),
+        &mut rand::thread_rng(),
+    );
+    board.apply_field(&field);
+
+    let copper_points = board.layers[0].points();
+    let mask_points = board.layers[1].points();
+    let drill_points = board.layers[2].points();
+    let source = [(10.0, 10.0), (30.0, 20.0)];
+
+    for (k, original) in source.iter().enumerate() {
+        for points in [&copper_points, &mask_points, &drill_points] {
+            assert!(
+                (points[k].0 - copper_points[k].0).abs() < Length::mm(1e-5)
+                    && (points[k].1 - copper_points[k].1).abs() < Length::mm(1e-5),
+                "焊盘、开窗和孔应移动相同的矢量"
+            );
+        }
+        let moved = (copper_points[k].0 - Length::mm(original.0))
+            .hypot(copper_points[k].1 - Length::mm(original.1));
+        assert!(
+            moved <= amplitude + Length::mm(1e-5),
+            "位移超出幅值: {:?}",
+            moved
+        );
+    }
+
+    // 板框同样按位移场移动且保持闭合
+    let outline_points = board.layers[3].points();
+    assert_eq!(outline_points.first(), outline_points.last());
+
+    // 几何扰动同样按整板位移场移动，铜层、开窗和孔保持对齐，丝印与板框不动
+    let geometry_only = ObfuscateOptions {
+        displacement: false,
+        timestamp: false,
+        silkscreen: false,
+        geometry: true,
+        structure: false,
+        physical: false,
+        ..Default::default()
+    };
+    let mut board = Board::new(vec![
+        Layer::new("a.GTL", GerberFileType::TopLayer, copper.to_string()),
+        Layer::new("a.GTS", GerberFileType::TopSolderMask, mask.to_string()),
+        Layer::new("a.DRL", GerberFileType::Drill, drill.to_string()),
+        Layer::new("a.GKO", GerberFileType::BoardOutline, outline.to_string()),
+    ]);
+    let pipeline = ghostpcb_lib::gerber::pipeline::ObfuscationPipeline::with_seed(
+        &geometry_only,
+        &ghostpcb_lib::gerber::CapabilityProfile::default(),
+        ghostpcb_lib::gerber::seed::Seed::new(31),
+    );
+    pipeline.process_board(&mut board).unwrap();
+    let copper_points = board.layers[0].points();
+    assert_ne!(copper_points[0], (Length::mm(10.0), Length::mm(10.0)));
+    for points in [board.layers[1].points(), board.layers[2].points()] {
+        for (p, c) in points.iter().zip(&copper_points) {
+            assert!(
+                (p.0 - c.0).abs() < Length::mm(1e-5) && (p.1 - c.1).abs() < Length::mm(1e-5),
+                "几何扰动后焊盘、开窗和孔应保持对齐"
+            );
+        }
+    }
+    assert_eq!(board.layers[3].content, outline);
+}
+
+#[test]
+fn test_length_units_and_formats() {
+    use ghostpcb_lib::gerber::obfuscators::{GeometryObfuscator, Obfuscator};
+    use ghostpcb_lib::gerber::report::measure_displacement;
+    use ghostpcb_lib::gerber::types::GerberFileType;
+    use ghostpcb_lib::gerber::units::{CoordinateFormat, Length};
+    use ghostpcb_lib::gerber::validation::validate_layer;
+    use ghostpcb_lib::gerber::CapabilityProfile;
+
+    assert!((Length::inch(1.0).as_mm() - 25.4).abs() < 1e-12);
+    assert!((Length::mil(1000.0).as_mm() - Length::inch(1.0).as_mm()).abs() < 1e-12);
+
+    let inch = CoordinateFormat::gerber("%FSLAX26Y26*%\n%MOIN*%\n");
+    assert_eq!(inch.to_raw(Length::mm(25.4)), 1_000_000);
+    let mm = CoordinateFormat::gerber("%FSLAX46Y46*%\n%MOMM*%\n");
+    assert_eq!(mm.to_raw(Length::mm(25.4)), 25_400_000);
+
+    // Excellon 省略小数点的两种补零方式
+    let tz = CoordinateFormat::excellon("M48\nINCH,TZ\n");
+    assert!((tz.parse("15000").unwrap().as_inch() - 1.5).abs() < 1e-12);
+    let lz = CoordinateFormat::excellon("M48\nMETRIC,LZ,000.000\n");
+    assert!((lz.parse("012345").unwrap().as_mm() - 12.345).abs() < 1e-12);
+    assert_eq!(lz.format(Length::mm(1.5), "012345"), "001500");
+
+    // 同样尺寸的板子，英制与公制文件校验结果一致
+    let profile = CapabilityProfile::standard();
+    let outline_in =
+        "%FSLAX26Y26*%\n%MOIN*%\nG01X0Y0D02*\nX5000000Y0D01*\nX5000000Y5000000D01*\nM02*\n";
+    let outline_mm =
+        "%FSLAX46Y46*%\n%MOMM*%\nG01X0Y0D02*\nX127000000Y0D01*\nX127000000Y127000000D01*\nM02*\n";
+    let issues_in = validate_layer("a.GKO", outline_in, GerberFileType::BoardOutline, &profile);
+    let issues_mm = validate_layer("b.GKO", outline_mm, GerberFileType::BoardOutline, &profile);
+    assert_eq!(issues_in.len(), 1);
+    assert_eq!(issues_in[0].message, issues_mm[0].message);
+
+    // 英制钻孔文件的偏移同样以毫米为上限
+    let drill = "M48\nINCH,LZ,00.0000\nT01C0.0120\n%\nT01\nX1.0000Y1.0000\nX2.0000Y1.5000\nM30\n";
+    let jittered = GeometryObfuscator::with_profile(&profile)
+        .obfuscate(drill, GerberFileType::Drill, &mut rand::thread_rng())
+        .unwrap();
+    let d = measure_displacement(drill, &jittered, GerberFileType::Drill).unwrap();
+    assert_eq!(d.total, 2);
+    assert!(
+        d.max_mm <= profile.tolerance.drill_jitter_mm * std::f64::consts::SQRT_2 + 0.003,
+        "英制钻孔偏移过大: {}",
+        d.max_mm
+    );
+
+    // Gerber 省略末尾零 (FST)：按总位数在右侧补零，写回时同样省略末尾零
+    let fst = "%FSTAX24Y24*%\n%MOIN*%\n%ADD10C,0.01*%\nD10*\nX015Y0025D02*\nX02Y0025D01*\nM02*\n";
+    let format = CoordinateFormat::gerber(fst);
+    assert_eq!(format.parse_raw("015"), Some(15_000));
+    assert_eq!(format.parse_raw("-0025"), Some(-2_500));
+    assert_eq!(format.format_raw(15_000, Some("015")), "015");
+    assert_eq!(format.format_raw(0, Some("0")), "0");
+    let mut layer = ghostpcb_lib::gerber::board::Layer::new(
+        "fst.GTL",
+        GerberFileType::TopLayer,
+        fst.to_string(),
+    );
+    let points = layer.points();
+    assert!((points[0].0.as_inch() - 1.5).abs() < 1e-9);
+    assert!((points[0].1.as_inch() - 0.25).abs() < 1e-9);
+    assert!((points[1].0.as_inch() - 2.0).abs() < 1e-9);
+    layer.transform(|p| (p.0 + Length::inch(0.1), p.1));
+    assert!(
+        layer.content.contains("X016Y0025D02*") && layer.content.contains("X021Y0025D01*"),
+        "FST 坐标应按原格式写回:\n{}",
+        layer.content
+    );
+}
+
+#[test]
+fn test_pipeline_honours_options() {
+    use ghostpcb_lib::gerber::pipeline::ObfuscationPipeline;
+    use ghostpcb_lib::gerber::types::GerberFileType;
+    use ghostpcb_lib::gerber::CapabilityProfile;
+
+    let profile = CapabilityProfile::default();
+    let none = ObfuscateOptions {
+        timestamp: false,
+        silkscreen: false,
+        geometry: false,
+        structure: false,
+        physical: false,
+        ..Default::default()
+    };
+    let all = ObfuscateOptions {
+        timestamp: true,
+        silkscreen: true,
+        geometry: true,
+        structure: true,
+        physical: true,
+        ..Default::default()
+    };
+
+    let pipeline = ObfuscationPipeline::from_options(&none, &profile);
+    assert_eq!(pipeline.obfuscator_names(), vec!["跨层一致位移"]);
+
+    // 关闭丝印选项后，丝印层只受全板位移影响，不再整体平移
+    let silk = "%FSLAX45Y45*%\n%MOMM*%\nG04 2024-01-01 10:00:00*\nG01X100000Y100000D02*\nX200000Y100000D01*\nM02*\n";
+    let (_, applied) = pipeline
+        .process_traced(silk, GerberFileType::TopSilkscreen)
+        .unwrap();
+    assert!(!applied.contains(&"丝印层扰动"));
+
+    let pipeline = ObfuscationPipeline::from_options(&all, &profile);
+    assert_eq!(
+        pipeline.obfuscator_names(),
+        vec![
+            "跨层一致位移",
+            "物理参数微调",
+            "几何结构扰动",
+            "丝印层扰动",
+            "文件结构混淆",
+            "时间戳修改"
+        ]
+    );
+    let (result, applied) = pipeline
+        .process_traced(silk, GerberFileType::TopSilkscreen)
+        .unwrap();
+    assert!(applied.contains(&"丝印层扰动"));
+    assert!(applied.contains(&"时间戳修改"));
+    assert!(result.contains("Build ID"), "结构混淆应生效");
+    assert!(!result.contains("2024-01-01 10:00:00"), "时间戳应被替换");
+}
+
+#[test]
+fn test_stage_params() {
+    use ghostpcb_lib::gerber::obfuscators::{
+        Obfuscator, SilkscreenObfuscator, StructureObfuscator,
+    };
+    use ghostpcb_lib::gerber::params::{SilkscreenParams, StageParams, StructureParams};
+    use ghostpcb_lib::gerber::types::GerberFileType;
+    use ghostpcb_lib::gerber::CapabilityProfile;
+
+    let profile = CapabilityProfile::standard();
+    assert!(StageParams::default().validate(&profile).is_ok());
+
+    // 缺省字段取默认值，JSON 只需写要调整的部分
+    let params: StageParams =
+        serde_json::from_str(r#"{"silkscreen":{"shift_min_mm":0.02,"shift_max_mm":0.02}}"#)
+            .unwrap();
+    assert_eq!(params.structure, StructureParams::default());
+    assert!(params.validate(&profile).is_ok());
+
+    let silk = "%FSLAX45Y45*%\n%MOMM*%\nG01X100000Y100000D02*\nM02*\n";
+    let shifted = SilkscreenObfuscator::with_params(&params.silkscreen)
+        .obfuscate(silk, GerberFileType::TopSilkscreen, &mut rand::thread_rng())
+        .unwrap();
+    assert!(shifted.contains("X102000Y102000D02*"), "{}", shifted);
+
+    // 把丝印平移扩展到阻焊层
+    let custom = SilkscreenParams {
+        layers: vec![GerberFileType::TopSolderMask],
+        ..params.silkscreen.clone()
+    };
+    let obfuscator = SilkscreenObfuscator::with_params(&custom);
+    assert_eq!(
+        obfuscator
+            .obfuscate(silk, GerberFileType::TopSilkscreen, &mut rand::thread_rng())
+            .unwrap(),
+        silk
+    );
+    assert_ne!(
+        obfuscator
+            .obfuscate(silk, GerberFileType::TopSolderMask, &mut rand::thread_rng())
+            .unwrap(),
+        silk
+    );
+
+    let quiet = StructureObfuscator::with_params(&StructureParams {
+        build_id_comment: false,
+        redundant_dcode_probability: 0.0,
+        ..Default::default()
+    });
+    assert_eq!(
+        quiet
+            .obfuscate(silk, GerberFileType::TopLayer, &mut rand::thread_rng())
+            .unwrap(),
+        silk
+    );
+
+    // 超出工艺能力或自相矛盾的参数被拒绝
+    let mut bad = StageParams::default();
+    bad.geometry.drill_jitter_mm = Some(0.2);
+    assert!(bad.validate(&profile).is_err());
+    let mut bad = StageParams::default();
+    bad.silkscreen.shift_min_mm = 0.05;
+    assert!(bad.validate(&profile).is_err());
+    let mut bad = StageParams::default();
+    bad.structure.redundant_dcode_probability = 1.5;
+    assert!(bad.validate(&profile).is_err());
+}
+
+#[test]
+fn test_layer_rules() {
+    use ghostpcb_lib::gerber::board::{Board, Layer};
+    use ghostpcb_lib::gerber::pipeline::ObfuscationPipeline;
+    use ghostpcb_lib::gerber::rules::{LayerClass, LayerRule};
+    use ghostpcb_lib::gerber::seed::Seed;
+    use ghostpcb_lib::gerber::types::GerberFileType;
+    use ghostpcb_lib::gerber::CapabilityProfile;
+
+    assert_eq!(
+        GerberFileType::from_extension("gdl"),
+        GerberFileType::Document
+    );
+    assert!(LayerClass::Copper.contains(GerberFileType::InnerLayer));
+    assert!(!LayerClass::OuterCopper.contains(GerberFileType::InnerLayer));
+
+    // 内层完全不动，板框使用更小的位移上限
+    let options: ObfuscateOptions = serde_json::from_str(
+        r#"{
+            "timestamp": false, "silkscreen": true, "geometry": false,
+            "structure": false, "physical": false,
+            "params": {"displacement": {"min_wavelength_mm": 5.0},
+                       "silkscreen": {"shift_min_mm": 0.02, "shift_max_mm": 0.04}},
+            "rules": [
+                {"layers": ["InnerCopper"],
+                 "stages": {"displacement": false, "silkscreen": false}},
+                {"files": ["*.gko"],
+                 "params": {"displacement": {"amplitude_mm": 0.0}}}
+            ]
+        }"#,
+    )
+    .unwrap();
+    let profile = CapabilityProfile::default();
+    assert!(options.validate(&profile).is_ok());
+
+    let inner = options.for_layer("Gerber/Gerber_InnerLayer1.G1", GerberFileType::InnerLayer);
+    assert!(!inner.displacement && !inner.silkscreen);
+    assert!(inner.rules.is_empty());
+    let outline = options.for_layer("Gerber_BoardOutlineLayer.GKO", GerberFileType::BoardOutline);
+    assert!(outline.displacement);
+    assert_eq!(outline.params.displacement.amplitude_mm, Some(0.0));
+    // 规则只覆盖给出的字段，其余沿用全局参数
+    assert_eq!(outline.params.displacement.min_wavelength_mm, 5.0);
+    assert_eq!(outline.params.silkscreen.shift_max_mm, 0.04);
+
+    let gerber = "%FSLAX45Y45*%\n%MOMM*%\nG01X100000Y100000D02*\nX2000000Y100000D01*\nX2000000Y1500000D01*\nM02*\n";
+    let mut board = Board::new(vec![
+        Layer::new("Top.GTL", GerberFileType::TopLayer, gerber.to_string()),
+        Layer::new("Inner.G1", GerberFileType::InnerLayer, gerber.to_string()),
+        Layer::new(
+            "Outline.GKO",
+            GerberFileType::BoardOutline,
+            gerber.to_string(),
+        ),
+    ]);
+    let layer_options: Vec<ObfuscateOptions> = board
+        .layers
+        .iter()
+        .map(|l| options.for_layer(&l.name, l.file_type))
+        .collect();
+    let applied = ObfuscationPipeline::process_board_by_layer(
+        &mut board,
+        &layer_options,
+        &profile,
+        Seed::new(7),
+        chrono::Local::now().naive_local(),
+    )
+    .unwrap();
+
+    assert_ne!(board.layers[0].content, gerber);
+    assert_eq!(applied[0], vec!["跨层一致位移"]);
+    assert_eq!(board.layers[1].content, gerber, "内层不应被修改");
+    assert!(applied[1].is_empty());
+    assert_eq!(board.layers[2].content, gerber, "板框位移上限为 0");
+
+    // 通配符语法错误在处理前被拒绝
+    let bad = ObfuscateOptions {
+        rules: vec![LayerRule {
+            files: vec!["[".to_string()],
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    assert!(bad.validate(&profile).is_err());
+
+    // 两条规则各自合法，叠加到同一文件后天数范围颠倒
+    let stacked: ObfuscateOptions = serde_json::from_str(
+        r#"{
+            "timestamp": true, "silkscreen": false, "geometry": false,
+            "structure": false, "physical": false,
+            "rules": [
+                {"layers": ["Copper"], "params": {"timestamp": {"days_ago_min": 20}}},
+                {"files": ["*.gtl"], "params": {"timestamp": {"days_ago_max": 10}}}
+            ]
+        }"#,
+    )
+    .unwrap();
+    assert!(stacked.validate(&profile).is_ok());
+    assert!(stacked
+        .validate_layer("Inner.G1", GerberFileType::InnerLayer, &profile)
+        .is_ok());
+    let err = stacked
+        .validate_layer("Top.GTL", GerberFileType::TopLayer, &profile)
+        .unwrap_err();
+    assert!(err.to_string().contains("Top.GTL"), "{}", err);
+
+    // 即使绕过校验，颠倒的范围也不会在采样时 panic
+    let params = stacked
+        .for_layer("Top.GTL", GerberFileType::TopLayer)
+        .params
+        .timestamp;
+    let now = chrono::NaiveDate::from_ymd_opt(2025, 3, 14)
+        .unwrap()
+        .and_hms_opt(9, 0, 0)
+        .unwrap();
+    ghostpcb_lib::gerber::obfuscators::TimestampObfuscator::with_params(
+        &params,
+        now,
+        &mut Seed::new(1).stream("时间戳"),
+    );
+}
+
+#[test]
+fn test_presets() {
+    use ghostpcb_lib::gerber::presets::{Preset, PresetStore, PRESET_SCHEMA_VERSION};
+    use ghostpcb_lib::gerber::rules::{LayerClass, LayerRule, StageToggles};
+
+    let dir = tempfile::TempDir::new().unwrap();
+    let store = PresetStore::new(dir.path().join("presets"));
+    assert!(store.list().unwrap().is_empty());
+
+    let mut options = ObfuscateOptions {
+        geometry: true,
+        rules: vec![LayerRule {
+            layers: vec![LayerClass::InnerCopper],
+            stages: StageToggles {
+                displacement: Some(false),
+                ..Default::default()
+            },
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    options.params.silkscreen.shift_max_mm = 0.02;
+    let preset = Preset::new("四层板", options);
+    store.save(&preset).unwrap();
+    assert_eq!(store.load("四层板").unwrap(), preset);
+    // 原子写入不留下临时文件
+    let files: Vec<_> = std::fs::read_dir(dir.path().join("presets"))
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    assert_eq!(files, vec!["四层板.json"]);
+
+    // TOML 与 JSON 导出后可以原样导入
+    for ext in ["toml", "json"] {
+        let path = dir.path().join(format!("exported.{}", ext));
+        store.export("四层板", &path).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        let renamed = text.replace("四层板", &format!("导入-{}", ext));
+        std::fs::write(&path, renamed).unwrap();
+        let imported = store.import(&path).unwrap();
+        assert_eq!(imported.options, preset.options);
+    }
+    let names: Vec<String> = store.list().unwrap().into_iter().map(|p| p.name).collect();
+    assert_eq!(names, vec!["四层板", "导入-json", "导入-toml"]);
+
+    // 旧版本只导出了选项本身，缺少的字段取默认值
+    let legacy = dir.path().join("legacy.json");
+    std::fs::write(&legacy, r#"{"timestamp": true, "silkscreen": false}"#).unwrap();
+    let migrated = store.import(&legacy).unwrap();
+    assert_eq!(migrated.name, "legacy");
+    assert_eq!(migrated.schema_version, PRESET_SCHEMA_VERSION);
+    assert!(migrated.options.timestamp && !migrated.options.silkscreen);
+    assert!(migrated.options.displacement && !migrated.options.physical);
+
+    let newer = dir.path().join("newer.json");
+    std::fs::write(
+        &newer,
+        r#"{"schema_version": 99, "name": "x", "options": {}}"#,
+    )
+    .unwrap();
+    assert!(store.import(&newer).is_err());
+
+    // 非法名称与越界参数都不会写入
+    assert!(store
+        .save(&Preset::new("../evil", ObfuscateOptions::default()))
+        .is_err());
+    let mut bad = ObfuscateOptions::default();
+    bad.params.structure.redundant_dcode_probability = 2.0;
+    assert!(store.save(&Preset::new("bad", bad)).is_err());
+
+    store.delete("legacy").unwrap();
+    assert!(store.load("legacy").is_err());
+    assert!(store.delete("legacy").is_err());
+}
+
+#[test]
+fn test_seeded_runs_are_reproducible() {
+    use std::io::Read;
+
+    let zip_path = Path::new(TEST_GERBER_ZIP);
+    if !zip_path.exists() {
+        return;
+    }
+
+    // 同一种子两次运行，输出包内每个文件的内容完全一致
+    let read_entries = |file: &str| -> Vec<(String, Vec<u8>)> {
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(file).unwrap()).unwrap();
+        (0..archive.len())
+            .map(|i| {
+                let mut entry = archive.by_index(i).unwrap();
+                let mut data = Vec::new();
+                entry.read_to_end(&mut data).unwrap();
+                (entry.name().to_string(), data)
+            })
+            .collect()
+    };
+    let run = |dir: &Path, reference_time| {
+        let request = ProcessRequest {
+            input_path: zip_path.to_string_lossy().to_string(),
+            output_dir: Some(dir.to_string_lossy().to_string()),
+            count: 2,
+            options: ObfuscateOptions {
+                timestamp: true,
+                geometry: true,
+                structure: true,
+                ..Default::default()
+            },
+            profile: None,
+            html_report: false,
+            seed: Some(42),
+            dry_run: false,
+            threads: None,
+            job_id: None,
+            archive_limits: Default::default(),
+            rollback_on_error: true,
+            strict: false,
+            output_format: None,
+            repack: Default::default(),
+            reference_time,
+        };
+        ghostpcb_lib::gerber::GerberProcessor::process(&request).unwrap()
+    };
+
+    // 第二次运行晚一些，传入第一次报告中记录的基准时间
+    let first_dir = tempfile::TempDir::new().unwrap();
+    let second_dir = tempfile::TempDir::new().unwrap();
+    let first = run(first_dir.path(), None);
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    let second = run(second_dir.path(), Some(first.reports[0].reference_time));
+
+    assert_eq!(first.seed, 42);
+    assert_eq!(first.reports[1].seed, 42);
+    assert_eq!(first.reports[1].variant, 2);
+    for (a, b) in first.output_files.iter().zip(&second.output_files) {
+        assert_eq!(
+            Path::new(a).file_name(),
+            Path::new(b).file_name(),
+            "输出文件名应一致"
+        );
+        assert_eq!(read_entries(a), read_entries(b));
+        assert_eq!(
+            std::fs::read(a).unwrap(),
+            std::fs::read(b).unwrap(),
+            "输出包应逐字节一致"
+        );
+    }
+
+    // 同一次运行中的不同包互不相同
+    assert_ne!(
+        read_entries(&first.output_files[0]),
+        read_entries(&first.output_files[1])
+    );
+}
+
+#[test]
+fn test_board_obfuscator_edits() {
+    use ghostpcb_lib::gerber::board::{Board, Layer};
+    use ghostpcb_lib::gerber::edit::{apply_edits, diff_lines, Edit, EditAction};
+    use ghostpcb_lib::gerber::error::Result;
+    use ghostpcb_lib::gerber::obfuscators::{
+        BoardContext, BoardObfuscator, Obfuscator, StructureObfuscator, TextObfuscatorAdapter,
+    };
+    use ghostpcb_lib::gerber::pipeline::ObfuscationPipeline;
+    use ghostpcb_lib::gerber::seed::Seed;
+    use ghostpcb_lib::gerber::types::GerberFileType;
+    use ghostpcb_lib::gerber::CapabilityProfile;
+
+    // 逐行修改可以精确重建 CRLF 与无末尾换行的文本
+    let before = "G04 a*\r\nD10*\r\nX1Y1D03*\r\nM02*";
+    let after = "G04 a*\r\nG04 Build ID: 1*\r\nD10*\r\nX2Y1D03*\r\nM02*";
+    let edits = diff_lines(before, after);
+    assert_eq!(apply_edits(before, &edits), after);
+    assert!(edits.contains(&Edit::insert(1, "G04 Build ID: 1*\r\n")));
+    assert!(edits
+        .iter()
+        .any(|e| matches!(&e.action, EditAction::Replace { old, .. } if old == "X1Y1D03*\r\n")));
+    assert!(diff_lines(before, before).is_empty());
+
+    // 适配后的文本级混淆器与直接调用结果一致
+    let silk =
+        "%FSLAX45Y45*%\n%MOMM*%\nG04 header*\nD10*\nX100000Y100000D02*\nX200000Y100000D01*\nM02*\n";
+    let layer = Layer::new("Silk.GTO", GerberFileType::TopSilkscreen, silk.to_string());
+    let board = Board::new(vec![layer.clone()]);
+    let profile = CapabilityProfile::default();
+    let structure = StructureObfuscator::new();
+    let direct = structure
+        .obfuscate(
+            silk,
+            GerberFileType::TopSilkscreen,
+            &mut Seed::new(1).stream("s"),
+        )
+        .unwrap();
+    let mut rng = Seed::new(1).stream("s");
+    let mut context = BoardContext {
+        board: &board,
+        profile: &profile,
+        outline: board.extent(),
+        rng: &mut rng,
+    };
+    let edits = TextObfuscatorAdapter::new(StructureObfuscator::new())
+        .edit(&layer, &mut context)
+        .unwrap();
+    assert_eq!(apply_edits(silk, &edits), direct);
+
+    // 可以读取其他层的第二代混淆器：在丝印层记录板框宽度
+    struct OutlineNote;
+    impl BoardObfuscator for OutlineNote {
+        fn name(&self) -> &'static str {
+            "板框注释"
+        }
+        fn edit(&self, layer: &Layer, context: &mut BoardContext) -> Result<Vec<Edit>> {
+            if !layer.file_type.is_silkscreen() {
+                return Ok(Vec::new());
+            }
+            let width = context.outline.map(|e| e.width().as_mm()).unwrap_or(0.0);
+            Ok(vec![Edit::insert(0, &format!("G04 width {:.1}*\n", width))])
+        }
+    }
+
+    let outline =
+        "%FSLAX45Y45*%\n%MOMM*%\nD10*\nX0Y0D02*\nX5000000Y0D01*\nX5000000Y3000000D01*\nM02*\n";
+    let mut board = Board::new(vec![
+        layer,
+        Layer::new(
+            "Edge.GKO",
+            GerberFileType::BoardOutline,
+            outline.to_string(),
+        ),
+    ]);
+    let options = ObfuscateOptions {
+        displacement: false,
+        silkscreen: false,
+        ..Default::default()
+    };
+    let mut pipeline = ObfuscationPipeline::with_seed(&options, &profile, Seed::new(3));
+    pipeline.push(Box::new(OutlineNote));
+    let applied = pipeline.process_board(&mut board).unwrap();
+    assert_eq!(applied[0], vec!["板框注释"]);
+    assert!(applied[1].is_empty());
+    assert!(board.layers[0].content.starts_with("G04 width 50.0*\n"));
+}
+
+#[test]
+fn test_dry_run_plan() {
+    let zip_path = Path::new(TEST_GERBER_ZIP);
+    if !zip_path.exists() {
+        return;
+    }
+
+    let dir = tempfile::TempDir::new().unwrap();
+    let request = |dry_run: bool| ProcessRequest {
+        input_path: zip_path.to_string_lossy().to_string(),
+        output_dir: Some(dir.path().to_string_lossy().to_string()),
+        seed: Some(7),
+        dry_run,
+        ..Default::default()
+    };
+
+    let plan = ghostpcb_lib::gerber::GerberProcessor::process(&request(true)).unwrap();
+    assert!(plan.output_files.is_empty());
+    assert_eq!(
+        std::fs::read_dir(dir.path()).unwrap().count(),
+        0,
+        "试运行不应创建任何文件"
+    );
+
+    let files = &plan.plan.as_ref().unwrap().files;
+    let silk = files
+        .iter()
+        .find(|f| f.file.ends_with(".GTO"))
+        .expect("应列出丝印层");
+    assert_eq!(silk.stages, vec!["跨层一致位移", "丝印层扰动"]);
+    assert!(files
+        .iter()
+        .any(|f| f.file.ends_with(".json") && !f.recognized));
+
+    // 预览与实际运行的结果一致
+    let real = ghostpcb_lib::gerber::GerberProcessor::process(&request(false)).unwrap();
+    let preview = &plan.reports[0];
+    let actual = &real.reports[0];
+    assert_eq!(preview.output_file, actual.output_file);
+    for layer in &actual.layers {
+        let planned = preview
+            .layers
+            .iter()
+            .find(|l| l.file == layer.file)
+            .unwrap();
+        assert_eq!(planned.transforms, layer.transforms);
+        assert_eq!(planned.max_displacement_mm, layer.max_displacement_mm);
+    }
+    assert_eq!(preview.validation.len(), actual.validation.len());
+}
+
+#[test]
+fn test_change_log_reverts_package() {
+    use ghostpcb_lib::gerber::changelog::ChangeLog;
+    use ghostpcb_lib::gerber::edit::{apply_edits, invert_edits, Edit};
+    use std::io::Read;
+
+    // 同一行的插入与替换、末尾追加都能撤销
+    let before = "a\r\nb\nc";
+    let edits = vec![
+        Edit::insert(1, "x\n"),
+        Edit::replace(1, "b\n", "B\n"),
+        Edit::remove(2, "c"),
+        Edit::insert(3, "d\n"),
+    ];
+    let after = apply_edits(before, &edits);
+    assert_eq!(after, "a\r\nx\nB\nd\n");
+    assert_eq!(apply_edits(&after, &invert_edits(&edits)), before);
+
+    let zip_path = Path::new(TEST_GERBER_ZIP);
+    if !zip_path.exists() {
+        return;
+    }
+
+    let dir = tempfile::TempDir::new().unwrap();
+    let request = ProcessRequest {
+        input_path: zip_path.to_string_lossy().to_string(),
+        output_dir: Some(dir.path().to_string_lossy().to_string()),
+        seed: Some(40),
+        ..Default::default()
+    };
+    let result = ghostpcb_lib::gerber::GerberProcessor::process(&request).unwrap();
+    let output = Path::new(&result.output_files[0]);
+
+    let log = ChangeLog::load(&ChangeLog::path_beside(output)).unwrap();
+    assert!(log.files.iter().any(|f| f.file.ends_with(".GTL")));
+
+    let restored = dir.path().join("restored.zip");
+    log.revert_package(output, &restored).unwrap();
+
+    let read_all = |path: &Path| {
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(path).unwrap()).unwrap();
+        let mut files = std::collections::BTreeMap::new();
+        for i in 0..archive.len() {
+            let mut entry = archive.by_index(i).unwrap();
+            if entry.is_dir() {
+                continue;
+            }
+            let mut bytes = Vec::new();
+            entry.read_to_end(&mut bytes).unwrap();
+            files.insert(entry.name().to_string(), bytes);
+        }
+        files
+    };
+    let original = read_all(zip_path);
+    assert_ne!(read_all(output), original);
+    assert_eq!(read_all(&restored), original, "还原后应与原始包逐字节一致");
+
+    // 记录与文件不符时拒绝还原
+    let content = "G04 changed*\n";
+    let gtl = log.files.iter().find(|f| f.file.ends_with(".GTL")).unwrap();
+    assert!(log.revert_file(&gtl.file, content).is_err());
+}
+
+#[test]
+fn test_variants_share_parsed_package() {
+    use ghostpcb_lib::gerber::package::Package;
+
+    let zip_path = Path::new(TEST_GERBER_ZIP);
+    if !zip_path.exists() {
+        return;
+    }
+
+    let input = Package::read_zip(zip_path).unwrap();
+    let board = input.board();
+    assert_eq!(
+        board.layers.len() + input.unrecognized().count(),
+        input.file_names().count()
+    );
+
+    let dir = tempfile::TempDir::new().unwrap();
+    let request = ProcessRequest {
+        input_path: zip_path.to_string_lossy().to_string(),
+        output_dir: Some(dir.path().to_string_lossy().to_string()),
+        count: 3,
+        seed: Some(41),
+        ..Default::default()
+    };
+    let result = ghostpcb_lib::gerber::GerberProcessor::process(&request).unwrap();
+    assert_eq!(result.output_files.len(), 3);
+
+    let outputs: Vec<Package> = result
+        .output_files
+        .iter()
+        .map(|path| Package::read_zip(Path::new(path)).unwrap())
+        .collect();
+    for output in &outputs {
+        // 条目顺序不变，未识别的文件逐字节保留
+        let names: Vec<&str> = output.entries.iter().map(|e| e.name.as_str()).collect();
+        let expected: Vec<&str> = input.entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, expected);
+        for (out, orig) in output.entries.iter().zip(&input.entries) {
+            if !orig.is_recognized() {
+                assert_eq!(out.data, orig.data, "{} 应原样保留", orig.name);
+            }
+        }
+    }
+
+    // 每个变体都从原始板生成，互不影响
+    let gtl = |package: &Package| {
+        package
+            .entries
+            .iter()
+            .find(|e| e.name.ends_with(".GTL"))
+            .unwrap()
+            .data
+            .clone()
+    };
+    assert_ne!(gtl(&outputs[0]), gtl(&outputs[1]));
+    assert_ne!(gtl(&outputs[1]), gtl(&outputs[2]));
+    for (output, path) in outputs.iter().zip(&result.output_files) {
+        let log = ghostpcb_lib::gerber::changelog::ChangeLog::load(
+            &ghostpcb_lib::gerber::changelog::ChangeLog::path_beside(Path::new(path)),
+        )
+        .unwrap();
+        let file = log.files.iter().find(|f| f.file.ends_with(".GTL")).unwrap();
+        let reverted = file
+            .revert(&String::from_utf8(gtl(output)).unwrap())
+            .unwrap();
+        assert_eq!(reverted.as_bytes(), gtl(&input).as_slice());
+    }
+}
+
+#[test]
+fn test_parallel_output_is_deterministic() {
+    let zip_path = Path::new(TEST_GERBER_ZIP);
+    if !zip_path.exists() {
+        return;
+    }
+
+    let run = |threads: usize| {
+        let dir = tempfile::TempDir::new().unwrap();
+        let request = ProcessRequest {
+            input_path: zip_path.to_string_lossy().to_string(),
+            output_dir: Some(dir.path().to_string_lossy().to_string()),
+            count: 4,
+            seed: Some(42),
+            threads: Some(threads),
+            ..Default::default()
+        };
+        let result = ghostpcb_lib::gerber::GerberProcessor::process(&request).unwrap();
+        let variants: Vec<_> = result
+            .output_files
+            .iter()
+            .map(|path| {
+                let package =
+                    ghostpcb_lib::gerber::package::Package::read_zip(Path::new(path)).unwrap();
+                let name = Path::new(path).file_name().unwrap().to_owned();
+                let contents: Vec<Vec<u8>> = package.entries.into_iter().map(|e| e.data).collect();
+                (name, contents)
+            })
+            .collect();
+        (variants, result.reports)
+    };
+
+    let (sequential, sequential_reports) = run(1);
+    let (parallel, parallel_reports) = run(4);
+    assert_eq!(sequential.len(), 4);
+    assert_eq!(sequential, parallel, "并行与顺序执行的输出应逐字节一致");
+    for (a, b) in sequential_reports.iter().zip(&parallel_reports) {
+        assert_eq!(a.variant, b.variant);
+        assert_eq!(a.layers.len(), b.layers.len());
+    }
+}
+
+#[test]
+fn test_progress_events_and_cancel() {
+    use ghostpcb_lib::gerber::error::GhostPcbError;
+    use ghostpcb_lib::gerber::progress::{CancelToken, ProgressEvent};
+    use std::sync::Mutex;
+
+    let zip_path = Path::new(TEST_GERBER_ZIP);
+    if !zip_path.exists() {
+        return;
+    }
+
+    let dir = tempfile::TempDir::new().unwrap();
+    let request = ProcessRequest {
+        input_path: zip_path.to_string_lossy().to_string(),
+        output_dir: Some(dir.path().to_string_lossy().to_string()),
+        count: 2,
+        seed: Some(43),
+        threads: Some(1),
+        job_id: Some("job-1".to_string()),
+        ..Default::default()
+    };
+
+    let events = Mutex::new(Vec::new());
+    let sink = |event: ProgressEvent| events.lock().unwrap().push(event);
+    let result =
+        ghostpcb_lib::gerber::GerberProcessor::process_with(&request, &sink, &CancelToken::new())
+            .unwrap();
+    let events = events.into_inner().unwrap();
+
+    let files = match &events[0] {
+        ProgressEvent::Started {
+            job_id, variants, ..
+        } => {
+            assert_eq!(job_id, "job-1");
+            assert_eq!(*variants, 2);
+            result.reports[0].layers.len()
+        }
+        other => panic!("第一个事件应为 Started: {:?}", other),
+    };
+    let count = |f: fn(&ProgressEvent) -> bool| events.iter().filter(|e| f(e)).count();
+    assert_eq!(
+        count(|e| matches!(e, ProgressEvent::File { .. })),
+        files * 2
+    );
+    assert_eq!(count(|e| matches!(e, ProgressEvent::Variant { .. })), 2);
+    assert!(count(|e| matches!(e, ProgressEvent::Warning { .. })) > 0);
+    assert!(matches!(
+        events.last(),
+        Some(ProgressEvent::Finished { success: true, .. })
+    ));
+
+    // 处理到第一个文件后取消：返回 Cancelled，并删除已写出的输出
+    let dir = tempfile::TempDir::new().unwrap();
+    let request = ProcessRequest {
+        output_dir: Some(dir.path().to_string_lossy().to_string()),
+        count: 3,
+        ..request
+    };
+    let cancel = CancelToken::new();
+    let finished = Mutex::new(None);
+    let sink = |event: ProgressEvent| match event {
+        ProgressEvent::Variant { .. } => cancel.cancel(),
+        ProgressEvent::Finished { success, .. } => *finished.lock().unwrap() = Some(success),
+        _ => {}
+    };
+    let error =
+        ghostpcb_lib::gerber::GerberProcessor::process_with(&request, &sink, &cancel).unwrap_err();
+    assert!(matches!(error, GhostPcbError::Cancelled));
+    assert_eq!(*finished.lock().unwrap(), Some(false));
+    assert_eq!(
+        std::fs::read_dir(dir.path()).unwrap().count(),
+        0,
+        "取消后不应留下输出"
+    );
+}
+
+#[test]
+fn test_unsafe_archives_are_rejected() {
+    use ghostpcb_lib::gerber::error::GhostPcbError;
+    use ghostpcb_lib::gerber::package::{ArchiveLimits, Package};
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+
+    let dir = tempfile::TempDir::new().unwrap();
+    let build = |name: &str, entries: &[(&str, &[u8])], symlink: Option<&str>| {
+        let path = dir.path().join(name);
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&path).unwrap());
+        for (entry, data) in entries {
+            zip.start_file(*entry, SimpleFileOptions::default())
+                .unwrap();
+            zip.write_all(data).unwrap();
+        }
+        if let Some(link) = symlink {
+            zip.add_symlink(link, "/etc/passwd", SimpleFileOptions::default())
+                .unwrap();
+        }
+        zip.finish().unwrap();
+        path
+    };
+    let rejected = |path: &Path, limits: &ArchiveLimits| {
+        matches!(
+            Package::read_zip_with(path, limits),
+            Err(GhostPcbError::UnsafeArchive(_))
+        )
+    };
+    let limits = ArchiveLimits::default();
+    let gerber: &[u8] = b"%FSLAX46Y46*%\n%MOMM*%\nM02*\n";
+
+    assert!(rejected(
+        &build("escape.zip", &[("../../evil.GTL", gerber)], None),
+        &limits
+    ));
+    assert!(rejected(
+        &build("absolute.zip", &[("/tmp/evil.GTL", gerber)], None),
+        &limits
+    ));
+    assert!(rejected(
+        &build("link.zip", &[("a.GTL", gerber)], Some("b.GTL")),
+        &limits
+    ));
+
+    let normal = build("normal.zip", &[("a.GTL", gerber), ("b.GBL", gerber)], None);
+    assert!(Package::read_zip_with(&normal, &limits).is_ok());
+    let few = ArchiveLimits {
+        max_entries: 1,
+        ..limits
+    };
+    assert!(rejected(&normal, &few));
+    let small = ArchiveLimits {
+        max_file_size: 8,
+        ..limits
+    };
+    assert!(rejected(&normal, &small));
+    let total = ArchiveLimits {
+        max_total_size: gerber.len() as u64 + 1,
+        ..limits
+    };
+    assert!(rejected(&normal, &total));
+
+    // 处理请求同样返回该错误
+    let request = ProcessRequest {
+        input_path: normal.to_string_lossy().to_string(),
+        output_dir: Some(dir.path().to_string_lossy().to_string()),
+        seed: Some(44),
+        dry_run: true,
+        archive_limits: small,
+        ..Default::default()
+    };
+    assert!(matches!(
+        ghostpcb_lib::gerber::GerberProcessor::process(&request),
+        Err(GhostPcbError::UnsafeArchive(_))
+    ));
+}
+
+#[test]
+fn test_failed_runs_roll_back_outputs() {
+    let zip_path = Path::new(TEST_GERBER_ZIP);
+    if !zip_path.exists() {
+        return;
+    }
+
+    let dir = tempfile::TempDir::new().unwrap();
+    let request = |dry_run: bool, rollback_on_error: bool| ProcessRequest {
+        input_path: zip_path.to_string_lossy().to_string(),
+        output_dir: Some(dir.path().to_string_lossy().to_string()),
+        count: 3,
+        html_report: true,
+        seed: Some(45),
+        dry_run,
+        threads: Some(1),
+        rollback_on_error,
+        ..Default::default()
+    };
+
+    // 在第二个输出包的位置放一个目录，使其无法写出
+    let plan = ghostpcb_lib::gerber::GerberProcessor::process(&request(true, true)).unwrap();
+    let blocked = Path::new(&plan.reports[1].output_file).to_path_buf();
+    let output_dir = blocked.parent().unwrap().to_path_buf();
+    std::fs::create_dir_all(&blocked).unwrap();
+
+    let files = || {
+        let mut names: Vec<String> = std::fs::read_dir(&output_dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        names
+    };
+    let blocked_name = blocked.file_name().unwrap().to_string_lossy().to_string();
+
+    assert!(ghostpcb_lib::gerber::GerberProcessor::process(&request(false, true)).is_err());
+    assert_eq!(files(), vec![blocked_name.clone()], "回滚后只剩原有内容");
+
+    // 关闭回滚时保留已完成的包，但不留下临时文件或半截的包
+    assert!(ghostpcb_lib::gerber::GerberProcessor::process(&request(false, false)).is_err());
+    let remaining = files();
+    assert!(remaining.iter().all(|name| !name.ends_with(".tmp")));
+    let first = &plan.reports[0].output_file;
+    assert!(Path::new(first).exists());
+    assert!(ghostpcb_lib::gerber::package::Package::read_zip(Path::new(first)).is_ok());
+    assert!(Path::new(first).with_extension("changes.json").exists());
+}
+
+#[test]
+fn test_unprocessable_files_pass_through() {
+    use ghostpcb_lib::gerber::package::Package;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+
+    let zip_path = Path::new(TEST_GERBER_ZIP);
+    if !zip_path.exists() {
+        return;
+    }
+
+    let dir = tempfile::TempDir::new().unwrap();
+    let input = dir.path().join("mixed.zip");
+    let broken: &[u8] = b"%FSLAX45Y45*%\nG54D10*\nG01X100Y0D01*\n";
+    let latin1: &[u8] = b"G04 caf\xe9*\n%FSLAX46Y46*%\n%MOMM*%\nM02*\n";
+    {
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&input).unwrap());
+        for entry in Package::read_zip(zip_path).unwrap().entries {
+            zip.start_file(entry.name.as_str(), SimpleFileOptions::default())
+                .unwrap();
+            zip.write_all(&entry.data).unwrap();
+        }
+        for (name, data) in [("extra/broken.GTP", broken), ("extra/latin.GBP", latin1)] {
+            zip.start_file(name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(data).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    let request = |strict: bool| ProcessRequest {
+        input_path: input.to_string_lossy().to_string(),
+        output_dir: Some(dir.path().to_string_lossy().to_string()),
+        seed: Some(46),
+        strict,
+        ..Default::default()
+    };
+
+    let result = ghostpcb_lib::gerber::GerberProcessor::process(&request(false)).unwrap();
+    assert_eq!(result.warnings.len(), 1, "{:?}", result.warnings);
+    assert!(result.warnings[0].contains("extra/broken.GTP"));
+    assert!(result.reports[0]
+        .warnings
+        .iter()
+        .any(|w| w.contains("extra/broken.GTP")));
+
+    let output = Package::read_zip(Path::new(&result.output_files[0])).unwrap();
+    let data = |name: &str| {
+        output
+            .entries
+            .iter()
+            .find(|e| e.name == name)
+            .unwrap()
+            .data
+            .clone()
+    };
+    assert_eq!(data("extra/broken.GTP"), broken);
+    // 非 UTF-8 的文件按字节处理，不再因编码被跳过
+    assert!(data("extra/latin.GBP").starts_with(b"G04 caf\xe9*\n"));
+    assert!(result.reports[0]
+        .layers
+        .iter()
+        .any(|l| l.file.ends_with(".GTL")));
+
+    // 严格模式保持中止
+    assert!(ghostpcb_lib::gerber::GerberProcessor::process(&request(true)).is_err());
+}
+
+#[test]
+fn test_non_utf8_files_keep_their_bytes() {
+    use ghostpcb_lib::gerber::changelog::ChangeLog;
+    use ghostpcb_lib::gerber::encoding::TextEncoding;
+    use ghostpcb_lib::gerber::package::Package;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+
+    // GBK 与 Latin-1 注释
+    let (text, encoding) = TextEncoding::detect(b"G04 \xc4\xe3\xba\xc3*\nM02*\n");
+    assert_eq!(encoding, TextEncoding::Bytes);
+    assert_eq!(
+        encoding.encode(&text).unwrap(),
+        b"G04 \xc4\xe3\xba\xc3*\nM02*\n"
+    );
+    assert_eq!(
+        TextEncoding::detect("G04 你好*".as_bytes()).1,
+        TextEncoding::Utf8
+    );
+    // 按字节映射的文件写不出 U+00FF 以上的字符，不混入 UTF-8
+    assert!(!encoding.can_encode("G04 你好*"));
+    assert!(encoding.encode("G04 你好*").is_err());
+
+    let zip_path = Path::new(TEST_GERBER_ZIP);
+    if !zip_path.exists() {
+        return;
+    }
+
+    // 在示例包的顶层丝印与底层铜中插入非 UTF-8 注释
+    let sample = Package::read_zip(zip_path).unwrap();
+    let dir = tempfile::TempDir::new().unwrap();
+    let input = dir.path().join("encoded.zip");
+    let mut originals = Vec::new();
+    {
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&input).unwrap());
+        for entry in &sample.entries {
+            let comment: &[u8] = if entry.name.ends_with(".GTO") {
+                b"G04 \xb6\xa5\xb2\xe3\xcb\xbf\xd3\xa1*\n"
+            } else if entry.name.ends_with(".GBL") {
+                b"G04 Fran\xe7ais*\n"
+            } else {
+                b""
+            };
+            let data = [comment, &entry.data].concat();
+            zip.start_file(entry.name.as_str(), SimpleFileOptions::default())
+                .unwrap();
+            zip.write_all(&data).unwrap();
+            if !comment.is_empty() {
+                originals.push((entry.name.clone(), comment.to_vec(), data));
+            }
+        }
+        zip.finish().unwrap();
+    }
+
+    let request = ProcessRequest {
+        input_path: input.to_string_lossy().to_string(),
+        output_dir: Some(dir.path().to_string_lossy().to_string()),
+        seed: Some(47),
+        strict: true,
+        ..Default::default()
+    };
+    let result = ghostpcb_lib::gerber::GerberProcessor::process(&request).unwrap();
+    assert!(result.warnings.is_empty());
+
+    let output_path = Path::new(&result.output_files[0]);
+    let output = Package::read_zip(output_path).unwrap();
+    for (name, comment, original) in &originals {
+        let data = &output
+            .entries
+            .iter()
+            .find(|e| &e.name == name)
+            .unwrap()
+            .data;
+        assert_ne!(data, original, "{} 应被处理", name);
+        assert!(
+            data.windows(comment.len()).any(|w| w == comment.as_slice()),
+            "{} 的注释字节应原样保留",
+            name
+        );
+        let report = result.reports[0]
+            .layers
+            .iter()
+            .find(|l| &l.file == name)
+            .unwrap();
+        assert!(report.coordinates_changed > 0);
+    }
+
+    // 修改记录同样可以逐字节还原
+    let log = ChangeLog::load(&ChangeLog::path_beside(output_path)).unwrap();
+    let restored = dir.path().join("restored.zip");
+    log.revert_package(output_path, &restored).unwrap();
+    let restored = Package::read_zip(&restored).unwrap();
+    for (name, _, original) in &originals {
+        let data = &restored
+            .entries
+            .iter()
+            .find(|e| &e.name == name)
+            .unwrap()
+            .data;
+        assert_eq!(data, original);
+    }
+}
+
+#[test]
+fn test_stages_keep_crlf_line_endings() {
+    use ghostpcb_lib::gerber::board::{Board, Layer};
+    use ghostpcb_lib::gerber::layout::{insert_line, split_lines, TextLayout};
+    use ghostpcb_lib::gerber::obfuscators::{
+        BoardTransform, CoherentJitterTransform, GeometryObfuscator, Obfuscator,
+        PhysicalObfuscator, SilkscreenObfuscator, StructureObfuscator, TimestampObfuscator,
+    };
+    use ghostpcb_lib::gerber::package::Package;
+    use ghostpcb_lib::gerber::seed::Seed;
+    use ghostpcb_lib::gerber::signature::{apply_lceda_signature, disguise_as_easyeda};
+    use std::collections::HashSet;
+
+    fn assert_crlf(stage: &str, content: &str) {
+        let bare = content
+            .match_indices('\n')
+            .filter(|(i, _)| !content[..*i].ends_with('\r'))
+            .count();
+        assert_eq!(bare, 0, "{} 写出了 {} 个 LF 换行", stage, bare);
+    }
+
+    // 插在没有换行的末行之后时，换行移到新行之前
+    let text = "D10*\r\nM02*";
+    let layout = TextLayout::detect(text);
+    assert_eq!(layout.eol, "\r\n");
+    let mut lines: Vec<String> = split_lines(text).into_iter().map(str::to_string).collect();
+    insert_line(&mut lines, 2, "G04 end*", layout);
+    assert_eq!(lines.concat(), "D10*\r\nM02*\r\nG04 end*");
+
+    let zip_path = Path::new(TEST_GERBER_ZIP);
+    if !zip_path.exists() {
+        return;
+    }
+
+    // 示例包转为 CRLF，并在一个钻孔坐标行尾加上空白
+    let mut board = Board::new(
+        Package::read_zip(zip_path)
+            .unwrap()
+            .board()
+            .layers
+            .into_iter()
+            .map(|l| Layer {
+                content: l.content.replace("\r\n", "\n").replace('\n', "\r\n"),
+                ..l
+            })
+            .collect(),
+    );
+    let drill = board
+        .layers
+        .iter_mut()
+        .find(|l| l.file_type.is_drill())
+        .unwrap();
+    let start = drill.content.find("\r\nX").unwrap() + 2;
+    let end = start + drill.content[start..].find('\r').unwrap();
+    let padded = format!("{}  ", &drill.content[start..end]);
+    drill.content.replace_range(start..end, &padded);
+
+    let stages: Vec<Box<dyn Obfuscator>> = vec![
+        Box::new(TimestampObfuscator::new()),
+        Box::new(SilkscreenObfuscator::new()),
+        Box::new(GeometryObfuscator::new()),
+        Box::new(StructureObfuscator::new()),
+        Box::new(PhysicalObfuscator::new()),
+    ];
+    let mut rng = Seed::new(48).stream("CRLF");
+    let mut changed = HashSet::new();
+    for layer in &board.layers {
+        for stage in &stages {
+            let output = stage
+                .obfuscate(&layer.content, layer.file_type, &mut rng)
+                .unwrap();
+            assert_crlf(&format!("{} ({})", stage.name(), layer.name), &output);
+            if output != layer.content {
+                changed.insert(stage.name());
+            }
+            if layer.content.contains(padded.as_str()) && stage.name() == "几何结构扰动" {
+                assert!(!output.contains(padded.as_str()), "钻孔坐标应被修改");
+                assert!(
+                    output
+                        .split("\r\n")
+                        .any(|l| l.starts_with('X') && l.ends_with("  ")),
+                    "改写后的坐标行应保留行尾空白"
+                );
+            }
+        }
+        if layer.file_type.is_drill() {
+            continue;
+        }
+
+        let disguised = disguise_as_easyeda(
+            &layer.content.replace("EasyEDA", "Other"),
+            &layer.name,
+            chrono::Local::now().naive_local(),
+        );
+        assert_crlf("EasyEDA 文件头伪装", &disguised);
+        let signed = apply_lceda_signature(&layer.content, false, &mut rng);
+        assert_ne!(signed, layer.content);
+        assert_crlf("立创签名", &signed);
+    }
+    assert_eq!(changed.len(), stages.len(), "每个阶段都应修改内容");
+
+    let mut jittered = board.clone();
+    CoherentJitterTransform::new()
+        .apply(&mut jittered, &mut rng)
+        .unwrap();
+    for (before, after) in board.layers.iter().zip(&jittered.layers) {
+        assert_crlf("跨层一致位移", &after.content);
+        assert_eq!(
+            before.content.matches("\r\n").count(),
+            after.content.matches("\r\n").count(),
+            "{} 的行数不应变化",
+            after.name
+        );
+    }
+}
+
+#[test]
+fn test_directory_tar_and_nested_zip_inputs() {
+    use ghostpcb_lib::gerber::error::GhostPcbError;
+    use ghostpcb_lib::gerber::package::{Package, PackageFormat};
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+
+    fn write_tar<W: Write>(package: &Package, writer: W) {
+        let mut tar = tar::Builder::new(writer);
+        for entry in &package.entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_mode(0o644);
+            header.set_size(entry.data.len() as u64);
+            tar.append_data(&mut header, &entry.name, entry.data.as_slice())
+                .unwrap();
+        }
+        tar.into_inner().unwrap();
+    }
+
+    // 路径越出包的 tar 条目被拒绝
+    let dir = tempfile::TempDir::new().unwrap();
+    let evil = dir.path().join("evil.tar");
+    {
+        let mut tar = tar::Builder::new(std::fs::File::create(&evil).unwrap());
+        let mut header = tar::Header::new_gnu();
+        header.as_old_mut().name[..11].copy_from_slice(b"../evil.GTL");
+        header.set_size(1);
+        header.set_cksum();
+        tar.append(&header, &b"x"[..]).unwrap();
+        tar.finish().unwrap();
+    }
+    assert!(matches!(
+        Package::open(&evil),
+        Err(GhostPcbError::UnsafeArchive(_))
+    ));
+
+    // 名称不是 UTF-8 的条目单独报错
+    let garbled = dir.path().join("garbled.tar");
+    {
+        let mut tar = tar::Builder::new(std::fs::File::create(&garbled).unwrap());
+        let mut header = tar::Header::new_gnu();
+        header.as_old_mut().name[..6].copy_from_slice(b"a\xff.GTL");
+        header.set_size(1);
+        header.set_cksum();
+        tar.append(&header, &b"x"[..]).unwrap();
+        tar.finish().unwrap();
+    }
+    assert!(matches!(
+        Package::open(&garbled),
+        Err(GhostPcbError::InvalidFileName(_))
+    ));
+
+    let zip_path = Path::new(TEST_GERBER_ZIP);
+    if !zip_path.exists() {
+        return;
+    }
+    let sample = Package::read_zip(zip_path).unwrap();
+    let mut names: Vec<&str> = sample.file_names().collect();
+    names.sort();
+
+    // 同一个包的四种转发形式
+    let folder = dir.path().join("folder");
+    for entry in &sample.entries {
+        let path = folder.join(&entry.name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, &entry.data).unwrap();
+    }
+    let plain_tar = dir.path().join("plain.tar");
+    write_tar(&sample, std::fs::File::create(&plain_tar).unwrap());
+    let gzipped = dir.path().join("gzipped.tar.gz");
+    write_tar(
+        &sample,
+        flate2::write::GzEncoder::new(
+            std::fs::File::create(&gzipped).unwrap(),
+            flate2::Compression::default(),
+        ),
+    );
+    // `tar -cf dotted.tar -C folder .` 的写法：先是 `./` 目录，条目都带 `./` 前缀
+    let dotted = dir.path().join("dotted.tar");
+    {
+        let mut tar = tar::Builder::new(std::fs::File::create(&dotted).unwrap());
+        let mut root = tar::Header::new_gnu();
+        root.as_old_mut().name[..2].copy_from_slice(b"./");
+        root.set_entry_type(tar::EntryType::Directory);
+        root.set_mode(0o755);
+        root.set_size(0);
+        root.set_cksum();
+        tar.append(&root, std::io::empty()).unwrap();
+        for entry in &sample.entries {
+            let name = format!("./{}", entry.name);
+            let mut header = tar::Header::new_gnu();
+            header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+            header.set_mode(0o644);
+            header.set_size(entry.data.len() as u64);
+            header.set_cksum();
+            tar.append(&header, entry.data.as_slice()).unwrap();
+        }
+        tar.finish().unwrap();
+    }
+    let nested = dir.path().join("forwarded.zip");
+    {
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&nested).unwrap());
+        zip.add_directory("from-fab/", SimpleFileOptions::default())
+            .unwrap();
+        zip.start_file("from-fab/Gerber.zip", SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(&std::fs::read(zip_path).unwrap()).unwrap();
+        zip.finish().unwrap();
+    }
+
+    let inputs = [
+        (folder, PackageFormat::Directory, ""),
+        (plain_tar, PackageFormat::Tar, ".tar"),
+        (dotted, PackageFormat::Tar, ".tar"),
+        (gzipped, PackageFormat::TarGz, ".tar.gz"),
+        (nested, PackageFormat::Zip, ".zip"),
+    ];
+    for (input, format, extension) in &inputs {
+        let package = Package::open(input).unwrap();
+        assert_eq!(package.format, *format);
+        let mut read: Vec<&str> = package.file_names().collect();
+        read.sort();
+        assert_eq!(read, names, "{:?} 应读成同样的条目", format);
+
+        // 缺省按输入的容器类型写出，报告与修改记录去掉包扩展名
+        let request = ProcessRequest {
+            input_path: input.to_string_lossy().to_string(),
+            output_dir: Some(dir.path().join("out").to_string_lossy().to_string()),
+            seed: Some(49),
+            ..Default::default()
+        };
+        let result = ghostpcb_lib::gerber::GerberProcessor::process(&request).unwrap();
+        let output = Path::new(&result.output_files[0]);
+        let file_name = output.file_name().unwrap().to_string_lossy().to_string();
+        assert!(file_name.ends_with(extension), "{}", file_name);
+        assert_eq!(output.is_dir(), *format == PackageFormat::Directory);
+        let stem = &file_name[..file_name.len() - extension.len()];
+        assert!(output
+            .with_file_name(format!("{}.report.json", stem))
+            .exists());
+
+        let written = Package::open(output).unwrap();
+        assert_eq!(written.format, *format);
+        let mut read: Vec<&str> = written.file_names().collect();
+        read.sort();
+        assert_eq!(read, names);
+
+        if *format == PackageFormat::Directory {
+            // 覆盖已有目录：旧内容被整体替换，不留下临时目录或备份
+            std::fs::write(output.join("stale.txt"), "old").unwrap();
+            written
+                .write_as(output, *format, &written.board(), &Default::default())
+                .unwrap();
+            assert!(!output.join("stale.txt").exists());
+            assert!(output.join(names[0]).exists());
+            let leftovers = std::fs::read_dir(output.parent().unwrap())
+                .unwrap()
+                .filter(|e| {
+                    let name = e.as_ref().unwrap().file_name();
+                    name.to_string_lossy().starts_with(".ghostpcb-")
+                })
+                .count();
+            assert_eq!(leftovers, 0);
+        }
+
+        // 修改记录按同样的容器类型还原
+        let log = ghostpcb_lib::gerber::changelog::ChangeLog::load(
+            &ghostpcb_lib::gerber::changelog::ChangeLog::path_beside(output),
+        )
+        .unwrap();
+        let restored = dir.path().join(format!("restored{}", extension));
+        log.revert_package(output, &restored).unwrap();
+        let restored = Package::open(&restored).unwrap();
+        for entry in sample.entries.iter().filter(|e| e.is_recognized()) {
+            let data = &restored
+                .entries
+                .iter()
+                .find(|e| e.name == entry.name)
+                .unwrap()
+                .data;
+            assert_eq!(data, &entry.data, "{} 应逐字节还原", entry.name);
+        }
+    }
+
+    // 显式指定输出类型：目录输入写成 ZIP
+    let request = ProcessRequest {
+        input_path: inputs[0].0.to_string_lossy().to_string(),
+        output_dir: Some(dir.path().join("as-zip").to_string_lossy().to_string()),
+        seed: Some(49),
+        output_format: Some(PackageFormat::Zip),
+        ..Default::default()
+    };
+    let result = ghostpcb_lib::gerber::GerberProcessor::process(&request).unwrap();
+    assert!(result.output_files[0].ends_with(".zip"));
+    assert_eq!(
+        Package::open(Path::new(&result.output_files[0]))
+            .unwrap()
+            .format,
+        PackageFormat::Zip
+    );
+}
+
+#[test]
+fn test_repacking_preserves_or_normalizes_entry_metadata() {
+    use ghostpcb_lib::gerber::changelog::ChangeLog;
+    use ghostpcb_lib::gerber::package::{Package, PackageFormat, RepackMode, RepackOptions};
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+    use zip::{CompressionMethod, DateTime, ZipArchive};
+
+    /// (名称, 压缩方式, 修改时间, 权限)
+    type Listing = Vec<(String, CompressionMethod, Option<DateTime>, Option<u32>)>;
+    fn listing(path: &Path) -> (Listing, Vec<u8>) {
+        let mut archive = ZipArchive::new(std::fs::File::open(path).unwrap()).unwrap();
+        let entries = (0..archive.len())
+            .map(|i| {
+                let entry = archive.by_index(i).unwrap();
+                (
+                    entry.name().to_string(),
+                    entry.compression(),
+                    entry.last_modified(),
+                    entry.unix_mode().map(|m| m & 0o777),
+                )
+            })
+            .collect();
+        (entries, archive.comment().to_vec())
+    }
+
+    let zip_path = Path::new(TEST_GERBER_ZIP);
+    if !zip_path.exists() {
+        return;
+    }
+
+    // 倒序写入示例包，轮流使用 Stored、Deflated 与 Bzip2，带时间、权限与包注释
+    let sample = Package::read_zip(zip_path).unwrap();
+    let dir = tempfile::TempDir::new().unwrap();
+    let input = dir.path().join("annotated.zip");
+    {
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&input).unwrap());
+        let time = DateTime::from_date_and_time(2021, 3, 4, 5, 6, 8).unwrap();
+        for (i, entry) in sample.entries.iter().rev().enumerate() {
+            let method = match i % 3 {
+                0 => CompressionMethod::Stored,
+                1 => CompressionMethod::Deflated,
+                _ => CompressionMethod::Bzip2,
+            };
+            let options = SimpleFileOptions::default()
+                .compression_method(method)
+                .last_modified_time(time)
+                .unix_permissions(if i == 1 { 0o600 } else { 0o644 });
+            zip.start_file(entry.name.as_str(), options).unwrap();
+            zip.write_all(&entry.data).unwrap();
+        }
+        zip.set_comment("来自工厂的包注释");
+        zip.finish().unwrap();
+    }
+
+    // zip 库不能写条目注释，直接在中央目录的第一项补上
+    {
+        let mut data = std::fs::read(&input).unwrap();
+        let comment = b"top copper";
+        let u16_at = |d: &[u8], p: usize| u16::from_le_bytes([d[p], d[p + 1]]) as usize;
+        let u32_at =
+            |d: &[u8], p: usize| u32::from_le_bytes([d[p], d[p + 1], d[p + 2], d[p + 3]]) as usize;
+        let end = data.len() - 22 - "来自工厂的包注释".len();
+        let start = u32_at(&data, end + 16);
+        let size = u32_at(&data, end + 12) + comment.len();
+        data[end + 12..end + 16].copy_from_slice(&(size as u32).to_le_bytes());
+        data[start + 32..start + 34].copy_from_slice(&(comment.len() as u16).to_le_bytes());
+        let at = start + 46 + u16_at(&data, start + 28) + u16_at(&data, start + 30);
+        data.splice(at..at, comment.iter().copied());
+        std::fs::write(&input, data).unwrap();
+    }
+    let package = Package::read_zip(&input).unwrap();
+    let commented = package.entries[0].name.clone();
+    assert_eq!(package.entries[0].meta.comment, "top copper");
+
+    // DOS/FAT 写入的条目没有权限位，重新打包时不补上
+    let dos = dir.path().join("dos.zip");
+    {
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&dos).unwrap());
+        zip.start_file("board.GTL", SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(&sample.entries[0].data).unwrap();
+        zip.finish().unwrap();
+    }
+    {
+        let mut data = std::fs::read(&dos).unwrap();
+        let start = data.len() - 22;
+        let start = u32::from_le_bytes(data[start + 16..start + 20].try_into().unwrap()) as usize;
+        // 中央目录中“创建系统”字节：0 为 MS-DOS
+        data[start + 5] = 0;
+        std::fs::write(&dos, data).unwrap();
+    }
+    assert_eq!(
+        Package::read_zip(&dos).unwrap().entries[0].meta.unix_mode,
+        None
+    );
+
+    let (original, archive_comment) = listing(&input);
+    assert_eq!(archive_comment, "来自工厂的包注释".as_bytes());
+    assert_eq!(original[1].3, Some(0o600));
+
+    let request = |repack: RepackOptions, out: &str| ProcessRequest {
+        input_path: input.to_string_lossy().to_string(),
+        output_dir: Some(dir.path().join(out).to_string_lossy().to_string()),
+        seed: Some(50),
+        repack,
+        ..Default::default()
+    };
+
+    // 缺省保留顺序、压缩方式、时间、权限与包注释，只有内容不同
+    let result =
+        ghostpcb_lib::gerber::GerberProcessor::process(&request(Default::default(), "kept"))
+            .unwrap();
+    let output = Path::new(&result.output_files[0]);
+    let (kept, kept_comment) = listing(output);
+    assert_eq!(kept, original);
+    assert_eq!(kept_comment, archive_comment);
+    assert!(kept.iter().any(|e| e.1 == CompressionMethod::Bzip2));
+    // 条目注释无法写回，在报告中说明
+    assert!(
+        result.reports[0]
+            .warnings
+            .contains(&format!("条目注释无法写回，已丢弃: {}", commented)),
+        "{:?}",
+        result.reports[0].warnings
+    );
+
+    // 只能读取的压缩方式改用 Deflate，并列出这些条目
+    let mut legacy = Package::read_zip(&input).unwrap();
+    legacy.entries[2].meta.compression = Some(CompressionMethod::Deflate64);
+    let preserve = RepackOptions::default();
+    assert_eq!(
+        legacy
+            .recompressed(PackageFormat::Zip, &preserve)
+            .collect::<Vec<_>>(),
+        vec![(
+            legacy.entries[2].name.as_str(),
+            CompressionMethod::Deflate64
+        )]
+    );
+    assert_eq!(
+        legacy.recompressed(PackageFormat::Tar, &preserve).count(),
+        0
+    );
+
+    // 还原后的包同样保留元数据
+    let restored = dir.path().join("restored.zip");
+    ChangeLog::load(&ChangeLog::path_beside(output))
+        .unwrap()
+        .revert_package(output, &restored)
+        .unwrap();
+    assert_eq!(listing(&restored), (original.clone(), archive_comment));
+
+    // 规范化：按名称排序、不压缩、固定时间、去掉包注释
+    let normalize = RepackOptions {
+        mode: RepackMode::Normalize,
+        sort_entries: true,
+        compress: false,
+        compression_level: None,
+    };
+    let result =
+        ghostpcb_lib::gerber::GerberProcessor::process(&request(normalize, "normalized")).unwrap();
+    let (normalized, normalized_comment) = listing(Path::new(&result.output_files[0]));
+    let mut names: Vec<String> = original.iter().map(|e| e.0.clone()).collect();
+    names.sort();
+    assert_eq!(
+        normalized.iter().map(|e| e.0.clone()).collect::<Vec<_>>(),
+        names
+    );
+    for (name, method, modified, _) in &normalized {
+        assert_eq!(*method, CompressionMethod::Stored, "{}", name);
+        assert_eq!(*modified, Some(DateTime::default()), "{}", name);
+    }
+    assert!(normalized_comment.is_empty());
+
+    // 没有修改时间的条目写成 tar 时取固定时间，不读时钟
+    let mut untimed = Package::read_zip(&input).unwrap();
+    for entry in &mut untimed.entries {
+        entry.meta.modified = None;
+    }
+    let mut tarball = Vec::new();
+    untimed
+        .write_tar_to(
+            &mut tarball,
+            &ghostpcb_lib::gerber::board::Board::default(),
+            &RepackOptions::default(),
+        )
+        .unwrap();
+    for entry in tar::Archive::new(tarball.as_slice()).entries().unwrap() {
+        assert_eq!(entry.unwrap().header().mtime().unwrap(), 315_532_800);
+    }
+}
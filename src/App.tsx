@@ -1,5 +1,6 @@
 import { useState, useEffect } from "react";
 import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
 import { open } from "@tauri-apps/plugin-dialog";
 import { getCurrentWebviewWindow } from "@tauri-apps/api/webviewWindow";
 import { openUrl } from "@tauri-apps/plugin-opener";
@@ -9,6 +10,7 @@ import "./App.css";
 import {
   ProcessRequest,
   ProcessResult,
+  ProgressEvent,
   defaultOptions,
 } from "./types";
 
@@ -21,6 +23,8 @@ function App() {
   const [countInput, setCountInput] = useState("1");
   const [outputDir, setOutputDir] = useState<string | null>(null);
   const [processing, setProcessing] = useState(false);
+  const [jobId, setJobId] = useState<string | null>(null);
+  const [progress, setProgress] = useState({ done: 0, total: 0 });
   const [status, setStatus] = useState<{
     type: "idle" | "success" | "error";
     message: string;
@@ -94,11 +98,11 @@ function App() {
         const paths = event.payload.paths;
         if (paths && paths.length > 0) {
           const file = paths[0];
-          if (file.toLowerCase().endsWith(".zip")) {
+          if (isSupportedInput(file)) {
             setInputFile(file);
             setStatus({ type: "idle", message: "" });
           } else {
-            setStatus({ type: "error", message: "请选择 ZIP 文件" });
+            setStatus({ type: "error", message: "请选择 ZIP、tar 包或文件夹" });
           }
         }
       } else if (event.payload.type === "leave") {
@@ -112,7 +116,9 @@ function App() {
 
   const selectFile = async () => {
     const selected = await open({
-      filters: [{ name: "Gerber ZIP", extensions: ["zip"] }],
+      filters: [
+        { name: "Gerber 压缩包", extensions: ["zip", "tar", "gz", "tgz"] },
+      ],
       multiple: false,
     });
     if (selected) {
@@ -121,6 +127,14 @@ function App() {
     }
   };
 
+  const selectInputDir = async () => {
+    const selected = await open({ directory: true });
+    if (selected) {
+      setInputFile(selected as string);
+      setStatus({ type: "idle", message: "" });
+    }
+  };
+
   const selectOutputDir = async () => {
     const selected = await open({ directory: true });
     if (selected) {
@@ -133,14 +147,27 @@ function App() {
       setStatus({ type: "error", message: "请先选择 Gerber 文件" });
       return;
     }
+    const id = crypto.randomUUID();
+    setJobId(id);
+    setProgress({ done: 0, total: 0 });
     setProcessing(true);
     setStatus({ type: "idle", message: "" });
+    const unlisten = await listen<ProgressEvent>("gerber-progress", (event) => {
+      const e = event.payload;
+      if (e.job_id !== id) return;
+      if (e.kind === "started") {
+        setProgress({ done: 0, total: e.variants * e.files });
+      } else if (e.kind === "file") {
+        setProgress((p) => ({ ...p, done: p.done + 1 }));
+      }
+    });
     try {
       const request: ProcessRequest = {
         input_path: inputFile,
         output_dir: outputDir,
         count,
         options: defaultOptions,
+        job_id: id,
       };
       const result = await invoke<ProcessResult>("process_gerber", { request });
       if (result.success) {
@@ -154,12 +181,29 @@ function App() {
     } catch (e) {
       setStatus({ type: "error", message: String(e) });
     } finally {
+      unlisten();
+      setJobId(null);
       setProcessing(false);
     }
   };
 
+  const handleCancel = async () => {
+    if (jobId) {
+      await invoke<boolean>("cancel_job", { jobId });
+    }
+  };
+
   const getFileName = (path: string) => path.split(/[/\\]/).pop() || path;
 
+  /** 压缩包按扩展名判断，没有扩展名的视为文件夹 */
+  const isSupportedInput = (path: string) => {
+    const name = (path.split(/[/\\]/).pop() || path).toLowerCase();
+    return (
+      [".zip", ".tar", ".tar.gz", ".tgz"].some((ext) => name.endsWith(ext)) ||
+      !name.includes(".")
+    );
+  };
+
   return (
     <div className="app">
       <header className="header">
@@ -250,10 +294,13 @@ function App() {
               ) : (
                 <div className="drop-hint">
                   <span className="drop-icon">📁</span>
-                  <span>点击选择或拖拽 ZIP 文件</span>
+                  <span>点击选择或拖拽 ZIP、tar 包或文件夹</span>
                 </div>
               )}
             </div>
+            <button className="btn-folder" onClick={selectInputDir}>
+              选择文件夹
+            </button>
           </section>
 
           <section className="card settings-card">
@@ -342,13 +389,21 @@ function App() {
             {processing ? (
               <>
                 <span className="spinner"></span>
-                处理中...
+                处理中
+                {progress.total > 0 &&
+                  ` ${Math.round((progress.done / progress.total) * 100)}%`}
               </>
             ) : (
               <>开始处理</>
             )}
           </button>
 
+          {processing && (
+            <button className="btn-cancel" onClick={handleCancel}>
+              取消
+            </button>
+          )}
+
           {status.message && (
             <div className={`status-bar ${status.type}`}>
               <span className="status-icon">
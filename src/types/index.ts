@@ -1,9 +1,81 @@
 export interface ObfuscateOptions {
+  displacement: boolean;
   timestamp: boolean;
   silkscreen: boolean;
   geometry: boolean;
   structure: boolean;
   physical: boolean;
+  params: StageParams;
+  /** 按层覆盖开关与参数，后面的规则优先 */
+  rules: LayerRule[];
+}
+
+export type LayerClass =
+  | "Copper"
+  | "OuterCopper"
+  | "InnerCopper"
+  | "Silkscreen"
+  | "SolderMask"
+  | "Paste"
+  | "Outline"
+  | "Drill"
+  | "Document";
+
+/** 未给出的开关沿用之前的设置 */
+export interface StageToggles {
+  displacement?: boolean | null;
+  timestamp?: boolean | null;
+  silkscreen?: boolean | null;
+  geometry?: boolean | null;
+  structure?: boolean | null;
+  physical?: boolean | null;
+}
+
+export interface LayerRule {
+  layers: LayerClass[];
+  /** 文件名通配符，如 "*.GKO" */
+  files: string[];
+  stages: StageToggles;
+  params?: StageParamsPatch | null;
+}
+
+/** 规则中的参数只覆盖给出的字段，其余沿用之前的设置 */
+export type StageParamsPatch = {
+  [Stage in keyof StageParams]?: Partial<StageParams[Stage]>;
+};
+
+/** 位移类参数为 null 时取工艺配置中的容差 */
+export interface StageParams {
+  displacement: {
+    amplitude_mm: number | null;
+    min_wavelength_mm: number;
+  };
+  physical: {
+    offset_min_mm: number | null;
+    offset_max_mm: number | null;
+    layers: GerberFileType[];
+  };
+  geometry: {
+    drill_jitter_mm: number | null;
+    copper_jitter_mm: number | null;
+    layers: GerberFileType[];
+  };
+  silkscreen: {
+    shift_min_mm: number;
+    shift_max_mm: number;
+    layers: GerberFileType[];
+  };
+  structure: {
+    build_id_comment: boolean;
+    redundant_dcode_probability: number;
+    layers: GerberFileType[];
+  };
+  timestamp: {
+    days_ago_min: number;
+    days_ago_max: number;
+    hour_start: number;
+    hour_end: number;
+  };
 }
 
 export interface ProcessRequest {
@@ -11,18 +83,239 @@ export interface ProcessRequest {
   output_dir: string | null;
   count: number;
   options: ObfuscateOptions;
+  profile?: string | null;
+  html_report?: boolean;
+  /** 随机种子，相同种子可重新生成相同的输出 */
+  seed?: number | null;
+  /** 试运行：只返回计划，不写出任何文件 */
+  dry_run?: boolean;
+  /** 并行生成的最大线程数，缺省为 CPU 核数 */
+  threads?: number | null;
+  /** 任务 ID，用于进度事件与取消 */
+  job_id?: string | null;
+  /** 读取输入包时的条目数与解压大小限制 */
+  archive_limits?: ArchiveLimits;
+  /** 出错时删除本次已生成的所有输出，默认开启 */
+  rollback_on_error?: boolean;
+  /** 严格模式：任一文件无法处理即中止 */
+  strict?: boolean;
+  /** 输出的容器类型，缺省与输入相同 */
+  output_format?: PackageFormat | null;
+  /** 输出包的打包方式，缺省保留输入的条目元数据 */
+  repack?: RepackOptions;
+  /** 作为“现在”的时间 (如 "2025-03-14T09:26:53")，缺省取本机当前时间 */
+  reference_time?: string | null;
+}
+
+/** 输入包的容器类型 */
+export type PackageFormat = "zip" | "tar" | "tar_gz" | "directory";
+
+/** preserve 保留原有的顺序、压缩方式、时间、权限与包注释（条目注释无法写回，报告中警告）；normalize 统一打包 */
+export type RepackMode = "preserve" | "normalize";
+
+/** 打包选项，sort_entries 等只在 normalize 时生效 */
+export interface RepackOptions {
+  mode: RepackMode;
+  /** 按名称排序条目 */
+  sort_entries: boolean;
+  /** 关闭时 ZIP 条目以 Stored 方式存储 */
+  compress: boolean;
+  /** Deflate 压缩级别 */
+  compression_level?: number | null;
+}
+
+export interface ArchiveLimits {
+  max_entries: number;
+  /** 单个文件解压后的最大字节数 */
+  max_file_size: number;
+  /** 所有文件解压后的总字节数上限 */
+  max_total_size: number;
+}
+
+export type Severity = "error" | "warning";
+
+export interface ValidationIssue {
+  file: string;
+  severity: Severity;
+  code: string;
+  message: string;
+}
+
+export interface Diagnostic {
+  file: string;
+  line: number;
+  severity: Severity;
+  code: string;
+  message: string;
+}
+
+export type GerberFileType =
+  | "TopLayer"
+  | "BottomLayer"
+  | "TopSilkscreen"
+  | "BottomSilkscreen"
+  | "TopSolderMask"
+  | "BottomSolderMask"
+  | "TopPaste"
+  | "BottomPaste"
+  | "BoardOutline"
+  | "InnerLayer"
+  | "Document"
+  | "Drill"
+  | "Unknown";
+
+export interface LayerReport {
+  file: string;
+  file_type: GerberFileType;
+  transforms: string[];
+  coordinates_total: number;
+  coordinates_changed: number;
+  max_displacement_mm: number;
+}
+
+export interface PackageReport {
+  output_file: string;
+  profile: string;
+  seed: number;
+  variant: number;
+  /** 本次处理的基准时间，与种子一起传回即可重现该包 */
+  reference_time: string;
+  layers: LayerReport[];
+  validation: ValidationIssue[];
+  warnings: string[];
 }
 
 export interface ProcessResult {
   success: boolean;
   output_files: string[];
   message: string;
+  seed: number;
+  obfuscators: string[];
+  validation: ValidationIssue[];
+  diagnostics: Diagnostic[];
+  /** 无法处理、原样保留的文件 */
+  warnings: string[];
+  reports: PackageReport[];
+  plan: ProcessPlan | null;
+}
+
+export interface PlannedFile {
+  file: string;
+  file_type: GerberFileType;
+  recognized: boolean;
+  stages: string[];
+}
+
+export interface ProcessPlan {
+  output_dir: string;
+  files: PlannedFile[];
+}
+
+export interface PositionTolerance {
+  feature_jitter_mm: number;
+  outline_jitter_mm: number;
+  drill_jitter_mm: number;
+  outline_offset_min_mm: number;
+  outline_offset_max_mm: number;
+}
+
+export interface CapabilityProfile {
+  name: string;
+  description: string;
+  min_trace_mm: number;
+  min_space_mm: number;
+  min_drill_mm: number;
+  min_annular_ring_mm: number;
+  min_silk_width_mm: number;
+  edge_clearance_mm: number;
+  max_board_width_mm: number;
+  max_board_height_mm: number;
+  tolerance: PositionTolerance;
 }
 
+export const defaultStageParams: StageParams = {
+  displacement: { amplitude_mm: null, min_wavelength_mm: 10 },
+  physical: {
+    offset_min_mm: null,
+    offset_max_mm: null,
+    layers: ["BoardOutline"],
+  },
+  geometry: {
+    drill_jitter_mm: null,
+    copper_jitter_mm: null,
+    layers: ["TopLayer", "BottomLayer", "InnerLayer", "Drill"],
+  },
+  silkscreen: {
+    shift_min_mm: 0.01,
+    shift_max_mm: 0.03,
+    layers: ["TopSilkscreen", "BottomSilkscreen"],
+  },
+  structure: {
+    build_id_comment: true,
+    redundant_dcode_probability: 0.05,
+    layers: [
+      "TopLayer",
+      "BottomLayer",
+      "TopSilkscreen",
+      "BottomSilkscreen",
+      "TopSolderMask",
+      "BottomSolderMask",
+      "TopPaste",
+      "BottomPaste",
+      "BoardOutline",
+      "InnerLayer",
+      "Document",
+    ],
+  },
+  timestamp: { days_ago_min: 1, days_ago_max: 29, hour_start: 8, hour_end: 18 },
+};
+
 export const defaultOptions: ObfuscateOptions = {
+  displacement: true,
   timestamp: false,
   silkscreen: true,
   geometry: false,
   structure: false,
   physical: false,
+  params: defaultStageParams,
+  rules: [],
 };
+
+/** 保存在应用配置目录中的命名预设 */
+export interface Preset {
+  schema_version: number;
+  name: string;
+  description: string;
+  profile: string | null;
+  options: ObfuscateOptions;
+}
+
+/** 对文件某一行的修改，行文本包含换行符 */
+export type EditAction =
+  | { Insert: string }
+  | { Remove: string }
+  | { Replace: { old: string; new: string } };
+
+export interface Edit {
+  line: number;
+  action: EditAction;
+}
+
+/** 输出包旁 `<包名>.changes.json` 中的修改记录 */
+export interface ChangeLog {
+  output_file: string;
+  files: {
+    file: string;
+    /** Bytes: 非 UTF-8 文件，按字节逐个映射 */
+    encoding: "Utf8" | "Bytes";
+    stages: { stage: string; edits: Edit[] }[];
+  }[];
+}
+
+/** 后端通过 `gerber-progress` 事件发出的处理进度 */
+export type ProgressEvent =
+  | { kind: "started"; job_id: string; variants: number; files: number }
+  | { kind: "file"; job_id: string; variant: number; file: string }
+  | { kind: "variant"; job_id: string; variant: number; output_file: string | null }
+  | { kind: "warning"; job_id: string; message: string }
+  | { kind: "finished"; job_id: string; success: boolean; message: string };